@@ -0,0 +1,340 @@
+pub const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+const TRIANGLE_SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15,
+];
+
+/// Volume envelope shared by the pulse and noise channels.
+#[derive(Default)]
+pub struct Envelope {
+    pub start: bool,
+    pub looping: bool,
+    pub constant_volume: bool,
+    pub volume: u8,
+    divider: u8,
+    decay: u8,
+}
+
+impl Envelope {
+    pub fn write(&mut self, data: u8) {
+        self.looping = data & 0b0010_0000 != 0;
+        self.constant_volume = data & 0b0001_0000 != 0;
+        self.volume = data & 0b1111;
+    }
+
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant_volume {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+}
+
+/// # Pulse channel $4000-$4007 https://www.nesdev.org/wiki/APU_Pulse
+pub struct Pulse {
+    pub enabled: bool,
+    pub length_counter: u8,
+    pub envelope: Envelope,
+    duty: u8,
+    sequence_pos: u8,
+    timer_period: u16,
+    timer: u16,
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_reload: bool,
+    sweep_divider: u8,
+    // pulse 1 negates with one's complement, pulse 2 with two's complement
+    ones_complement: bool,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Self {
+        Pulse {
+            enabled: false,
+            length_counter: 0,
+            envelope: Envelope::default(),
+            duty: 0,
+            sequence_pos: 0,
+            timer_period: 0,
+            timer: 0,
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_reload: false,
+            sweep_divider: 0,
+            ones_complement,
+        }
+    }
+
+    pub fn write(&mut self, register: u16, data: u8) {
+        match register {
+            0 => {
+                self.duty = data >> 6;
+                self.envelope.write(data);
+            }
+            1 => {
+                self.sweep_enabled = data & 0b1000_0000 != 0;
+                self.sweep_period = (data >> 4) & 0b111;
+                self.sweep_negate = data & 0b1000 != 0;
+                self.sweep_shift = data & 0b111;
+                self.sweep_reload = true;
+            }
+            2 => {
+                self.timer_period = (self.timer_period & 0xFF00) | data as u16;
+            }
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | (((data & 0b111) as u16) << 8);
+                if self.enabled {
+                    self.length_counter = LENGTH_TABLE[(data >> 3) as usize];
+                }
+                self.sequence_pos = 0;
+                self.envelope.start = true;
+            }
+        }
+    }
+
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence_pos = (self.sequence_pos + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if !self.envelope.looping && self.length_counter > 0 {
+            self.length_counter -= 1;
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+        if self.sweep_negate {
+            let change = if self.ones_complement {
+                change + 1
+            } else {
+                change
+            };
+            self.timer_period.saturating_sub(change)
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0
+            && self.sweep_enabled
+            && self.sweep_shift > 0
+            && !self.is_sweep_muting()
+        {
+            self.timer_period = self.sweep_target();
+        }
+
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    fn is_sweep_muting(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target() > 0x7FF
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.enabled
+            || self.length_counter == 0
+            || self.is_sweep_muting()
+            || DUTY_TABLE[self.duty as usize][self.sequence_pos as usize] == 0
+        {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+/// # Triangle channel $4008-$400B https://www.nesdev.org/wiki/APU_Triangle
+#[derive(Default)]
+pub struct Triangle {
+    pub enabled: bool,
+    pub length_counter: u8,
+    control: bool,
+    linear_reload_value: u8,
+    linear_counter: u8,
+    linear_reload: bool,
+    timer_period: u16,
+    timer: u16,
+    sequence_pos: u8,
+}
+
+impl Triangle {
+    pub fn write(&mut self, register: u16, data: u8) {
+        match register {
+            0 => {
+                self.control = data & 0b1000_0000 != 0;
+                self.linear_reload_value = data & 0b0111_1111;
+            }
+            1 => {}
+            2 => {
+                self.timer_period = (self.timer_period & 0xFF00) | data as u16;
+            }
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | (((data & 0b111) as u16) << 8);
+                if self.enabled {
+                    self.length_counter = LENGTH_TABLE[(data >> 3) as usize];
+                }
+                self.linear_reload = true;
+            }
+        }
+    }
+
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.length_counter > 0 && self.linear_counter > 0 {
+                self.sequence_pos = (self.sequence_pos + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_linear(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if !self.control && self.length_counter > 0 {
+            self.length_counter -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        // ultrasonic periods are silenced instead of aliasing
+        if !self.enabled || self.timer_period < 2 {
+            0
+        } else {
+            TRIANGLE_SEQUENCE[self.sequence_pos as usize]
+        }
+    }
+}
+
+/// # Noise channel $400C-$400F https://www.nesdev.org/wiki/APU_Noise
+pub struct Noise {
+    pub enabled: bool,
+    pub length_counter: u8,
+    pub envelope: Envelope,
+    period_table: &'static [u16; 16],
+    mode: bool,
+    timer_period: u16,
+    period_index: usize,
+    timer: u16,
+    shift_register: u16,
+}
+
+impl Noise {
+    pub fn new(period_table: &'static [u16; 16]) -> Self {
+        Noise {
+            enabled: false,
+            length_counter: 0,
+            envelope: Envelope::default(),
+            period_table,
+            mode: false,
+            timer_period: period_table[0],
+            period_index: 0,
+            timer: 0,
+            shift_register: 1,
+        }
+    }
+
+    pub fn set_period_table(&mut self, period_table: &'static [u16; 16]) {
+        self.period_table = period_table;
+        self.timer_period = period_table[self.period_index];
+    }
+
+    pub fn write(&mut self, register: u16, data: u8) {
+        match register {
+            0 => self.envelope.write(data),
+            1 => {}
+            2 => {
+                self.mode = data & 0b1000_0000 != 0;
+                self.period_index = (data & 0b1111) as usize;
+                self.timer_period = self.period_table[self.period_index];
+            }
+            _ => {
+                if self.enabled {
+                    self.length_counter = LENGTH_TABLE[(data >> 3) as usize];
+                }
+                self.envelope.start = true;
+            }
+        }
+    }
+
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            let tap = if self.mode { 6 } else { 1 };
+            let feedback = (self.shift_register & 1) ^ ((self.shift_register >> tap) & 1);
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if !self.envelope.looping && self.length_counter > 0 {
+            self.length_counter -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.enabled || self.length_counter == 0 || self.shift_register & 1 == 1 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
@@ -0,0 +1,249 @@
+pub mod channels;
+
+use channels::Noise;
+use channels::Pulse;
+use channels::Triangle;
+
+use crate::nes::region::Region;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+/// # 2A03 audio processing unit https://www.nesdev.org/wiki/APU
+///
+/// Pulse, triangle and noise are synthesised; the DMC only supports the
+/// $4011 direct load since the APU has no access to CPU memory.
+pub struct Apu {
+    pub pulse1: Pulse,
+    pub pulse2: Pulse,
+    pub triangle: Triangle,
+    pub noise: Noise,
+    pub dmc_output: u8,
+    /// DMC timer period in CPU cycles, as selected by $4010.
+    pub dmc_period: u16,
+    dmc_rates: &'static [u16; 16],
+    // https://www.nesdev.org/wiki/APU_Frame_Counter
+    frame_steps: &'static [u32; 5],
+    five_step_mode: bool,
+    irq_inhibit: bool,
+    pub frame_irq: bool,
+    frame_cycle: u32,
+    odd_cycle: bool,
+    cpu_clock: f64,
+    sample_rate: u32,
+    sample_accumulator: f64,
+    samples: Vec<f32>,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Apu {
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::default(),
+            noise: Noise::new(Region::Ntsc.noise_periods()),
+            dmc_output: 0,
+            dmc_period: Region::Ntsc.dmc_rates()[0],
+            dmc_rates: Region::Ntsc.dmc_rates(),
+            frame_steps: Region::Ntsc.apu_frame_steps(),
+            five_step_mode: false,
+            irq_inhibit: false,
+            frame_irq: false,
+            frame_cycle: 0,
+            odd_cycle: false,
+            cpu_clock: Region::Ntsc.cpu_clock(),
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            sample_accumulator: 0.0,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Switches clock rate and rate tables, register contents are kept.
+    pub fn set_region(&mut self, region: Region) {
+        self.cpu_clock = region.cpu_clock();
+        self.frame_steps = region.apu_frame_steps();
+        self.noise.set_period_table(region.noise_periods());
+        self.dmc_rates = region.dmc_rates();
+    }
+
+    /// Reset line: silences every channel like a $4015 write of 0.
+    pub fn reset(&mut self) {
+        self.write_register(0x4015, 0);
+        self.frame_irq = false;
+        self.frame_cycle = 0;
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4000..=0x4003 => self.pulse1.write(addr - 0x4000, data),
+            0x4004..=0x4007 => self.pulse2.write(addr - 0x4004, data),
+            0x4008..=0x400B => self.triangle.write(addr - 0x4008, data),
+            0x400C..=0x400F => self.noise.write(addr - 0x400C, data),
+            0x4011 => self.dmc_output = data & 0b0111_1111,
+            0x4010 => self.dmc_period = self.dmc_rates[(data & 0b1111) as usize],
+            0x4012 | 0x4013 => {}
+            0x4015 => {
+                self.pulse1.enabled = data & 0b0001 != 0;
+                self.pulse2.enabled = data & 0b0010 != 0;
+                self.triangle.enabled = data & 0b0100 != 0;
+                self.noise.enabled = data & 0b1000 != 0;
+                if !self.pulse1.enabled {
+                    self.pulse1.length_counter = 0;
+                }
+                if !self.pulse2.enabled {
+                    self.pulse2.length_counter = 0;
+                }
+                if !self.triangle.enabled {
+                    self.triangle.length_counter = 0;
+                }
+                if !self.noise.enabled {
+                    self.noise.length_counter = 0;
+                }
+            }
+            0x4017 => {
+                self.five_step_mode = data & 0b1000_0000 != 0;
+                self.irq_inhibit = data & 0b0100_0000 != 0;
+                if self.irq_inhibit {
+                    self.frame_irq = false;
+                }
+                self.frame_cycle = 0;
+                if self.five_step_mode {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// $4015 read, acknowledges the frame interrupt.
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.frame_irq = false;
+        status
+    }
+
+    pub fn peek_status(&self) -> u8 {
+        let mut status = 0;
+        if self.pulse1.length_counter > 0 {
+            status |= 0b0001;
+        }
+        if self.pulse2.length_counter > 0 {
+            status |= 0b0010;
+        }
+        if self.triangle.length_counter > 0 {
+            status |= 0b0100;
+        }
+        if self.noise.length_counter > 0 {
+            status |= 0b1000;
+        }
+        if self.frame_irq {
+            status |= 0b0100_0000;
+        }
+        status
+    }
+
+    /// Advances the APU by the given number of CPU cycles.
+    pub fn tick(&mut self, cpu_cycles: u16) {
+        for _ in 0..cpu_cycles {
+            self.tick_cycle();
+        }
+    }
+
+    fn tick_cycle(&mut self) {
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        if self.odd_cycle {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.odd_cycle = !self.odd_cycle;
+
+        self.frame_cycle += 1;
+        self.clock_frame_counter();
+
+        self.sample_accumulator += self.sample_rate as f64;
+        if self.sample_accumulator >= self.cpu_clock {
+            self.sample_accumulator -= self.cpu_clock;
+            self.samples.push(self.output());
+        }
+    }
+
+    fn clock_frame_counter(&mut self) {
+        let steps = self.frame_steps;
+
+        if self.frame_cycle == steps[0] || self.frame_cycle == steps[2] {
+            self.clock_quarter_frame();
+        } else if self.frame_cycle == steps[1] {
+            self.clock_quarter_frame();
+            self.clock_half_frame();
+        } else if !self.five_step_mode && self.frame_cycle == steps[3] {
+            self.clock_quarter_frame();
+            self.clock_half_frame();
+            if !self.irq_inhibit {
+                self.frame_irq = true;
+            }
+        } else if !self.five_step_mode && self.frame_cycle > steps[3] {
+            self.frame_cycle = 0;
+        } else if self.five_step_mode && self.frame_cycle == steps[4] {
+            self.clock_quarter_frame();
+            self.clock_half_frame();
+        } else if self.five_step_mode && self.frame_cycle > steps[4] {
+            self.frame_cycle = 0;
+        }
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse1.envelope.clock();
+        self.pulse2.envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_linear();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse1.clock_length();
+        self.pulse1.clock_sweep();
+        self.pulse2.clock_length();
+        self.pulse2.clock_sweep();
+        self.triangle.clock_length();
+        self.noise.clock_length();
+    }
+
+    /// Non-linear mixer, https://www.nesdev.org/wiki/APU_Mixer
+    pub fn output(&self) -> f32 {
+        let pulse = (self.pulse1.output() + self.pulse2.output()) as f32;
+        let pulse_out = if pulse == 0.0 {
+            0.0
+        } else {
+            95.88 / (8128.0 / pulse + 100.0)
+        };
+
+        let tnd = self.triangle.output() as f32 / 8227.0
+            + self.noise.output() as f32 / 12241.0
+            + self.dmc_output as f32 / 22638.0;
+        let tnd_out = if tnd == 0.0 {
+            0.0
+        } else {
+            159.79 / (1.0 / tnd + 100.0)
+        };
+
+        pulse_out + tnd_out
+    }
+
+    /// Samples produced since the last call, in the 0.0..1.0 range.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+}
+
+#[cfg(test)]
+mod test;
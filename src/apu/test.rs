@@ -0,0 +1,115 @@
+use super::*;
+
+#[test]
+fn test_length_counter_status() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4015, 0b0001);
+    apu.write_register(0x4003, 0b0000_1000); // length index 1 -> 254
+
+    assert_eq!(apu.read_status() & 0b0001, 1);
+    assert_eq!(apu.pulse1.length_counter, 254);
+
+    apu.write_register(0x4015, 0);
+    assert_eq!(apu.read_status() & 0b0001, 0);
+}
+
+#[test]
+fn test_length_counter_ignored_when_disabled() {
+    let mut apu = Apu::new();
+    apu.write_register(0x400F, 0b0000_1000);
+
+    assert_eq!(apu.noise.length_counter, 0);
+}
+
+#[test]
+fn test_frame_irq_four_step() {
+    let mut apu = Apu::new();
+
+    apu.tick(29828);
+    assert!(!apu.frame_irq);
+
+    apu.tick(1);
+    assert_eq!(apu.peek_status() & 0b0100_0000, 0b0100_0000);
+    assert_eq!(apu.read_status() & 0b0100_0000, 0b0100_0000);
+    assert!(!apu.frame_irq);
+}
+
+#[test]
+fn test_frame_irq_inhibit() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4017, 0b0100_0000);
+
+    apu.tick(30000);
+    assert!(!apu.frame_irq);
+}
+
+#[test]
+fn test_half_frame_clocks_length() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4015, 0b0010);
+    apu.write_register(0x4007, 0b0000_1000);
+
+    apu.tick(14913);
+    assert_eq!(apu.pulse2.length_counter, 253);
+}
+
+#[test]
+fn test_sample_rate() {
+    let mut apu = Apu::new();
+
+    apu.tick(29781);
+    let samples = apu.take_samples();
+
+    // one NTSC frame of audio at 44.1kHz
+    assert_eq!(samples.len(), 733);
+    assert!(apu.take_samples().is_empty());
+}
+
+#[test]
+fn test_pulse_produces_sound() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4015, 0b0001);
+    apu.write_register(0x4000, 0b1011_1111); // 50% duty, constant volume 15
+    apu.write_register(0x4002, 0xFD);
+    apu.write_register(0x4003, 0b0000_1000);
+
+    apu.tick(10000);
+    let samples = apu.take_samples();
+
+    assert!(samples.iter().any(|&s| s > 0.1));
+    assert!(samples.contains(&0.0));
+}
+
+#[test]
+fn test_pal_frame_counter() {
+    let mut apu = Apu::new();
+    apu.set_region(Region::Pal);
+
+    apu.tick(33252);
+    assert!(!apu.frame_irq);
+    apu.tick(1);
+    assert!(apu.frame_irq);
+}
+
+#[test]
+fn test_pal_sample_rate() {
+    let mut apu = Apu::new();
+    apu.set_region(Region::Pal);
+
+    apu.tick(33248);
+    let samples = apu.take_samples();
+
+    // one PAL frame of audio at 44.1kHz
+    assert_eq!(samples.len(), 881);
+}
+
+#[test]
+fn test_dmc_rate_table() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4010, 0x0F);
+    assert_eq!(apu.dmc_period, 54);
+
+    apu.set_region(Region::Pal);
+    apu.write_register(0x4010, 0x0F);
+    assert_eq!(apu.dmc_period, 50);
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+/// Expression evaluator for operands and directives.
+///
+/// Numbers are `$FF` hex, `%1010` binary, `255` decimal or `'c'` characters.
+/// `*` on its own is the address of the current statement. Operators from
+/// lowest to highest precedence:
+///
+/// ```text
+///  ||    &&    == != < <= > >=    |    ^    &    << >>    + -    * / %    unary - ~ < >
+/// ```
+///
+/// Unary `<` and `>` take the low and high byte of a word. Comparisons and
+/// logical operators give 1 or 0.
+///
+/// Returns `Ok(None)` when the expression uses a symbol that is not defined
+/// (yet), which the first pass treats as a forward reference.
+pub fn evaluate(
+    text: &str,
+    symbols: &HashMap<String, i64>,
+    pc: u16,
+) -> Result<Option<i64>, String> {
+    let tokens = tokenize(text)?;
+    if tokens.is_empty() {
+        return Err("missing expression".to_string());
+    }
+
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        symbols,
+        pc,
+    };
+    let value = parser.binary(0)?;
+
+    match parser.peek() {
+        None => Ok(value),
+        Some(token) => Err(format!("unexpected {:?} in '{}'", token, text)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Symbol(String),
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+// two character operators first so they win over their prefixes
+const OPERATORS: [&str; 19] = [
+    "<<", ">>", "||", "&&", "==", "!=", "<=", ">=", "|", "^", "&", "+", "-", "*", "/", "%", "~",
+    "<", ">",
+];
+
+// binary operators grouped by precedence, lowest first
+const PRECEDENCE: [&[&str]; 9] = [
+    &["||"],
+    &["&&"],
+    &["==", "!=", "<", "<=", ">", ">="],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '$' || c == '%' && expects_value(&tokens) && i + 1 < chars.len() {
+            let radix = if c == '$' { 16 } else { 2 };
+            i += 1;
+            while i < chars.len() && chars[i].is_digit(radix) {
+                i += 1;
+            }
+            let digits: String = chars[start + 1..i].iter().collect();
+            let value = i64::from_str_radix(&digits, radix)
+                .map_err(|_| format!("invalid number '{}{}'", c, digits))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                i += 1;
+            }
+            let digits: String = chars[start..i].iter().collect();
+            let value = digits
+                .parse()
+                .map_err(|_| format!("invalid number '{}'", digits))?;
+            tokens.push(Token::Number(value));
+        } else if c == '\'' {
+            if i + 2 >= chars.len() || chars[i + 2] != '\'' {
+                return Err(format!("invalid character literal in '{}'", text));
+            }
+            tokens.push(Token::Number(chars[i + 1] as i64));
+            i += 3;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Symbol(chars[start..i].iter().collect()));
+        } else if c == '(' {
+            tokens.push(Token::Open);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::Close);
+            i += 1;
+        } else {
+            let rest: String = chars[i..].iter().collect();
+            let operator = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .ok_or_else(|| format!("unexpected '{}' in '{}'", c, text))?;
+            tokens.push(Token::Operator(operator));
+            i += operator.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+// `%` starts a binary number where a value is expected, otherwise it is modulo
+fn expects_value(tokens: &[Token]) -> bool {
+    matches!(
+        tokens.last(),
+        None | Some(Token::Operator(_)) | Some(Token::Open)
+    )
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    symbols: &'a HashMap<String, i64>,
+    pc: u16,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Option<i64>, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Operator(op)) = self.peek() {
+            let op = *op;
+            if !PRECEDENCE[level].contains(&op) {
+                break;
+            }
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+
+            left = match (left, right) {
+                (Some(l), Some(r)) => Some(apply(op, l, r)?),
+                _ => None,
+            };
+        }
+
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Option<i64>, String> {
+        match self.peek() {
+            Some(Token::Operator(op)) if ["-", "~", "<", ">"].contains(op) => {
+                let op = *op;
+                self.position += 1;
+                let value = match self.unary()? {
+                    Some(value) => value,
+                    None => return Ok(None),
+                };
+                Ok(Some(match op {
+                    "-" => value.checked_neg().ok_or("overflow in negation")?,
+                    "~" => !value,
+                    "<" => value & 0xFF,
+                    _ => (value >> 8) & 0xFF,
+                }))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Option<i64>, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Some(value)),
+            Some(Token::Symbol(name)) => Ok(self.symbols.get(&name).copied()),
+            Some(Token::Operator("*")) => Ok(Some(self.pc as i64)),
+            Some(Token::Open) => {
+                let value = self.binary(0)?;
+                match self.next() {
+                    Some(Token::Close) => Ok(value),
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            Some(token) => Err(format!("unexpected {:?}", token)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn apply(op: &str, l: i64, r: i64) -> Result<i64, String> {
+    Ok(match op {
+        "||" => (l != 0 || r != 0) as i64,
+        "&&" => (l != 0 && r != 0) as i64,
+        "==" => (l == r) as i64,
+        "!=" => (l != r) as i64,
+        "<" => (l < r) as i64,
+        "<=" => (l <= r) as i64,
+        ">" => (l > r) as i64,
+        ">=" => (l >= r) as i64,
+        "|" => l | r,
+        "^" => l ^ r,
+        "&" => l & r,
+        "<<" => l.checked_shl(r as u32).unwrap_or(0),
+        ">>" => l.checked_shr(r as u32).unwrap_or(0),
+        "+" => l.wrapping_add(r),
+        "-" => l.wrapping_sub(r),
+        "*" => l.wrapping_mul(r),
+        "/" | "%" if r == 0 => return Err("division by zero".to_string()),
+        "/" => l.checked_div(r).ok_or("overflow in division")?,
+        _ => l.checked_rem(r).ok_or("overflow in division")?,
+    })
+}
@@ -0,0 +1,466 @@
+pub mod expr;
+
+use std::collections::HashMap;
+
+use crate::helper::AddressingMode;
+use crate::helper::EmmulationHelpers;
+use crate::helper::OpCode;
+use crate::helper::OpCodeCat;
+
+/// Where code goes until the first `.org`, same as `CPU::load`.
+pub const DEFAULT_ORIGIN: u16 = 0x8000;
+
+/// Assembled code as one block starting at `origin`, gaps between `.org`
+/// sections are zero filled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+    pub symbols: HashMap<String, i64>,
+}
+
+impl Program {
+    /// Address of a label, or value of a constant.
+    pub fn symbol(&self, name: &str) -> Option<u16> {
+        self.symbols.get(name).map(|&value| value as u16)
+    }
+}
+
+/// Two-pass assembler for the instructions known to `EmmulationHelpers`.
+///
+/// ```text
+///         .org $8000
+/// COUNT = 5
+/// start:  LDX #COUNT      ; comments run to the end of the line
+/// loop:   DEX
+///         BNE loop
+///         LDA table,X
+///         JMP (vector)
+/// table:  .byte 1, 2, %11, 'a'
+/// vector: .word start
+/// ```
+///
+/// Operands pick zero page over absolute addressing when their value is
+/// known in the first pass and fits in a byte. Errors name the line.
+pub fn assemble(source: &str) -> Result<Program, String> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(i, text)| parse_line(text).map_err(|e| format!("line {}: {}", i + 1, e)))
+        .collect::<Result<Vec<Line>, String>>()?;
+
+    // first pass: sizes, addressing modes and symbol values
+    let mut symbols = HashMap::new();
+    let mut opcodes = Vec::with_capacity(lines.len());
+    let mut pc = DEFAULT_ORIGIN as u32;
+    for (i, line) in lines.iter().enumerate() {
+        let opcode = first_pass(line, &mut symbols, &mut pc)
+            .map_err(|e| format!("line {}: {}", i + 1, e))?;
+        opcodes.push(opcode);
+    }
+
+    // second pass: every symbol is known, emit bytes
+    let mut memory: Vec<Option<u8>> = vec![None; 0x10000];
+    let mut pc = DEFAULT_ORIGIN as u32;
+    for (i, (line, opcode)) in lines.iter().zip(opcodes).enumerate() {
+        second_pass(line, opcode, &symbols, &mut pc, &mut memory)
+            .map_err(|e| format!("line {}: {}", i + 1, e))?;
+    }
+
+    let first = memory.iter().position(Option::is_some);
+    let last = memory.iter().rposition(Option::is_some);
+    let (origin, bytes) = match (first, last) {
+        (Some(first), Some(last)) => (
+            first as u16,
+            memory[first..=last]
+                .iter()
+                .map(|b| b.unwrap_or(0))
+                .collect(),
+        ),
+        _ => (DEFAULT_ORIGIN, Vec::new()),
+    };
+
+    Ok(Program {
+        origin,
+        bytes,
+        symbols,
+    })
+}
+
+/// Assembles one source line per string literal into a `Vec<u8>`, panicking
+/// with the assembler error otherwise. Meant for tests:
+///
+/// ```text
+/// cpu.load_and_run(assemble!("LDA #$0A", "TAX", "BRK"));
+/// ```
+///
+/// The bytes are meant for `CPU::load`, so code that `.org` places anywhere
+/// but `DEFAULT_ORIGIN` panics too; use `assemble` and `Program::origin`
+/// for that.
+#[macro_export]
+macro_rules! assemble {
+    ($($line:literal),* $(,)?) => {
+        match $crate::asm::assemble(concat!($($line, "\n"),*)) {
+            Ok(program) if program.origin == $crate::asm::DEFAULT_ORIGIN => program.bytes,
+            Ok(program) => panic!(
+                "assemble! code starts at ${:04X}, CPU::load puts it at ${:04X}",
+                program.origin,
+                $crate::asm::DEFAULT_ORIGIN
+            ),
+            Err(message) => panic!("{}", message),
+        }
+    };
+}
+
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(String),
+    Direct(String),
+    DirectX(String),
+    DirectY(String),
+    Indirect(String),
+    IndirectX(String),
+    IndirectY(String),
+}
+
+impl Operand {
+    fn expression(&self) -> Option<&str> {
+        match self {
+            Operand::None | Operand::Accumulator => None,
+            Operand::Immediate(e)
+            | Operand::Direct(e)
+            | Operand::DirectX(e)
+            | Operand::DirectY(e)
+            | Operand::Indirect(e)
+            | Operand::IndirectX(e)
+            | Operand::IndirectY(e) => Some(e),
+        }
+    }
+}
+
+enum Statement {
+    Empty,
+    Constant(String, String),
+    Org(String),
+    Byte(Vec<String>),
+    Word(Vec<String>),
+    Instruction(String, Operand),
+}
+
+struct Line {
+    label: Option<String>,
+    statement: Statement,
+}
+
+fn parse_line(text: &str) -> Result<Line, String> {
+    let mut rest = strip_comment(text).trim();
+    let mut label = None;
+
+    if let Some((name, after)) = rest.split_once(':') {
+        if is_identifier(name.trim()) {
+            label = Some(name.trim().to_string());
+            rest = after.trim();
+        }
+    }
+
+    let constant = rest
+        .split_once('=')
+        .filter(|(name, _)| is_identifier(name.trim()));
+
+    let statement = if rest.is_empty() {
+        Statement::Empty
+    } else if rest.starts_with('.') {
+        let (directive, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        match directive.to_ascii_lowercase().as_str() {
+            ".org" => Statement::Org(args.trim().to_string()),
+            ".byte" | ".db" => Statement::Byte(split_args(args)),
+            ".word" | ".dw" => Statement::Word(split_args(args)),
+            _ => return Err(format!("unknown directive '{}'", directive)),
+        }
+    } else if let Some((name, value)) = constant {
+        Statement::Constant(name.trim().to_string(), value.trim().to_string())
+    } else {
+        let (mnemonic, operand) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        Statement::Instruction(mnemonic.to_ascii_uppercase(), parse_operand(operand))
+    };
+
+    Ok(Line { label, statement })
+}
+
+fn parse_operand(text: &str) -> Operand {
+    let text = compact(text);
+    let upper = text.to_ascii_uppercase();
+    let inner = |start: usize, end: usize| text[start..text.len() - end].to_string();
+
+    if text.is_empty() {
+        Operand::None
+    } else if upper == "A" {
+        Operand::Accumulator
+    } else if text.starts_with('#') {
+        Operand::Immediate(inner(1, 0))
+    } else if upper.starts_with('(') && upper.ends_with(",X)") {
+        Operand::IndirectX(inner(1, 3))
+    } else if upper.ends_with("),Y") && closing_paren(&text) == Some(text.len() - 3) {
+        Operand::IndirectY(inner(1, 3))
+    } else if closing_paren(&text) == Some(text.len() - 1) {
+        Operand::Indirect(inner(1, 1))
+    } else if upper.ends_with(",X") {
+        Operand::DirectX(inner(0, 2))
+    } else if upper.ends_with(",Y") {
+        Operand::DirectY(inner(0, 2))
+    } else {
+        Operand::Direct(inner(0, 0))
+    }
+}
+
+/// Position of the `)` matching a leading `(`.
+fn closing_paren(text: &str) -> Option<usize> {
+    if !text.starts_with('(') {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// the only quoted things are 'c' literals, which may hold ';', ',' or ' '
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            ';' if !quoted => return &text[..i],
+            _ => {}
+        }
+    }
+    text
+}
+
+fn compact(text: &str) -> String {
+    let mut quoted = false;
+    text.chars()
+        .filter(|&c| {
+            if c == '\'' {
+                quoted = !quoted;
+            }
+            quoted || !c.is_whitespace()
+        })
+        .collect()
+}
+
+fn split_args(text: &str) -> Vec<String> {
+    let mut args = vec![String::new()];
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '\'' => {
+                quoted = !quoted;
+                args.last_mut().unwrap().push(c);
+            }
+            ',' if !quoted => args.push(String::new()),
+            _ => args.last_mut().unwrap().push(c),
+        }
+    }
+    args.iter().map(|arg| arg.trim().to_string()).collect()
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn define(symbols: &mut HashMap<String, i64>, name: &str, value: i64) -> Result<(), String> {
+    if symbols.insert(name.to_string(), value).is_some() {
+        return Err(format!("'{}' is already defined", name));
+    }
+    Ok(())
+}
+
+fn first_pass(
+    line: &Line,
+    symbols: &mut HashMap<String, i64>,
+    pc: &mut u32,
+) -> Result<Option<OpCode>, String> {
+    if let Some(label) = &line.label {
+        define(symbols, label, *pc as i64)?;
+    }
+
+    let mut opcode = None;
+    let size = match &line.statement {
+        Statement::Empty => 0,
+        Statement::Constant(name, text) => {
+            let value = expr::evaluate(text, symbols, *pc as u16)?
+                .ok_or_else(|| format!("'{}' uses a symbol defined later", name))?;
+            define(symbols, name, value)?;
+            0
+        }
+        Statement::Org(text) => {
+            let value = expr::evaluate(text, symbols, *pc as u16)?
+                .ok_or(".org uses a symbol defined later")?;
+            if !(0..=0xFFFF).contains(&value) {
+                return Err(format!(".org ${:X} is outside the address space", value));
+            }
+            *pc = value as u32;
+            0
+        }
+        Statement::Byte(args) => args.len() as u32,
+        Statement::Word(args) => 2 * args.len() as u32,
+        Statement::Instruction(mnemonic, operand) => {
+            let value = match operand.expression() {
+                Some(text) => expr::evaluate(text, symbols, *pc as u16)?,
+                None => None,
+            };
+            let found = select_opcode(mnemonic, operand, value)?;
+            opcode = Some(found);
+            found.bytes as u32
+        }
+    };
+
+    *pc += size;
+    if *pc > 0x10000 {
+        return Err("code runs past $FFFF".to_string());
+    }
+
+    Ok(opcode)
+}
+
+fn second_pass(
+    line: &Line,
+    opcode: Option<OpCode>,
+    symbols: &HashMap<String, i64>,
+    pc: &mut u32,
+    memory: &mut [Option<u8>],
+) -> Result<(), String> {
+    let address = *pc as u16;
+    let evaluate = |text: &str| {
+        expr::evaluate(text, symbols, address)?
+            .ok_or_else(|| format!("undefined symbol in '{}'", text))
+    };
+
+    let mut bytes = Vec::new();
+    match &line.statement {
+        Statement::Empty | Statement::Constant(..) => {}
+        Statement::Org(text) => *pc = evaluate(text)? as u32,
+        Statement::Byte(args) => {
+            for arg in args {
+                bytes.push(to_byte(evaluate(arg)?, true)?);
+            }
+        }
+        Statement::Word(args) => {
+            for arg in args {
+                bytes.extend(to_word(evaluate(arg)?)?.to_le_bytes());
+            }
+        }
+        Statement::Instruction(_, operand) => {
+            let opcode = opcode.expect("first pass selects an opcode");
+            bytes.push(opcode.code);
+
+            if let Some(text) = operand.expression() {
+                let value = evaluate(text)?;
+                match opcode.mode {
+                    AddressingMode::Relative => {
+                        let offset = value - (address as i64 + 2);
+                        if !(-128..=127).contains(&offset) {
+                            return Err(format!("branch target ${:04X} out of range", value));
+                        }
+                        bytes.push(offset as u8);
+                    }
+                    AddressingMode::Immediate => bytes.push(to_byte(value, true)?),
+                    _ if opcode.bytes == 2 => bytes.push(to_byte(value, false)?),
+                    _ => bytes.extend(to_word(value)?.to_le_bytes()),
+                }
+            }
+        }
+    }
+
+    for byte in bytes {
+        if memory[*pc as usize].replace(byte).is_some() {
+            return Err(format!("${:04X} is assembled twice", *pc));
+        }
+        *pc += 1;
+    }
+
+    Ok(())
+}
+
+fn to_byte(value: i64, signed: bool) -> Result<u8, String> {
+    let min = if signed { -128 } else { 0 };
+    if !(min..=0xFF).contains(&value) {
+        return Err(format!("{} does not fit in a byte", value));
+    }
+    Ok(value as u8)
+}
+
+fn to_word(value: i64) -> Result<u16, String> {
+    if !(-0x8000..=0xFFFF).contains(&value) {
+        return Err(format!("{} does not fit in a word", value));
+    }
+    Ok(value as u16)
+}
+
+fn find_opcode(mnemonic: &str, mode: AddressingMode) -> Option<OpCode> {
+    (0..=0xFF)
+        .map(EmmulationHelpers::get_op_code_struct)
+        .find(|op| {
+            !matches!(op.match_code, OpCodeCat::NONE) && op.code_name == mnemonic && op.mode == mode
+        })
+}
+
+/// Picks the opcode for `operand`, `value` is the operand when already known.
+fn select_opcode(mnemonic: &str, operand: &Operand, value: Option<i64>) -> Result<OpCode, String> {
+    use AddressingMode::*;
+
+    let fits_zero_page = matches!(value, Some(0..=0xFF));
+    let zero_page_first = |zero_page, absolute| {
+        if fits_zero_page {
+            vec![zero_page, absolute]
+        } else {
+            vec![absolute, zero_page]
+        }
+    };
+
+    let candidates = match operand {
+        Operand::None => vec![Implied, NoneAddressing, Accumulator],
+        Operand::Accumulator => vec![Accumulator],
+        Operand::Immediate(_) => vec![Immediate],
+        Operand::Indirect(_) => vec![Indirect],
+        Operand::IndirectX(_) => vec![Indirect_X],
+        Operand::IndirectY(_) => vec![Indirect_Y],
+        Operand::Direct(_) => {
+            let mut modes = vec![Relative];
+            modes.extend(zero_page_first(ZeroPage, Absolute));
+            modes
+        }
+        Operand::DirectX(_) => zero_page_first(ZeroPage_X, Absolute_X),
+        Operand::DirectY(_) => zero_page_first(ZeroPage_Y, Absolute_Y),
+    };
+
+    candidates
+        .into_iter()
+        .find_map(|mode| find_opcode(mnemonic, mode))
+        .ok_or_else(|| {
+            if (0..=0xFF)
+                .any(|code| EmmulationHelpers::get_op_code_struct(code).code_name == mnemonic)
+            {
+                format!("{} does not support this addressing mode", mnemonic)
+            } else {
+                format!("unknown instruction '{}'", mnemonic)
+            }
+        })
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,165 @@
+use super::*;
+use crate::cpu::CPU;
+use crate::disasm;
+
+fn bytes(source: &str) -> Vec<u8> {
+    assemble(source).unwrap().bytes
+}
+
+#[test]
+fn test_addressing_modes() {
+    let program = bytes(
+        "
+        LDA #$0A
+        LDA $20
+        LDA $20,X
+        LDX $20,Y
+        LDA $1234
+        LDA $1234,X
+        LDA $1234,Y
+        JMP ($FFFC)
+        LDA ($20,X)
+        LDA ($20),Y
+        ASL A
+        LSR
+        TAX
+        BRK
+        ",
+    );
+
+    assert_eq!(
+        program,
+        vec![
+            0xa9, 0x0a, 0xa5, 0x20, 0xb5, 0x20, 0xb6, 0x20, 0xad, 0x34, 0x12, 0xbd, 0x34, 0x12,
+            0xb9, 0x34, 0x12, 0x6c, 0xfc, 0xff, 0xa1, 0x20, 0xb1, 0x20, 0x0a, 0x4a, 0xaa, 0x00,
+        ]
+    );
+}
+
+#[test]
+fn test_round_trip_through_disassembler() {
+    let source = "LDA ($20),Y\nBNE $8010\nSTA $0200,X\nCPY #$FF";
+    let program = assemble(source).unwrap();
+
+    let text: Vec<String> = disasm::disassemble(&program.bytes, program.origin)
+        .iter()
+        .map(|i| i.text())
+        .collect();
+
+    assert_eq!(text.join("\n"), source);
+}
+
+#[test]
+fn test_labels_and_branches() {
+    let program = assemble(
+        "
+        start:  LDX #5
+        loop:   DEX
+                BNE loop
+                BEQ done
+                NOP
+        done:   JMP start
+        ",
+    )
+    .unwrap();
+
+    assert_eq!(
+        program.bytes,
+        vec![0xa2, 0x05, 0xca, 0xd0, 0xfd, 0xf0, 0x01, 0xea, 0x4c, 0x00, 0x80]
+    );
+    assert_eq!(program.symbol("done"), Some(0x8008));
+}
+
+#[test]
+fn test_forward_reference_uses_absolute() {
+    // not known in the first pass, so it cannot be shortened to zero page
+    let program = bytes("LDA data\nBRK\ndata = $10");
+    assert_eq!(program, vec![0xad, 0x10, 0x00, 0x00]);
+
+    let program = bytes("LDA data\nBRK\ndata: .byte 7");
+    assert_eq!(program, vec![0xad, 0x04, 0x80, 0x00, 0x07]);
+}
+
+#[test]
+fn test_directives_and_expressions() {
+    let program = assemble(
+        "
+        BASE = $0200
+                .org $C000
+        table:  .byte 1, %101, 'a', ';', -1
+                .word table, BASE + 2 * 3
+                .byte <vector, >vector, * & $FF
+                .org $C010
+        vector: .dw $1234
+        ",
+    )
+    .unwrap();
+
+    assert_eq!(program.origin, 0xC000);
+    assert_eq!(program.bytes.len(), 0x12);
+    assert_eq!(
+        program.bytes[..12],
+        [1, 5, 0x61, 0x3b, 0xff, 0x00, 0xc0, 0x06, 0x02, 0x10, 0xc0, 0x09]
+    );
+    assert_eq!(program.bytes[0x10..], [0x34, 0x12]);
+}
+
+#[test]
+fn test_errors() {
+    let error = |source: &str| assemble(source).unwrap_err();
+
+    assert_eq!(error("NOP\nFOO #1"), "line 2: unknown instruction 'FOO'");
+    assert_eq!(
+        error("LDX ($20),Y"),
+        "line 1: LDX does not support this addressing mode"
+    );
+    assert_eq!(error("LDA #$100"), "line 1: 256 does not fit in a byte");
+    assert_eq!(error("x: NOP\nx: NOP"), "line 2: 'x' is already defined");
+    assert_eq!(
+        error("JMP nowhere"),
+        "line 1: undefined symbol in 'nowhere'"
+    );
+    assert_eq!(
+        error(".org $8000\nNOP\n.org $8000\nNOP"),
+        "line 4: $8000 is assembled twice"
+    );
+    assert!(error("BNE far\n.org $9000\nfar: NOP").contains("out of range"));
+    assert_eq!(
+        error(".byte -(-9223372036854775807 - 1)"),
+        "line 1: overflow in negation"
+    );
+    assert_eq!(
+        error(".byte (-9223372036854775807 - 1) / -1"),
+        "line 1: overflow in division"
+    );
+}
+
+#[test]
+fn test_macro_runs_on_cpu() {
+    let mut cpu = CPU::new();
+    cpu.load_and_run(crate::assemble!(
+        "       LDX #0",
+        "loop:  INX",
+        "       CPX #10",
+        "       BNE loop",
+        "       LDA #$42",
+        "       STA $10",
+        "       BRK",
+    ));
+
+    assert_eq!(cpu.register_x, 10);
+    assert_eq!(cpu.mem_peek(0x10), 0x42);
+}
+
+#[test]
+#[should_panic(expected = "assemble! code starts at $C000, CPU::load puts it at $8000")]
+fn test_macro_rejects_other_origin() {
+    crate::assemble!(".org $C000", "NOP");
+}
+
+#[test]
+fn test_comparison_operators() {
+    let program = bytes(".byte 3 > 2, 3 <= 2, 1 == 1 && 2 != 2, 0 || 5, $10 | 1 == $11");
+
+    assert_eq!(program, vec![1, 0, 0, 1, 1]);
+}
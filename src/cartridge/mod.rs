@@ -0,0 +1,102 @@
+use crate::nes::region::Region;
+
+const NES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+const PRG_ROM_PAGE_SIZE: usize = 16384;
+const CHR_ROM_PAGE_SIZE: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    Vertical,
+    Horizontal,
+    FourScreen,
+}
+
+/// # iNES / NES 2.0 cartridge image https://www.nesdev.org/wiki/INES
+///
+/// Only mapper 0 (NROM) is emulated, other mappers are parsed so their
+/// header can be inspected but are rejected by `Nes::load_rom`.
+pub struct Rom {
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    pub mapper: u8,
+    pub screen_mirroring: Mirroring,
+    pub battery: bool,
+    pub nes2: bool,
+    /// CPU/PPU timing from the NES 2.0 header, `None` for iNES 1.0 files
+    /// and for games that run on any region.
+    pub region: Option<Region>,
+}
+
+impl Rom {
+    pub fn new(raw: &[u8]) -> Result<Rom, String> {
+        if raw.len() < 16 || raw[0..4] != NES_TAG {
+            return Err("File is not in iNES file format".to_string());
+        }
+
+        if raw[4] == 0 {
+            return Err("ROM has no PRG ROM banks".to_string());
+        }
+
+        let mapper = (raw[7] & 0b1111_0000) | (raw[6] >> 4);
+
+        let nes2 = raw[7] & 0b0000_1100 == 0b0000_1000;
+
+        let four_screen = raw[6] & 0b1000 != 0;
+        let vertical_mirroring = raw[6] & 0b1 != 0;
+        let screen_mirroring = match (four_screen, vertical_mirroring) {
+            (true, _) => Mirroring::FourScreen,
+            (false, true) => Mirroring::Vertical,
+            (false, false) => Mirroring::Horizontal,
+        };
+
+        let battery = raw[6] & 0b10 != 0;
+
+        let region = if nes2 {
+            match raw[12] & 0b11 {
+                0 => Some(Region::Ntsc),
+                1 => Some(Region::Pal),
+                3 => Some(Region::Dendy),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let prg_rom_size = raw[4] as usize * PRG_ROM_PAGE_SIZE;
+        let chr_rom_size = raw[5] as usize * CHR_ROM_PAGE_SIZE;
+
+        let skip_trainer = raw[6] & 0b100 != 0;
+
+        let prg_rom_start = 16 + if skip_trainer { 512 } else { 0 };
+        let chr_rom_start = prg_rom_start + prg_rom_size;
+
+        if raw.len() < chr_rom_start + chr_rom_size {
+            return Err("File is shorter than its header claims".to_string());
+        }
+
+        Ok(Rom {
+            prg_rom: raw[prg_rom_start..(prg_rom_start + prg_rom_size)].to_vec(),
+            chr_rom: raw[chr_rom_start..(chr_rom_start + chr_rom_size)].to_vec(),
+            mapper,
+            screen_mirroring,
+            battery,
+            nes2,
+            region,
+        })
+    }
+
+    /// PRG ROM byte as seen by the CPU at `addr` ($8000-$FFFF), a single
+    /// 16K bank is mirrored into both halves.
+    pub fn read_prg_rom(&self, addr: u16) -> u8 {
+        let mut addr = addr as usize - 0x8000;
+        if self.prg_rom.len() == PRG_ROM_PAGE_SIZE && addr >= PRG_ROM_PAGE_SIZE {
+            addr %= PRG_ROM_PAGE_SIZE;
+        }
+        self.prg_rom[addr]
+    }
+}
+
+#[cfg(test)]
+mod test;
+#[cfg(test)]
+pub mod test_rom;
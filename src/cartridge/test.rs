@@ -0,0 +1,175 @@
+use super::*;
+
+struct TestRom {
+    header: Vec<u8>,
+    trainer: Option<Vec<u8>>,
+    prg_rom: Vec<u8>,
+    chr_rom: Vec<u8>,
+}
+
+fn create_rom(rom: TestRom) -> Vec<u8> {
+    let mut result = Vec::with_capacity(
+        rom.header.len()
+            + rom.trainer.as_ref().map_or(0, |t| t.len())
+            + rom.prg_rom.len()
+            + rom.chr_rom.len(),
+    );
+
+    result.extend(&rom.header);
+    if let Some(t) = rom.trainer {
+        result.extend(t);
+    }
+    result.extend(&rom.prg_rom);
+    result.extend(&rom.chr_rom);
+
+    result
+}
+
+#[test]
+fn test_ines() {
+    let test_rom = create_rom(TestRom {
+        header: vec![
+            0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0x31, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+        ],
+        trainer: None,
+        prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+        chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+    });
+
+    let rom = Rom::new(&test_rom).unwrap();
+
+    assert_eq!(rom.chr_rom, vec!(2; CHR_ROM_PAGE_SIZE));
+    assert_eq!(rom.prg_rom, vec!(1; 2 * PRG_ROM_PAGE_SIZE));
+    assert_eq!(rom.mapper, 3);
+    assert_eq!(rom.screen_mirroring, Mirroring::Vertical);
+    assert!(!rom.nes2);
+    assert_eq!(rom.region, None);
+}
+
+#[test]
+fn test_with_trainer() {
+    let test_rom = create_rom(TestRom {
+        header: vec![
+            0x4E,
+            0x45,
+            0x53,
+            0x1A,
+            0x02,
+            0x01,
+            0x31 | 0b100,
+            00,
+            00,
+            00,
+            00,
+            00,
+            00,
+            00,
+            00,
+            00,
+        ],
+        trainer: Some(vec![0; 512]),
+        prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+        chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+    });
+
+    let rom = Rom::new(&test_rom).unwrap();
+
+    assert_eq!(rom.chr_rom, vec!(2; CHR_ROM_PAGE_SIZE));
+    assert_eq!(rom.prg_rom, vec!(1; 2 * PRG_ROM_PAGE_SIZE));
+    assert_eq!(rom.mapper, 3);
+    assert_eq!(rom.screen_mirroring, Mirroring::Vertical);
+}
+
+#[test]
+fn test_nes2_header_flag() {
+    let test_rom = create_rom(TestRom {
+        header: vec![
+            0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x02, 0x08, 00, 00, 00, 00, 00, 00, 00, 00,
+        ],
+        trainer: None,
+        prg_rom: vec![1; PRG_ROM_PAGE_SIZE],
+        chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+    });
+
+    let rom = Rom::new(&test_rom).unwrap();
+
+    assert!(rom.nes2);
+    assert!(rom.battery);
+    assert_eq!(rom.screen_mirroring, Mirroring::Horizontal);
+    assert_eq!(rom.region, Some(Region::Ntsc));
+}
+
+#[test]
+fn test_nes2_timing() {
+    let mut header = vec![
+        0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x08, 00, 00, 00, 00, 00, 00, 00, 00,
+    ];
+    let expected = [
+        Some(Region::Ntsc),
+        Some(Region::Pal),
+        None,
+        Some(Region::Dendy),
+    ];
+
+    for (timing, region) in expected.iter().enumerate() {
+        header[12] = timing as u8;
+        let test_rom = create_rom(TestRom {
+            header: header.clone(),
+            trainer: None,
+            prg_rom: vec![1; PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+        });
+
+        assert_eq!(Rom::new(&test_rom).unwrap().region, *region);
+    }
+}
+
+#[test]
+fn test_prg_rom_mirroring() {
+    let mut prg_rom = vec![0; PRG_ROM_PAGE_SIZE];
+    prg_rom[0x10] = 0xAB;
+    let test_rom = create_rom(TestRom {
+        header: vec![
+            0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+        ],
+        trainer: None,
+        prg_rom,
+        chr_rom: vec![0; CHR_ROM_PAGE_SIZE],
+    });
+
+    let rom = Rom::new(&test_rom).unwrap();
+
+    assert_eq!(rom.read_prg_rom(0x8010), 0xAB);
+    assert_eq!(rom.read_prg_rom(0xC010), 0xAB);
+}
+
+#[test]
+fn test_not_ines() {
+    let test_rom = create_rom(TestRom {
+        header: vec![
+            0x4E, 0x45, 0x53, 0x00, 0x01, 0x01, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+        ],
+        trainer: None,
+        prg_rom: vec![1; PRG_ROM_PAGE_SIZE],
+        chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+    });
+
+    assert!(Rom::new(&test_rom).is_err());
+}
+
+#[test]
+fn test_no_prg_rom() {
+    let test_rom = create_rom(TestRom {
+        header: vec![
+            0x4E, 0x45, 0x53, 0x1A, 0x00, 0x01, 00, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+        ],
+        trainer: None,
+        prg_rom: vec![],
+        chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+    });
+
+    assert_eq!(
+        Rom::new(&test_rom).err(),
+        Some("ROM has no PRG ROM banks".to_string())
+    );
+}
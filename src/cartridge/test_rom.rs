@@ -0,0 +1,32 @@
+//! iNES images built on the fly for tests across the crate.
+
+/// 16K NROM image with `program` at $C000 (mirrored at $8000) and the
+/// vectors pointing into it. `battery` sets the header's battery flag.
+pub fn test_rom(program: &[u8], nmi: u16, reset: u16, battery: bool) -> Vec<u8> {
+    test_rom_with_header(program, nmi, reset, battery, &[0; 8])
+}
+
+/// Same as `test_rom` with header bytes 8-15 set, e.g. NES 2.0 fields.
+pub fn test_rom_with_header(
+    program: &[u8],
+    nmi: u16,
+    reset: u16,
+    battery: bool,
+    extra: &[u8; 8],
+) -> Vec<u8> {
+    let flags6 = if battery { 0b10 } else { 0 };
+    let nes2 = if extra.iter().any(|&b| b != 0) {
+        0x08
+    } else {
+        0
+    };
+    let mut raw = vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, flags6, nes2];
+    raw.extend(extra);
+    let mut prg_rom = vec![0; 0x4000];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFA..0x3FFC].copy_from_slice(&nmi.to_le_bytes());
+    prg_rom[0x3FFC..0x3FFE].copy_from_slice(&reset.to_le_bytes());
+    raw.extend(prg_rom);
+    raw.extend(vec![0; 0x2000]);
+    raw
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::Path;
+
+use bitflags::bitflags;
+
+use crate::cartridge::Rom;
+
+bitflags! {
+    /// # FCEUX code/data log https://fceux.com/web/help/CodeDataLogger.html
+    ///
+    /// One byte per PRG ROM byte:
+    ///
+    ///  7 6 5 4 3 2 1 0
+    ///  _ P d c A A D C
+    ///  | | | | | | | +--- Code, fetched as an opcode or operand
+    ///  | | | | | | +----- Data, read through an addressing mode
+    ///  | | | | +-+------- 8K window it was read through, $8000 = 0 .. $E000 = 3
+    ///  | | | +----------- Code reached through JMP ($xxxx)
+    ///  | | +------------- Data read through ($xx,X) or ($xx),Y
+    ///  | +--------------- PCM sample fetched by the DMC, never set here
+    ///
+    pub struct CdlFlags: u8 {
+        const CODE          = 0b0000_0001;
+        const DATA          = 0b0000_0010;
+        const BANK          = 0b0000_1100;
+        const INDIRECT_CODE = 0b0001_0000;
+        const INDIRECT_DATA = 0b0010_0000;
+    }
+}
+
+/// Marks which PRG ROM bytes the CPU executed and which it read as data,
+/// in the layout of FCEUX's .cdl files: the PRG ROM flags followed by one
+/// byte per CHR ROM byte.
+///
+/// Set `CPU::cdl` to `Some(CodeDataLogger::for_rom(&rom))`. The CPU marks
+/// every instruction's bytes as code when it fetches them, and switches
+/// reads to data once `get_operand_address` has worked out where the
+/// operand lives. CHR accesses are not logged, that part of the file stays
+/// zero. Neither is bit 6 ever set, the APU does not fetch DMC samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeDataLogger {
+    pub prg: Vec<u8>,
+    pub chr: Vec<u8>,
+    access: CdlFlags,
+    indirect_jump: bool,
+}
+
+impl CodeDataLogger {
+    /// PRG ROM is mirrored into $8000-$FFFF, so `prg_size` must be a power
+    /// of two no larger than 32K, or a multiple of 32K.
+    pub fn new(prg_size: usize, chr_size: usize) -> Self {
+        CodeDataLogger {
+            prg: vec![0; prg_size],
+            chr: vec![0; chr_size],
+            access: CdlFlags::DATA,
+            indirect_jump: false,
+        }
+    }
+
+    pub fn for_rom(rom: &Rom) -> Self {
+        Self::new(rom.prg_rom.len(), rom.chr_rom.len())
+    }
+
+    /// Continues an earlier log, `data` being the contents of a .cdl file.
+    pub fn from_bytes(prg_size: usize, chr_size: usize, data: &[u8]) -> Result<Self, String> {
+        if data.len() != prg_size + chr_size {
+            return Err(format!(
+                "code/data log is {} bytes, expected {}",
+                data.len(),
+                prg_size + chr_size
+            ));
+        }
+        let mut cdl = Self::new(prg_size, chr_size);
+        cdl.prg.copy_from_slice(&data[..prg_size]);
+        cdl.chr.copy_from_slice(&data[prg_size..]);
+        Ok(cdl)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.prg.as_slice(), self.chr.as_slice()].concat()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        fs::write(path, self.to_bytes())
+            .map_err(|e| format!("cannot write {}: {}", path.display(), e))
+    }
+
+    fn prg_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xFFFF if !self.prg.is_empty() => {
+                Some((addr as usize - 0x8000) % self.prg.len())
+            }
+            _ => None,
+        }
+    }
+
+    fn mark(&mut self, addr: u16, flags: CdlFlags) {
+        if let Some(offset) = self.prg_offset(addr) {
+            let bank = ((addr >> 13) & 0b11) as u8;
+            self.prg[offset] |= flags.bits() | (bank << 2);
+        }
+    }
+
+    pub fn flags(&self, addr: u16) -> CdlFlags {
+        match self.prg_offset(addr) {
+            Some(offset) => CdlFlags::from_bits_truncate(self.prg[offset]),
+            None => CdlFlags::empty(),
+        }
+    }
+
+    /// Bytes marked as code and as data, a byte can count for both.
+    pub fn coverage(&self) -> (usize, usize) {
+        let count = |flag: CdlFlags| {
+            self.prg
+                .iter()
+                .filter(|&&b| CdlFlags::from_bits_truncate(b).contains(flag))
+                .count()
+        };
+        (count(CdlFlags::CODE), count(CdlFlags::DATA))
+    }
+
+    /// How the CPU's following reads are logged.
+    pub fn set_access(&mut self, access: CdlFlags) {
+        self.access = access;
+    }
+
+    pub fn log_read(&mut self, addr: u16) {
+        self.mark(addr, self.access);
+    }
+
+    /// The `len` bytes of the instruction at `addr` were fetched.
+    pub fn log_instruction(&mut self, addr: u16, len: u16) {
+        let mut flags = CdlFlags::CODE;
+        if self.indirect_jump {
+            flags |= CdlFlags::INDIRECT_CODE;
+            self.indirect_jump = false;
+        }
+        for i in 0..len {
+            self.mark(addr.wrapping_add(i), flags);
+        }
+    }
+
+    /// The next instruction was reached through JMP ($xxxx).
+    pub fn log_indirect_jump(&mut self) {
+        self.indirect_jump = true;
+    }
+}
+
+#[cfg(test)]
+mod test;
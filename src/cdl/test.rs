@@ -0,0 +1,92 @@
+use super::*;
+use crate::asm;
+use crate::cpu::CPU;
+
+fn logged_run() -> (CPU, asm::Program) {
+    let program = asm::assemble(
+        "        LDA table
+                 LDY #0
+                 LDA ($10),Y
+                 JMP (vector)
+         skipped: NOP
+         target: BRK
+         vector: .word target
+         table:  .byte 1, 2",
+    )
+    .unwrap();
+
+    let mut cpu = CPU::new();
+    cpu.load_at(program.origin, &program.bytes).unwrap();
+    let pointer = program.symbol("table").unwrap() + 1;
+    cpu.load_at(0x10, &pointer.to_le_bytes()).unwrap();
+    cpu.program_counter = program.origin;
+    cpu.cdl = Some(CodeDataLogger::new(0x8000, 0x2000));
+    cpu.run();
+    (cpu, program)
+}
+
+#[test]
+fn test_code_and_data() {
+    let (cpu, program) = logged_run();
+    let cdl = cpu.cdl.as_ref().unwrap();
+    let at = |name: &str| cdl.flags(program.symbol(name).unwrap());
+
+    // LDA table, all three bytes fetched as code
+    for addr in 0x8000..0x8003 {
+        assert_eq!(cdl.flags(addr), CdlFlags::CODE);
+    }
+    // immediate operands are code, not data
+    assert_eq!(cdl.flags(0x8004), CdlFlags::CODE);
+
+    assert_eq!(at("table"), CdlFlags::DATA);
+    let table = program.symbol("table").unwrap();
+    assert_eq!(
+        cdl.flags(table + 1),
+        CdlFlags::DATA | CdlFlags::INDIRECT_DATA
+    );
+
+    let vector = program.symbol("vector").unwrap();
+    for addr in vector..vector + 2 {
+        assert_eq!(cdl.flags(addr), CdlFlags::DATA | CdlFlags::INDIRECT_DATA);
+    }
+    assert_eq!(at("target"), CdlFlags::CODE | CdlFlags::INDIRECT_CODE);
+    assert_eq!(at("skipped"), CdlFlags::empty());
+
+    // zero page is not ROM
+    assert_eq!(cdl.flags(0x0010), CdlFlags::empty());
+    assert_eq!(cdl.coverage(), (11, 4));
+}
+
+#[test]
+fn test_bank_bits_and_mirroring() {
+    let mut cdl = CodeDataLogger::new(0x4000, 0);
+
+    cdl.log_instruction(0xC000, 1);
+    cdl.set_access(CdlFlags::DATA);
+    cdl.log_read(0xE000);
+    // NROM-128 shows the same 16K at $8000 and $C000
+    assert_eq!(cdl.prg[0], 0b0000_1001);
+    assert_eq!(cdl.prg[0x2000], 0b0000_1110);
+    assert_eq!(
+        cdl.flags(0x8000),
+        CdlFlags::CODE | CdlFlags::from_bits_truncate(0b1000)
+    );
+
+    cdl.log_instruction(0x8001, 1);
+    assert_eq!(cdl.prg[1], 0b0000_0001);
+}
+
+#[test]
+fn test_file_round_trip() {
+    let (cpu, _) = logged_run();
+    let cdl = cpu.cdl.unwrap();
+
+    let bytes = cdl.to_bytes();
+    assert_eq!(bytes.len(), 0x8000 + 0x2000);
+    assert_eq!(CodeDataLogger::from_bytes(0x8000, 0x2000, &bytes), Ok(cdl));
+
+    assert_eq!(
+        CodeDataLogger::from_bytes(0x4000, 0x2000, &bytes),
+        Err("code/data log is 40960 bytes, expected 24576".to_string())
+    );
+}
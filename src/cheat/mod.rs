@@ -0,0 +1,170 @@
+use std::fmt;
+
+// letter values 0-15
+const GAME_GENIE_LETTERS: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+/// One cheat: a Game Genie patch over what the CPU reads from ROM, or a
+/// freeze that puts a value back into RAM every frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    /// The Game Genie code as entered, `None` for a freeze.
+    pub code: Option<String>,
+    pub addr: u16,
+    pub value: u8,
+    /// Only patch reads that would have returned this (8-letter codes), so
+    /// the patch leaves other banks mapped at the same address alone.
+    pub compare: Option<u8>,
+    pub enabled: bool,
+}
+
+impl Cheat {
+    /// # Game Genie https://www.nesdev.org/wiki/Game_Genie
+    ///
+    /// Every letter is a nibble; 6 letters hold a 15-bit address in
+    /// $8000-$FFFF and a value, 8 letters add a compare value. The bits are
+    /// scrambled across the nibbles, e.g. SXIOPO is $91D9 = $AD.
+    pub fn game_genie(code: &str) -> Result<Self, String> {
+        let code = code.to_ascii_uppercase();
+        let n = code
+            .bytes()
+            .map(|c| {
+                GAME_GENIE_LETTERS
+                    .iter()
+                    .position(|&letter| letter == c)
+                    .map(|i| i as u16)
+                    .ok_or(format!("'{}' is not a Game Genie letter", c as char))
+            })
+            .collect::<Result<Vec<u16>, String>>()?;
+        if n.len() != 6 && n.len() != 8 {
+            return Err(format!(
+                "Game Genie codes have 6 or 8 letters, '{}' has {}",
+                code,
+                n.len()
+            ));
+        }
+
+        let addr = 0x8000
+            | ((n[3] & 7) << 12)
+            | ((n[5] & 7) << 8)
+            | ((n[4] & 8) << 8)
+            | ((n[2] & 7) << 4)
+            | ((n[1] & 8) << 4)
+            | (n[4] & 7)
+            | (n[3] & 8);
+        let (value, compare) = if n.len() == 6 {
+            let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7) | (n[5] & 8);
+            (value, None)
+        } else {
+            let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7) | (n[7] & 8);
+            let compare = ((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8);
+            (value, Some(compare as u8))
+        };
+
+        Ok(Cheat {
+            code: Some(code),
+            addr,
+            value: value as u8,
+            compare,
+            enabled: true,
+        })
+    }
+
+    /// Keeps `addr` at `value`, rewritten at the end of every frame.
+    pub fn freeze(addr: u16, value: u8) -> Self {
+        Cheat {
+            code: None,
+            addr,
+            value,
+            compare: None,
+            enabled: true,
+        }
+    }
+
+    pub fn is_freeze(&self) -> bool {
+        self.code.is_none()
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{} ${:04X} = ${:02X}", code, self.addr, self.value)?,
+            None => write!(f, "freeze ${:04X} = ${:02X}", self.addr, self.value)?,
+        }
+        if let Some(compare) = self.compare {
+            write!(f, " if ${:02X}", compare)?;
+        }
+        if !self.enabled {
+            write!(f, " (off)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The cheats in effect on a CPU, see `CPU::cheats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Cheats::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cheats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cheat> {
+        self.cheats.iter()
+    }
+
+    /// Adds a cheat and returns its number.
+    pub fn add(&mut self, cheat: Cheat) -> usize {
+        self.cheats.push(cheat);
+        self.cheats.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<Cheat, String> {
+        if index >= self.cheats.len() {
+            return Err(format!("no cheat {}", index));
+        }
+        Ok(self.cheats.remove(index))
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> Result<(), String> {
+        let cheat = self
+            .cheats
+            .get_mut(index)
+            .ok_or(format!("no cheat {}", index))?;
+        cheat.enabled = enabled;
+        Ok(())
+    }
+
+    /// What a read of `addr` returns with the Game Genie codes applied,
+    /// `value` being what the bus returned.
+    pub fn patch_read(&self, addr: u16, value: u8) -> u8 {
+        self.cheats
+            .iter()
+            .filter(|c| c.enabled && !c.is_freeze() && c.addr == addr)
+            .find(|c| c.compare.is_none_or(|compare| compare == value))
+            .map_or(value, |c| c.value)
+    }
+
+    /// Address and value of every enabled freeze.
+    pub fn freezes(&self) -> Vec<(u16, u8)> {
+        self.cheats
+            .iter()
+            .filter(|c| c.enabled && c.is_freeze())
+            .map(|c| (c.addr, c.value))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test;
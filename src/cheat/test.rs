@@ -0,0 +1,95 @@
+use super::*;
+use crate::cpu::CPU;
+
+#[test]
+fn test_decode_six_letters() {
+    let cheat = Cheat::game_genie("SXIOPO").unwrap();
+    assert_eq!(
+        (cheat.addr, cheat.value, cheat.compare),
+        (0x91D9, 0xAD, None)
+    );
+    assert_eq!(cheat.to_string(), "SXIOPO $91D9 = $AD");
+
+    // letters are not case sensitive
+    assert_eq!(Cheat::game_genie("sxiopo").unwrap(), cheat);
+}
+
+#[test]
+fn test_decode_eight_letters() {
+    let cheat = Cheat::game_genie("SXIOPOAP").unwrap();
+    assert_eq!(
+        (cheat.addr, cheat.value, cheat.compare),
+        (0x91D9, 0xA5, Some(0x18))
+    );
+    assert_eq!(cheat.to_string(), "SXIOPOAP $91D9 = $A5 if $18");
+}
+
+#[test]
+fn test_decode_errors() {
+    assert_eq!(
+        Cheat::game_genie("SXIOPB"),
+        Err("'B' is not a Game Genie letter".to_string())
+    );
+    assert_eq!(
+        Cheat::game_genie("SXIOP"),
+        Err("Game Genie codes have 6 or 8 letters, 'SXIOP' has 5".to_string())
+    );
+}
+
+#[test]
+fn test_read_patch() {
+    let mut cpu = CPU::new();
+    cpu.load_at(0x91D9, &[0x18]).unwrap();
+    let mut cheats = Cheats::new();
+    cheats.add(Cheat::game_genie("SXIOPOAP").unwrap());
+    cpu.cheats = cheats;
+    assert_eq!(cpu.mem_peek(0x91D9), 0xA5);
+    assert_eq!(cpu.mem_peek(0x91DA), 0x00);
+
+    // a compare value that does not match leaves the read alone
+    cpu.load_at(0x91D9, &[0x19]).unwrap();
+    assert_eq!(cpu.mem_peek(0x91D9), 0x19);
+
+    cpu.cheats.set_enabled(0, false).unwrap();
+    cpu.load_at(0x91D9, &[0x18]).unwrap();
+    assert_eq!(cpu.mem_peek(0x91D9), 0x18);
+    assert_eq!(
+        cpu.cheats.iter().next().unwrap().to_string(),
+        "SXIOPOAP $91D9 = $A5 if $18 (off)"
+    );
+}
+
+#[test]
+fn test_patch_is_seen_by_instructions() {
+    // LDA $9000; BRK
+    let mut cpu = CPU::new();
+    cpu.load_at(0x8000, &[0xAD, 0x00, 0x90, 0x00]).unwrap();
+    cpu.load_at(0x9000, &[0x01]).unwrap();
+    cpu.program_counter = 0x8000;
+    cpu.cheats.add(Cheat {
+        code: Some("test".to_string()),
+        addr: 0x9000,
+        value: 0x63,
+        compare: None,
+        enabled: true,
+    });
+
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x63);
+}
+
+#[test]
+fn test_freeze() {
+    let mut cpu = CPU::new();
+    cpu.cheats.add(Cheat::freeze(0x0075, 9));
+    let off = cpu.cheats.add(Cheat::freeze(0x0076, 9));
+    cpu.cheats.set_enabled(off, false).unwrap();
+
+    cpu.apply_freezes();
+    assert_eq!(cpu.mem_peek(0x0075), 9);
+    assert_eq!(cpu.mem_peek(0x0076), 0);
+    assert_eq!(cpu.cheats.freezes(), vec![(0x0075, 9)]);
+
+    assert_eq!(cpu.cheats.remove(off).unwrap().addr, 0x0076);
+    assert_eq!(cpu.cheats.remove(off), Err("no cheat 1".to_string()));
+}
@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::net::TcpListener;
+use std::path::Path;
+
+use crate::cartridge::Mirroring;
+use crate::cartridge::Rom;
+use crate::cpu::CPU;
+use crate::debugger::Debugger;
+use crate::disasm;
+use crate::frame::screenshot::ImageFormat;
+use crate::frame::screenshot::ScreenshotOptions;
+use crate::gdb::GdbStub;
+use crate::nes::region::Region;
+use crate::nes::Nes;
+use crate::terminal::TerminalHost;
+use crate::trace::trace;
+
+const USAGE: &str = "usage: rustiness <command> [options]
+
+commands:
+  run <rom> [--frames N]       run a .nes file without a window for N frames (default 60)
+        [--screenshot FILE]    save the last frame as .png or .ppm
+  trace <file> [--org ADDR]    print a nestest.log style line per instruction
+        [--instructions N]     stop after N instructions (default 1000) or at BRK
+  info <rom>                   show what the header of a .nes file says
+  disasm <file> [--org ADDR]   disassemble a raw binary or the PRG ROM of a .nes file
+  debug <file> [--org ADDR]    debug a .nes file, or a raw binary loaded at ADDR
+        [--gdb PORT]           serve the GDB remote protocol on 127.0.0.1:PORT
+  term <file> [--org ADDR]     play a raw binary (default at 0600) in the terminal,
+        [--display ADDR]       showing the 32x32 screen at ADDR (default 0200)
+        [--input ADDR]         with the last key pressed at ADDR (default 00FF)
+        [--speed N]            running N instructions per 1/60 second (default 1000)
+
+ADDR is hexadecimal, optionally prefixed with $ or 0x.
+
+exit status: 0 on success, 1 when the command fails, 2 for bad arguments";
+
+/// Exit status for a command that ran into an error.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit status for arguments that could not be understood.
+pub const EXIT_USAGE: i32 = 2;
+
+/// Why a command failed, and the status the process exits with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CliError {
+    pub message: String,
+    pub code: i32,
+}
+
+impl CliError {
+    fn usage<S: Into<String>>(message: S) -> Self {
+        CliError {
+            message: message.into(),
+            code: EXIT_USAGE,
+        }
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError {
+            message,
+            code: EXIT_FAILURE,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Entry point for the command line, `args` excludes the program name.
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    match args.first().map(String::as_str) {
+        Some("run") => run_command(&args[1..]),
+        Some("trace") => trace_command(&args[1..]),
+        Some("info") => info_command(&args[1..]),
+        Some("disasm") => disasm_command(&args[1..]),
+        Some("debug") => debug_command(&args[1..]),
+        Some("term") => term_command(&args[1..]),
+        Some("help") | Some("--help") | Some("-h") => {
+            write_stdout(|out| writeln!(out, "{}", USAGE))
+        }
+        Some(command) => Err(CliError::usage(format!(
+            "unknown command '{}'\n\n{}",
+            command, USAGE
+        ))),
+        None => Err(CliError::usage(USAGE)),
+    }
+}
+
+/// Parses `$C000`, `0xC000` or `C000`.
+pub fn parse_address(text: &str) -> Result<u16, String> {
+    let digits = text
+        .strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid address '{}'", text))
+}
+
+/// The file and `--name VALUE` options of a command, `options` being the
+/// names it takes.
+struct Args {
+    file: String,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(command: &str, args: &[String], options: &[&str]) -> Result<Args, CliError> {
+        let mut file = None;
+        let mut values = HashMap::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if options.contains(&arg.as_str()) {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| CliError::usage(format!("{} needs a value", arg)))?;
+                values.insert(arg.clone(), value.clone());
+            } else if arg.starts_with("--") {
+                return Err(CliError::usage(format!(
+                    "unknown option '{}' for {}",
+                    arg, command
+                )));
+            } else if file.is_none() {
+                file = Some(arg.clone());
+            } else {
+                return Err(CliError::usage(format!("unexpected argument '{}'", arg)));
+            }
+        }
+
+        let file = file.ok_or_else(|| CliError::usage(format!("{} needs a file", command)))?;
+        Ok(Args {
+            file,
+            options: values,
+        })
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    fn org(&self) -> Result<Option<u16>, CliError> {
+        self.address("--org")
+    }
+
+    fn address(&self, name: &str) -> Result<Option<u16>, CliError> {
+        self.get(name)
+            .map(parse_address)
+            .transpose()
+            .map_err(CliError::usage)
+    }
+
+    fn count(&self, name: &str, default: u64) -> Result<u64, CliError> {
+        match self.get(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| CliError::usage(format!("invalid {} '{}'", name, value))),
+            None => Ok(default),
+        }
+    }
+}
+
+fn read_file(file: &str) -> Result<Vec<u8>, String> {
+    fs::read(file).map_err(|e| format!("cannot read {}: {}", file, e))
+}
+
+fn run_command(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse("run", args, &["--frames", "--screenshot"])?;
+    let frames = args.count("--frames", 60)?;
+    let screenshot = args.get("--screenshot").map(Path::new);
+    if let Some(path) = screenshot {
+        if ImageFormat::from_path(path).is_none() {
+            return Err(CliError::usage(format!(
+                "screenshot {} should end in .png or .ppm",
+                path.display()
+            )));
+        }
+    }
+
+    let mut nes = Nes::new();
+    nes.load_rom_file(&args.file)?;
+    for _ in 0..frames {
+        nes.run_frame();
+        if nes.unimplemented().is_some() {
+            break;
+        }
+    }
+    nes.flush_battery()?;
+    if let Some(unimplemented) = nes.unimplemented() {
+        return Err(unimplemented.to_string().into());
+    }
+
+    if let Some(path) = screenshot {
+        let frame = &nes.cpu.bus.as_ref().unwrap().ppu.frame;
+        frame
+            .save_screenshot(path, &ScreenshotOptions::default())
+            .map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+fn trace_command(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse("trace", args, &["--instructions", "--org"])?;
+    let count = args.count("--instructions", 1000)?;
+    let raw = read_file(&args.file)?;
+
+    let mut nes = Nes::new();
+    if Rom::new(&raw).is_ok() {
+        nes.load_rom(&raw)?;
+    } else {
+        let org = args.org()?.unwrap_or(0x8000);
+        nes.cpu.load_at(org, &raw)?;
+        nes.cpu.program_counter = org;
+    }
+
+    write_stdout(|out| trace_instructions(&mut nes, count, out))?;
+    match nes.unimplemented() {
+        Some(unimplemented) => Err(unimplemented.to_string().into()),
+        None => Ok(()),
+    }
+}
+
+/// Runs `write` on a buffered, locked stdout. A reader that goes away early,
+/// as in `trace game.nes | head`, is not an error.
+fn write_stdout<F>(write: F) -> Result<(), CliError>
+where
+    F: FnOnce(&mut BufWriter<io::StdoutLock>) -> io::Result<()>,
+{
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    match write(&mut out).and_then(|_| out.flush()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(|e| CliError::from(e.to_string())),
+    }
+}
+
+/// Writes a trace line for each of the next `count` instructions, stopping
+/// early at BRK or an unimplemented opcode.
+fn trace_instructions<W: Write>(nes: &mut Nes, count: u64, out: &mut W) -> io::Result<()> {
+    for _ in 0..count {
+        writeln!(out, "{}", trace(&nes.cpu))?;
+        let running = if nes.cpu.bus.is_some() {
+            nes.step_instruction();
+            !nes.is_halted()
+        } else {
+            nes.cpu.step()
+        };
+        if !running {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn info_command(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse("info", args, &[])?;
+    let rom = Rom::new(&read_file(&args.file)?)?;
+    write_stdout(|out| writeln!(out, "{}", rom_info(&rom)))
+}
+
+/// What the header of `rom` says, one field per line.
+fn rom_info(rom: &Rom) -> String {
+    let kib = |bytes: usize| format!("{} KiB", bytes / 1024);
+    let lines = [
+        (
+            "format",
+            if rom.nes2 { "NES 2.0" } else { "iNES" }.to_string(),
+        ),
+        (
+            "mapper",
+            match rom.mapper {
+                0 => "0 (NROM)".to_string(),
+                mapper => format!("{} (not supported)", mapper),
+            },
+        ),
+        ("PRG ROM", kib(rom.prg_rom.len())),
+        (
+            "CHR ROM",
+            match rom.chr_rom.len() {
+                0 => "none, CHR RAM".to_string(),
+                len => kib(len),
+            },
+        ),
+        (
+            "mirroring",
+            match rom.screen_mirroring {
+                Mirroring::Vertical => "vertical",
+                Mirroring::Horizontal => "horizontal",
+                Mirroring::FourScreen => "four screen",
+            }
+            .to_string(),
+        ),
+        (
+            "battery",
+            if rom.battery { "yes" } else { "no" }.to_string(),
+        ),
+        (
+            "region",
+            match rom.region {
+                Some(Region::Ntsc) => "NTSC",
+                Some(Region::Pal) => "PAL",
+                Some(Region::Dendy) => "Dendy",
+                None if rom.nes2 => "any",
+                None => "not in header, NTSC assumed",
+            }
+            .to_string(),
+        ),
+    ];
+
+    lines
+        .iter()
+        .map(|(name, value)| format!("{:<10} {}", name, value))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn disasm_command(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse("disasm", args, &["--org"])?;
+    let org = args.org()?;
+    let raw = read_file(&args.file)?;
+
+    // .nes files are disassembled from their PRG ROM as the CPU sees it
+    let (bytes, default_org) = match Rom::new(&raw) {
+        Ok(rom) => {
+            let org = if rom.prg_rom.len() <= 0x4000 {
+                0xC000
+            } else {
+                0x8000
+            };
+            (rom.prg_rom, org)
+        }
+        Err(_) => (raw, 0x8000),
+    };
+
+    write_stdout(|out| {
+        for instruction in disasm::disassemble(&bytes, org.unwrap_or(default_org)) {
+            writeln!(out, "{}", instruction)?;
+        }
+        Ok(())
+    })
+}
+
+fn debug_command(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse("debug", args, &["--org", "--gdb"])?;
+    let org = args.org()?;
+    let gdb_port = args
+        .get("--gdb")
+        .map(|value| {
+            value
+                .parse::<u16>()
+                .map_err(|_| CliError::usage(format!("invalid port '{}'", value)))
+        })
+        .transpose()?;
+    let raw = read_file(&args.file)?;
+
+    let mut debugger = if Rom::new(&raw).is_ok() {
+        let mut nes = Nes::new();
+        nes.load_rom(&raw)?;
+        Debugger::new(nes)
+    } else {
+        Debugger::with_program(&raw, org.unwrap_or(0x8000))?
+    };
+
+    if let Some(port) = gdb_port {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("cannot listen on port {}: {}", port, e))?;
+        eprintln!("waiting for gdb on 127.0.0.1:{}", port);
+        return GdbStub::new(debugger)
+            .serve(&listener)
+            .map_err(|e| CliError::from(e.to_string()));
+    }
+
+    let stdin = io::stdin();
+    debugger
+        .repl(stdin.lock(), &mut io::stdout())
+        .map_err(|e| CliError::from(e.to_string()))
+}
+
+fn term_command(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse("term", args, &["--org", "--display", "--input", "--speed"])?;
+    let mut host = TerminalHost::new();
+    if let Some(addr) = args.address("--display")? {
+        host.display.base = addr;
+    }
+    if let Some(addr) = args.address("--input")? {
+        host.input = addr;
+    }
+    host.speed = args.count("--speed", host.speed)?;
+
+    let raw = read_file(&args.file)?;
+    if Rom::new(&raw).is_ok() {
+        return Err("term runs raw 6502 binaries, not .nes files"
+            .to_string()
+            .into());
+    }
+    let org = args.org()?.unwrap_or(0x0600);
+    let mut cpu = CPU::new();
+    cpu.load_at(org, &raw)?;
+    cpu.program_counter = org;
+
+    host.run(&mut cpu)
+        .map_err(|e| CliError::from(format!("terminal: {}", e)))
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,174 @@
+use super::*;
+use crate::cartridge::test_rom::test_rom;
+use tempfile::TempDir;
+
+fn args(words: &[&str]) -> Vec<String> {
+    words.iter().map(|w| w.to_string()).collect()
+}
+
+// NROM-128 that loops forever at $C000, with the battery flag set
+fn looping_rom() -> Vec<u8> {
+    // JMP $C000
+    test_rom(&[0x4C, 0x00, 0xC0], 0xC000, 0xC000, true)
+}
+
+#[test]
+fn test_usage_errors() {
+    let usage = |words: &[&str]| run(&args(words)).unwrap_err();
+
+    assert_eq!(usage(&[]).code, EXIT_USAGE);
+    assert_eq!(usage(&["frobnicate"]).code, EXIT_USAGE);
+    assert_eq!(usage(&["run"]), CliError::usage("run needs a file"));
+    assert_eq!(
+        usage(&["trace", "game.nes", "--instructions"]),
+        CliError::usage("--instructions needs a value")
+    );
+    assert_eq!(
+        usage(&["trace", "game.nes", "--instructions", "many"]),
+        CliError::usage("invalid --instructions 'many'")
+    );
+    assert_eq!(
+        usage(&["info", "game.nes", "--org", "C000"]),
+        CliError::usage("unknown option '--org' for info")
+    );
+    assert_eq!(
+        usage(&["disasm", "a.bin", "b.bin"]),
+        CliError::usage("unexpected argument 'b.bin'")
+    );
+    assert_eq!(
+        usage(&["run", "game.nes", "--screenshot", "shot.bmp"]),
+        CliError::usage("screenshot shot.bmp should end in .png or .ppm")
+    );
+}
+
+#[test]
+fn test_failures() {
+    let dir = TempDir::new().unwrap();
+    let missing = dir.path().join("missing.nes");
+    let error = run(&args(&["info", missing.to_str().unwrap()])).unwrap_err();
+    assert_eq!(error.code, EXIT_FAILURE);
+    assert!(error.message.starts_with("cannot read "));
+
+    let text = dir.path().join("notes.txt");
+    fs::write(&text, "hello").unwrap();
+    assert_eq!(
+        run(&args(&["info", text.to_str().unwrap()])),
+        Err(CliError::from(
+            "File is not in iNES file format".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_rom_info() {
+    let rom = Rom::new(&looping_rom()).unwrap();
+    assert_eq!(
+        rom_info(&rom),
+        "format     iNES\n\
+         mapper     0 (NROM)\n\
+         PRG ROM    16 KiB\n\
+         CHR ROM    8 KiB\n\
+         mirroring  horizontal\n\
+         battery    yes\n\
+         region     not in header, NTSC assumed"
+    );
+}
+
+#[test]
+fn test_run_with_screenshot() {
+    let dir = TempDir::new().unwrap();
+    let rom = dir.path().join("loop.nes");
+    fs::write(&rom, looping_rom()).unwrap();
+    let shot = dir.path().join("shot.ppm");
+
+    run(&args(&[
+        "run",
+        rom.to_str().unwrap(),
+        "--frames",
+        "3",
+        "--screenshot",
+        shot.to_str().unwrap(),
+    ]))
+    .unwrap();
+
+    let ppm = fs::read(&shot).unwrap();
+    assert!(ppm.starts_with(b"P6\n256 240\n255\n"));
+}
+
+#[test]
+fn test_run_unimplemented_opcode() {
+    let dir = TempDir::new().unwrap();
+    let rom = dir.path().join("php.nes");
+    // NOP; PHP
+    fs::write(&rom, test_rom(&[0xEA, 0x08], 0xC000, 0xC000, false)).unwrap();
+
+    assert_eq!(
+        run(&args(&["run", rom.to_str().unwrap()])),
+        Err(CliError::from(
+            "unimplemented opcode $08 at $C001".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_binary_past_end_of_memory() {
+    let dir = TempDir::new().unwrap();
+    let bin = dir.path().join("long.bin");
+    fs::write(&bin, [0xEA; 4]).unwrap();
+
+    for command in ["trace", "debug", "term"] {
+        assert_eq!(
+            run(&args(&[command, bin.to_str().unwrap(), "--org", "FFFE"])),
+            Err(CliError::from(
+                "4 bytes loaded at $FFFE run past $FFFF".to_string()
+            )),
+            "{}",
+            command
+        );
+    }
+}
+
+#[test]
+fn test_trace_stops_at_brk() {
+    // LDX #$01; INX; BRK
+    let mut nes = Nes::new();
+    nes.cpu.load_at(0x8000, &[0xA2, 0x01, 0xE8, 0x00]).unwrap();
+    nes.cpu.program_counter = 0x8000;
+
+    let mut out = Vec::new();
+    trace_instructions(&mut nes, 100, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("8000  A2 01     LDX #$01"));
+    assert!(lines[2].starts_with("8003  00        BRK"));
+
+    let mut nes = Nes::new();
+    nes.cpu.load_at(0x8000, &[0xA2, 0x01, 0xE8, 0x00]).unwrap();
+    nes.cpu.program_counter = 0x8000;
+    let mut out = Vec::new();
+    trace_instructions(&mut nes, 1, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+}
+
+#[test]
+fn test_term_arguments() {
+    assert_eq!(
+        run(&args(&["term", "snake.bin", "--speed", "fast"])),
+        Err(CliError::usage("invalid --speed 'fast'"))
+    );
+    assert_eq!(
+        run(&args(&["term", "snake.bin", "--input", "G0"])),
+        Err(CliError::usage("invalid address 'G0'"))
+    );
+
+    let dir = TempDir::new().unwrap();
+    let rom = dir.path().join("loop.nes");
+    fs::write(&rom, looping_rom()).unwrap();
+    assert_eq!(
+        run(&args(&["term", rom.to_str().unwrap()])),
+        Err(CliError::from(
+            "term runs raw 6502 binaries, not .nes files".to_string()
+        ))
+    );
+}
@@ -0,0 +1,206 @@
+//! Klaus Dormann's 6502 functional and decimal tests,
+//! https://github.com/Klaus2m5/6502_65C02_functional_tests
+//!
+//! The tests read `6502_functional_test.bin` (64K image, default
+//! configuration) and an assembled `6502_decimal_test.bin` from
+//! `src/cpu/golden/`, and say they skipped when a binary is missing. The
+//! CPU does not implement every opcode the suites use yet, so until it does
+//! they check how far each suite gets instead of a pass.
+
+use std::fs;
+use std::path::PathBuf;
+
+use super::*;
+
+const FUNCTIONAL_ENTRY: u16 = 0x0400;
+const FUNCTIONAL_SUCCESS: u16 = 0x3469;
+// the functional test keeps the number of the running test here
+const FUNCTIONAL_TEST_CASE: u16 = 0x0200;
+
+// TXS at $0403 is not implemented, the run stops while initialising;
+// raise this as the CPU learns the opcodes the later cases use
+const FUNCTIONAL_REACHED: u8 = 0x00;
+
+const DECIMAL_ENTRY: u16 = 0x0200;
+// STY, the second instruction, is not implemented yet
+const DECIMAL_STOPS_AT: u8 = 0x84;
+// 0 when every decimal mode result was correct
+const DECIMAL_ERROR: u16 = 0x000B;
+
+const INSTRUCTION_LIMIT: u64 = 100_000_000;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    /// Jumped or branched to itself.
+    Trapped(u16),
+    /// Reached BRK, which stops `CPU::step`.
+    Halted(u16),
+    /// Hit an instruction the CPU does not implement.
+    Unimplemented(u16),
+    TimedOut,
+}
+
+/// Reads a binary from `src/cpu/golden`, `None` when it is not there.
+fn golden_image(name: &str) -> Option<Vec<u8>> {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "src", "cpu", "golden", name]
+        .iter()
+        .collect();
+    match fs::read(&path) {
+        Ok(image) => Some(image),
+        Err(e) => {
+            eprintln!("skipping, cannot read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Flat 64K machine with `image` loaded from $0000 and PC at `entry`.
+fn flat_cpu(image: &[u8], entry: u16) -> CPU {
+    let mut cpu = CPU::new();
+    cpu.flat_memory = true;
+    cpu.load_at(0x0000, image).unwrap();
+    cpu.program_counter = entry;
+    cpu
+}
+
+/// Runs until an instruction leaves PC where it was, which is how both
+/// test suites signal success and failure.
+fn run_until_trap(cpu: &mut CPU, limit: u64) -> Outcome {
+    for _ in 0..limit {
+        let pc = cpu.program_counter;
+        match cpu.step() {
+            true if cpu.program_counter == pc => return Outcome::Trapped(pc),
+            true => {}
+            false if cpu.unimplemented().is_some() => return Outcome::Unimplemented(pc),
+            false => return Outcome::Halted(pc),
+        }
+    }
+    Outcome::TimedOut
+}
+
+fn functional_report(cpu: &CPU, outcome: &Outcome) -> Result<(), String> {
+    match outcome {
+        Outcome::Trapped(FUNCTIONAL_SUCCESS) => Ok(()),
+        _ => Err(format!(
+            "functional test failed in test ${:02X}: {:?} after {} cycles",
+            cpu.mem_peek(FUNCTIONAL_TEST_CASE),
+            outcome,
+            cpu.cycles
+        )),
+    }
+}
+
+#[test]
+fn test_klaus_functional() {
+    let Some(image) = golden_image("6502_functional_test.bin") else {
+        return;
+    };
+
+    let mut cpu = flat_cpu(&image, FUNCTIONAL_ENTRY);
+    let outcome = run_until_trap(&mut cpu, INSTRUCTION_LIMIT);
+
+    if let Err(report) = functional_report(&cpu, &outcome) {
+        assert!(matches!(outcome, Outcome::Unimplemented(_)), "{}", report);
+        assert_eq!(
+            cpu.mem_peek(FUNCTIONAL_TEST_CASE),
+            FUNCTIONAL_REACHED,
+            "{}",
+            report
+        );
+    }
+}
+
+#[test]
+fn test_klaus_decimal() {
+    let Some(image) = golden_image("6502_decimal_test.bin") else {
+        return;
+    };
+
+    let mut cpu = flat_cpu(&image, DECIMAL_ENTRY);
+    let outcome = run_until_trap(&mut cpu, INSTRUCTION_LIMIT);
+
+    if let Outcome::Unimplemented(pc) = outcome {
+        assert_eq!(
+            cpu.unimplemented().map(|u| u.opcode),
+            Some(DECIMAL_STOPS_AT),
+            "decimal test stopped at ${:04X}",
+            pc
+        );
+        return;
+    }
+    assert!(
+        matches!(outcome, Outcome::Trapped(_) | Outcome::Halted(_)),
+        "decimal test did not finish: {:?}",
+        outcome
+    );
+    assert_eq!(
+        cpu.mem_peek(DECIMAL_ERROR),
+        0,
+        "decimal test reported an error"
+    );
+}
+
+/// Mimics the functional test: numbered cases, a failure trap and a success
+/// trap at $3469.
+fn sample_image(expected_x: u8) -> Vec<u8> {
+    let program = crate::asm::assemble(&format!(
+        "
+                .org $0400
+                LDA #1
+                STA $0200
+                LDX #3
+                CPX #3
+        fail1:  BNE fail1
+                LDA #2
+                STA $0200
+                DEX
+                CPX #{}
+        fail2:  BNE fail2
+                JMP success
+                .org $3469
+        success: JMP success
+        ",
+        expected_x
+    ))
+    .unwrap();
+
+    let mut image = vec![0; 0x10000];
+    let start = program.origin as usize;
+    image[start..start + program.bytes.len()].copy_from_slice(&program.bytes);
+    image
+}
+
+#[test]
+fn test_trap_detection_success() {
+    let mut cpu = flat_cpu(&sample_image(2), FUNCTIONAL_ENTRY);
+
+    let outcome = run_until_trap(&mut cpu, 1000);
+
+    assert_eq!(outcome, Outcome::Trapped(0x3469));
+    assert_eq!(functional_report(&cpu, &outcome), Ok(()));
+}
+
+#[test]
+fn test_trap_detection_reports_test_case() {
+    let mut cpu = flat_cpu(&sample_image(5), FUNCTIONAL_ENTRY);
+
+    let outcome = run_until_trap(&mut cpu, 1000);
+    let report = functional_report(&cpu, &outcome).unwrap_err();
+
+    assert_eq!(outcome, Outcome::Trapped(0x0413));
+    assert!(
+        report.starts_with("functional test failed in test $02"),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn test_trap_detection_unimplemented_opcode() {
+    // PHP is not implemented
+    let mut image = vec![0; 0x10000];
+    image[0x0400] = 0x08;
+    let mut cpu = flat_cpu(&image, FUNCTIONAL_ENTRY);
+
+    assert_eq!(run_until_trap(&mut cpu, 10), Outcome::Unimplemented(0x0400));
+}
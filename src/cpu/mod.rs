@@ -1,9 +1,28 @@
+pub mod rewind;
+pub mod state;
+pub mod undo;
+pub mod watch;
+
+use std::fmt;
+
 use bitflags::bitflags;
 
+use crate::cdl::CdlFlags;
+use crate::cdl::CodeDataLogger;
+use crate::cheat::Cheats;
 use crate::helper::AddressingMode;
 use crate::helper::EmmulationHelpers;
-use crate::helper::OpCode;
 use crate::helper::OpCodeCat;
+use crate::input::InputDevice;
+use crate::joypad::Joypad;
+use crate::nes::bus::NesBus;
+use crate::profiler::Profiler;
+
+use undo::UndoLog;
+use watch::Access;
+use watch::Breakpoint;
+use watch::WatchHit;
+use watch::Watchpoint;
 
 bitflags! {
     /// # Status Register (P) http://wiki.nesdev.com/w/index.php/Status_flags
@@ -18,6 +37,7 @@ bitflags! {
     ///  | +--------------- Overflow Flag
     ///  +----------------- Negative Flag
     ///
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct CpuFlags: u8 {
         const CARRY             = 0b00000001;
         const ZERO              = 0b00000010;
@@ -33,6 +53,49 @@ bitflags! {
 const STACK: u16 = 0x0100;
 const STACK_RESET: u8 = 0xfd;
 
+const NMI_VECTOR: u16 = 0xFFFA;
+const RESET_VECTOR: u16 = 0xFFFC;
+
+const OAM_DMA: u16 = 0x4014;
+const APU_STATUS: u16 = 0x4015;
+const JOYPAD1: u16 = 0x4016;
+const JOYPAD2: u16 = 0x4017;
+
+// Controller reads only drive the low bits of the data bus, the rest keep
+// whatever was last on it, which for `LDA $4016` is the high address byte.
+const JOYPAD_OPEN_BUS_MASK: u8 = 0b1110_0000;
+
+/// An opcode `CPU::step` does not know how to execute, with the address it
+/// was fetched from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unimplemented {
+    pub opcode: u8,
+    pub pc: u16,
+}
+
+impl fmt::Display for Unimplemented {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unimplemented opcode ${:02X} at ${:04X}",
+            self.opcode, self.pc
+        )
+    }
+}
+
+/// One read or write on the bus, as recorded in `CPU::bus_log`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusCycle {
+    pub addr: u16,
+    pub value: u8,
+    pub access: Access,
+}
+
+/// With the `serde` feature only the machine state is serialized: registers,
+/// cycles and memory. Controllers, the bus and debugging aids are skipped
+/// and, like any field missing from the input, come from `CPU::new`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct CPU {
     pub register_a: u8,
     pub register_x: u8,
@@ -40,7 +103,61 @@ pub struct CPU {
     pub status: CpuFlags,
     pub program_counter: u16,
     pub stack_pointer: u8,
-    memory: [u8; 0xFFFF],
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub port1: InputDevice,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub port2: InputDevice,
+    pub cycles: u64,
+    /// PPU, APU and cartridge. Without them the CPU sees flat memory.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub bus: Option<NesBus>,
+    /// Treat the controller ports as RAM too, for test suites that expect a
+    /// plain 64K address space.
+    pub flat_memory: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub watchpoints: Vec<Watchpoint>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub breakpoints: Vec<Breakpoint>,
+    /// Instruction and cycle counts, when profiling.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub profiler: Option<Profiler>,
+    /// Which PRG ROM bytes were executed or read, when logging.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cdl: Option<CodeDataLogger>,
+    /// Per-instruction history for `step_back`, when recording.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub undo: Option<UndoLog>,
+    /// Game Genie codes patch `mem_read`, freezes are applied by
+    /// `apply_freezes`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cheats: Cheats,
+    /// Every bus read and write in order, when recording. Peeks are left
+    /// out.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub bus_log: Option<Vec<BusCycle>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    watch_hit: Option<WatchHit>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    page_crossed: bool,
+    // opcode and operand fetches, which READ watchpoints ignore
+    #[cfg_attr(feature = "serde", serde(skip))]
+    fetching_code: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    unimplemented: Option<Unimplemented>,
+    #[cfg_attr(feature = "serde", serde(with = "state::memory"))]
+    memory: Box<[u8; 0x10000]>,
+}
+
+// on the heap, so the CPU is cheap to move and never builds the array on
+// the stack
+fn zeroed_memory() -> Box<[u8; 0x10000]> {
+    vec![0; 0x10000].into_boxed_slice().try_into().unwrap()
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CPU {
@@ -52,22 +169,204 @@ impl CPU {
             status: CpuFlags::from_bits_truncate(0b100100),
             stack_pointer: STACK_RESET,
             program_counter: 0,
-            memory: [0; 0xFFFF],
+            port1: InputDevice::Joypad(Joypad::new()),
+            port2: InputDevice::Joypad(Joypad::new()),
+            cycles: 0,
+            bus: None,
+            flat_memory: false,
+            watchpoints: Vec::new(),
+            breakpoints: Vec::new(),
+            profiler: None,
+            cdl: None,
+            undo: None,
+            cheats: Cheats::new(),
+            bus_log: None,
+            watch_hit: None,
+            page_crossed: false,
+            fetching_code: false,
+            unimplemented: None,
+            memory: zeroed_memory(),
+        }
+    }
+
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        let mut value = self.bus_read(addr);
+        if !self.cheats.is_empty() {
+            value = self.cheats.patch_read(addr, value);
+        }
+        if addr >= 0x8000 {
+            if let Some(cdl) = self.cdl.as_mut() {
+                cdl.log_read(addr);
+            }
+        }
+        if !self.watchpoints.is_empty() && !self.fetching_code {
+            self.watch_access(addr, value, Access::READ);
+        }
+        self.log_bus(addr, value, Access::READ);
+        value
+    }
+
+    fn bus_read(&mut self, addr: u16) -> u8 {
+        if let Some(bus) = self.bus.as_mut() {
+            match addr {
+                0x0000..=0x1FFF => return self.memory[(addr & 0x07FF) as usize],
+                0x2000..=0x3FFF => return bus.ppu_register_read(addr),
+                APU_STATUS => return bus.apu.read_status(),
+                0x8000..=0xFFFF => return bus.rom.read_prg_rom(addr),
+                _ => {}
+            }
+        }
+
+        match addr {
+            JOYPAD1 if !self.flat_memory => {
+                self.port1.read() | ((addr >> 8) as u8 & JOYPAD_OPEN_BUS_MASK)
+            }
+            JOYPAD2 if !self.flat_memory => {
+                self.port2.read() | ((addr >> 8) as u8 & JOYPAD_OPEN_BUS_MASK)
+            }
+            _ => self.memory[addr as usize],
         }
     }
 
-    fn mem_read(&self, addr: u16) -> u8 {
-        self.memory[addr as usize]
+    /// Reads memory without side effects: PPU/APU registers and controller
+    /// ports report what a read would return but keep their state.
+    pub fn mem_peek(&self, addr: u16) -> u8 {
+        let value = self.bus_peek(addr);
+        if self.cheats.is_empty() {
+            value
+        } else {
+            self.cheats.patch_read(addr, value)
+        }
+    }
+
+    fn bus_peek(&self, addr: u16) -> u8 {
+        if let Some(bus) = self.bus.as_ref() {
+            match addr {
+                0x0000..=0x1FFF => return self.memory[(addr & 0x07FF) as usize],
+                0x2000..=0x3FFF => return bus.ppu_register_peek(addr),
+                APU_STATUS => return bus.apu.peek_status(),
+                0x8000..=0xFFFF => return bus.rom.read_prg_rom(addr),
+                _ => {}
+            }
+        }
+
+        match addr {
+            JOYPAD1 if !self.flat_memory => {
+                self.port1.peek() | ((addr >> 8) as u8 & JOYPAD_OPEN_BUS_MASK)
+            }
+            JOYPAD2 if !self.flat_memory => {
+                self.port2.peek() | ((addr >> 8) as u8 & JOYPAD_OPEN_BUS_MASK)
+            }
+            _ => self.memory[addr as usize],
+        }
     }
 
     fn mem_write(&mut self, addr: u16, data: u8) {
-        self.memory[addr as usize] = data;
+        if !self.watchpoints.is_empty() {
+            self.watch_access(addr, data, Access::WRITE);
+        }
+        self.undo_write(addr);
+        self.log_bus(addr, data, Access::WRITE);
+        self.bus_write(addr, data);
+    }
+
+    fn log_bus(&mut self, addr: u16, value: u8, access: Access) {
+        if let Some(log) = self.bus_log.as_mut() {
+            log.push(BusCycle {
+                addr,
+                value,
+                access,
+            });
+        }
+    }
+
+    fn bus_write(&mut self, addr: u16, data: u8) {
+        if let Some(bus) = self.bus.as_mut() {
+            match addr {
+                0x0000..=0x1FFF => {
+                    self.memory[(addr & 0x07FF) as usize] = data;
+                    return;
+                }
+                0x2000..=0x3FFF => {
+                    bus.ppu_register_write(addr, data);
+                    return;
+                }
+                OAM_DMA => {
+                    self.oam_dma(data);
+                    return;
+                }
+                0x4000..=0x4013 | APU_STATUS | JOYPAD2 => {
+                    bus.apu.write_register(addr, data);
+                    return;
+                }
+                // NROM has no registers, writes to ROM go nowhere
+                0x8000..=0xFFFF => return,
+                _ => {}
+            }
+        }
+
+        match addr {
+            // both ports share the strobe line, $4017 writes belong to the APU
+            JOYPAD1 if !self.flat_memory => {
+                self.port1.write(data);
+                self.port2.write(data);
+            }
+            _ => self.memory[addr as usize] = data,
+        }
+    }
+
+    /// Debugger write into RAM as the CPU sees it, mirrors included. I/O
+    /// registers and cartridge ROM are left alone, returns false for those.
+    pub fn mem_poke(&mut self, addr: u16, data: u8) -> bool {
+        if self.bus.is_some() {
+            match addr {
+                0x0000..=0x1FFF => self.memory[(addr & 0x07FF) as usize] = data,
+                0x4020..=0x7FFF => self.memory[addr as usize] = data,
+                _ => return false,
+            }
+        } else {
+            self.memory[addr as usize] = data;
+        }
+        true
+    }
+
+    /// Writes every enabled freeze cheat into RAM, once per frame.
+    pub fn apply_freezes(&mut self) {
+        for (addr, value) in self.cheats.freezes() {
+            self.mem_poke(addr, value);
+        }
     }
 
-    fn mem_read_u16(&self, pos: u16) -> u16 {
+    /// $4014: copies a whole CPU page into OAM, halting the CPU meanwhile.
+    fn oam_dma(&mut self, page: u8) {
+        let mut buffer = [0u8; 256];
+        let hi = (page as u16) << 8;
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.mem_read(hi + i as u16);
+        }
+
+        if let Some(bus) = self.bus.as_mut() {
+            bus.ppu.write_oam_dma(&buffer);
+        }
+        self.cycles += 513 + (self.cycles % 2);
+    }
+
+    /// Standard controller for `player` 1-4. Players 3 and 4 are only
+    /// available when a Four Score is plugged into the ports.
+    pub fn joypad_mut(&mut self, player: usize) -> Option<&mut Joypad> {
+        match player {
+            1 => self.port1.joypad_mut(0),
+            2 => self.port2.joypad_mut(0),
+            3 => self.port1.joypad_mut(1),
+            4 => self.port2.joypad_mut(1),
+            _ => None,
+        }
+    }
+
+    fn mem_read_u16(&mut self, pos: u16) -> u16 {
         let lo = self.mem_read(pos) as u16;
         let hi = self.mem_read(pos + 1) as u16;
-        (hi << 8) | (lo as u16)
+        (hi << 8) | lo
     }
 
     fn mem_write_u16(&mut self, pos: u16, data: u16) {
@@ -78,25 +377,28 @@ impl CPU {
     }
 
     fn stack_push(&mut self, data: u8) {
-        self.mem_write((STACK as u16) + self.stack_pointer as u16, data);
+        self.mem_write(STACK + self.stack_pointer as u16, data);
         self.stack_pointer = self.stack_pointer.wrapping_sub(1)
     }
 
+    // high byte first, so the address reads little endian from the stack
     fn stack_push_u16(&mut self, data: u16) {
-        let lo = (data >> 8) as u8;
-        let hi = (data & 0xFF) as u8;
+        let hi = (data >> 8) as u8;
+        let lo = (data & 0xFF) as u8;
         self.stack_push(hi);
         self.stack_push(lo);
     }
 
+    #[allow(dead_code)]
     fn stack_pop(&mut self) -> u8 {
         self.stack_pointer = self.stack_pointer.wrapping_add(1);
-        self.mem_read((STACK as u16) + self.stack_pointer as u16)
+        self.mem_read(STACK + self.stack_pointer as u16)
     }
 
+    #[allow(dead_code)]
     fn stack_pop_u16(&mut self) -> u16 {
-        let hi = self.stack_pop() as u16;
         let lo = self.stack_pop() as u16;
+        let hi = self.stack_pop() as u16;
 
         hi << 8 | lo
     }
@@ -107,8 +409,29 @@ impl CPU {
         self.register_y = 0;
         self.stack_pointer = STACK_RESET;
         self.status = CpuFlags::from_bits_truncate(0b100100);
+        self.unimplemented = None;
 
-        self.program_counter = self.mem_read_u16(0xFFFC);
+        self.program_counter = self.mem_read_u16(RESET_VECTOR);
+        // the reset sequence takes 7 cycles before the first instruction
+        self.cycles = 7;
+    }
+
+    /// Non-maskable interrupt, raised by the PPU when vertical blank starts.
+    pub fn nmi(&mut self) {
+        self.stack_push_u16(self.program_counter);
+
+        let mut flag = self.status;
+        flag.remove(CpuFlags::BREAK);
+        flag.insert(CpuFlags::BREAK2);
+        self.stack_push(flag.bits());
+
+        self.set_interupt_disable();
+        self.cycles += 7;
+        self.program_counter = self.mem_read_u16(NMI_VECTOR);
+
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.interrupt(self.program_counter, 7);
+        }
     }
 
     pub fn load_and_run(&mut self, program: Vec<u8>) {
@@ -119,10 +442,48 @@ impl CPU {
 
     pub fn load(&mut self, program: Vec<u8>) {
         self.memory[0x8000..(0x8000 + program.len())].copy_from_slice(&program[..]);
-        self.mem_write_u16(0xFFFC, 0x8000);
+        self.mem_write_u16(RESET_VECTOR, 0x8000);
+    }
+
+    /// Copies `data` into memory at `origin`, e.g. a full 64K test image.
+    /// Goes straight to the memory array and leaves the reset vector alone.
+    /// Fails when `data` would run past $FFFF.
+    pub fn load_at(&mut self, origin: u16, data: &[u8]) -> Result<(), String> {
+        let start = origin as usize;
+        if start + data.len() > self.memory.len() {
+            return Err(format!(
+                "{} bytes loaded at ${:04X} run past $FFFF",
+                data.len(),
+                origin
+            ));
+        }
+        self.memory[start..start + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    // whether the following reads fetch code or data, for the logger and
+    // for READ watchpoints
+    fn cdl_access(&mut self, access: CdlFlags) {
+        self.fetching_code = access.contains(CdlFlags::CODE);
+        if let Some(cdl) = self.cdl.as_mut() {
+            cdl.set_access(access);
+        }
     }
 
-    fn get_operand_address(&self, mode: &AddressingMode) -> u16 {
+    // operand bytes are read as code, what they point at as data
+    fn get_operand_address(&mut self, mode: &AddressingMode) -> u16 {
+        let addr = self.operand_address(mode);
+        match mode {
+            AddressingMode::Immediate => {}
+            AddressingMode::Indirect_X | AddressingMode::Indirect_Y => {
+                self.cdl_access(CdlFlags::DATA | CdlFlags::INDIRECT_DATA)
+            }
+            _ => self.cdl_access(CdlFlags::DATA),
+        }
+        addr
+    }
+
+    fn operand_address(&mut self, mode: &AddressingMode) -> u16 {
         match mode {
             AddressingMode::Immediate => self.program_counter,
 
@@ -132,32 +493,32 @@ impl CPU {
 
             AddressingMode::ZeroPage_X => {
                 let base = self.mem_read(self.program_counter);
-                let addr = base.wrapping_add(self.register_x) as u16;
-                addr
+                base.wrapping_add(self.register_x) as u16
             }
 
             AddressingMode::ZeroPage_Y => {
                 let base = self.mem_read(self.program_counter);
-                let addr = base.wrapping_add(self.register_y) as u16;
-                addr
+                base.wrapping_add(self.register_y) as u16
             }
 
             AddressingMode::Absolute_X => {
                 let base = self.mem_read_u16(self.program_counter);
                 let addr = base.wrapping_add(self.register_x as u16);
+                self.page_crossed = base & 0xFF00 != addr & 0xFF00;
                 addr
             }
 
             AddressingMode::Absolute_Y => {
                 let base = self.mem_read_u16(self.program_counter);
                 let addr = base.wrapping_add(self.register_y as u16);
+                self.page_crossed = base & 0xFF00 != addr & 0xFF00;
                 addr
             }
 
             AddressingMode::Indirect_X => {
                 let base = self.mem_read(self.program_counter);
 
-                let ptr: u8 = (base as u8).wrapping_add(self.register_x);
+                let ptr: u8 = base.wrapping_add(self.register_x);
                 let lo = self.mem_read(ptr as u16);
                 let hi = self.mem_read(ptr.wrapping_add(1) as u16);
                 (hi as u16) << 8 | (lo as u16)
@@ -166,9 +527,10 @@ impl CPU {
                 let base = self.mem_read(self.program_counter);
 
                 let lo = self.mem_read(base as u16);
-                let hi = self.mem_read((base as u8).wrapping_add(1) as u16);
+                let hi = self.mem_read(base.wrapping_add(1) as u16);
                 let deref_base = (hi as u16) << 8 | (lo as u16);
                 let deref = deref_base.wrapping_add(self.register_y as u16);
+                self.page_crossed = deref_base & 0xFF00 != deref & 0xFF00;
                 deref
             }
 
@@ -230,14 +592,12 @@ impl CPU {
     fn asl(&mut self, mode: &AddressingMode) {
         let addr = self.get_operand_address(mode);
 
-        let mut value = 0;
-
         // impossible memory value means we should use register a
-        if addr == 0xffff {
-            value = self.register_a;
+        let mut value = if addr == 0xffff {
+            self.register_a
         } else {
-            value = self.mem_read(addr);
-        }
+            self.mem_read(addr)
+        };
 
         if value >> 7 == 1 {
             self.set_carry_flag();
@@ -245,7 +605,7 @@ impl CPU {
             self.clear_carry_flag();
         }
 
-        value = value << 1;
+        value <<= 1;
 
         if addr == 0xffff {
             self.set_register_a(value);
@@ -342,7 +702,7 @@ impl CPU {
         let addr = self.get_operand_address(mode);
         let value = self.mem_read(addr);
 
-        self.register_a = self.register_a ^ value;
+        self.register_a ^= value;
 
         self.update_zero_and_negative_flags(self.register_a);
     }
@@ -379,10 +739,14 @@ impl CPU {
     fn branch(&mut self, condition: bool) {
         if condition {
             let jump: i8 = self.mem_read(self.program_counter) as i8;
-            let jump_addr = self
-                .program_counter
-                .wrapping_add(1)
-                .wrapping_add(jump as u16);
+            let next_instruction = self.program_counter.wrapping_add(1);
+            let jump_addr = next_instruction.wrapping_add(jump as u16);
+
+            self.cycles += 1;
+            if next_instruction & 0xFF00 != jump_addr & 0xFF00 {
+                self.cycles += 1;
+            }
+
             self.program_counter = jump_addr;
         }
     }
@@ -408,6 +772,7 @@ impl CPU {
         self.status.remove(CpuFlags::CARRY);
     }
 
+    #[allow(dead_code)]
     fn set_decimal_mode(&mut self) {
         self.status.insert(CpuFlags::DECIMAL_MODE);
     }
@@ -439,191 +804,294 @@ impl CPU {
     }
 
     pub fn run(&mut self) {
+        self.run_with_callback(|_| {});
+    }
+
+    /// Like `run`, calling `callback` before every instruction. Tracers and
+    /// debuggers hook in here.
+    ///
+    /// Also stops when a watchpoint or breakpoint fires, see
+    /// `take_watch_hit`. Breakpoints on the first instruction are skipped so
+    /// that running again continues past them.
+    pub fn run_with_callback<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(&mut CPU),
+    {
+        self.watch_hit = None;
+        let mut first = true;
         loop {
-            let opscode = self.mem_read(self.program_counter);
+            if !first {
+                if let Some(hit) = self.check_execute() {
+                    self.watch_hit = Some(hit);
+                    break;
+                }
+            }
+            first = false;
 
-            let val = EmmulationHelpers::get_op_code_struct(opscode);
+            callback(self);
+            if !self.step() || self.watch_hit.is_some() {
+                break;
+            }
+        }
+    }
 
-            self.program_counter += 1;
-            let program_counter_state = self.program_counter;
+    /// Executes a single instruction. Returns false once BRK is reached, or
+    /// without executing anything when the opcode at PC is not implemented,
+    /// see `unimplemented`.
+    pub fn step(&mut self) -> bool {
+        self.undo_begin();
+        self.unimplemented = None;
+        let pc = self.program_counter;
+        let cycles = self.cycles;
+        // peeked up front, the instruction may overwrite itself
+        let opcode = self.profiler.as_ref().map(|_| self.mem_peek(pc));
+
+        let running = self.execute();
+        if self.unimplemented.is_some() {
+            self.undo_cancel();
+        }
+        // reads between instructions, like interrupt vectors, are data
+        self.cdl_access(CdlFlags::DATA);
 
-            match val.match_code {
-                OpCodeCat::LDA => {
-                    self.lda(&val.mode);
-                }
+        if let (Some(profiler), Some(opcode)) = (self.profiler.as_mut(), opcode) {
+            if self.unimplemented.is_none() {
+                profiler.record(pc, opcode, self.cycles - cycles, self.program_counter);
+            }
+        }
+        running
+    }
 
-                OpCodeCat::STA => {
-                    self.sta(&val.mode);
-                }
+    /// The opcode that stopped the last `step`, if it was one the CPU does
+    /// not implement. PC still points at it.
+    pub fn unimplemented(&self) -> Option<Unimplemented> {
+        self.unimplemented
+    }
 
-                OpCodeCat::ASL => {
-                    self.asl(&val.mode);
-                }
+    fn execute(&mut self) -> bool {
+        self.cdl_access(CdlFlags::CODE);
+        let opscode = self.mem_read(self.program_counter);
 
-                OpCodeCat::AND => {
-                    self.and(&val.mode);
-                }
+        let val = EmmulationHelpers::get_op_code_struct(opscode);
+        if let Some(cdl) = self.cdl.as_mut() {
+            cdl.log_instruction(self.program_counter, val.bytes);
+        }
 
-                OpCodeCat::TAX => {
-                    self.tax();
-                }
+        self.program_counter += 1;
+        let program_counter_state = self.program_counter;
+        self.page_crossed = false;
 
-                OpCodeCat::TAY => {
-                    self.tay();
-                }
+        match val.match_code {
+            OpCodeCat::LDA => {
+                self.lda(&val.mode);
+            }
 
-                OpCodeCat::INX => {
-                    self.inx();
-                }
+            OpCodeCat::STA => {
+                self.sta(&val.mode);
+            }
 
-                OpCodeCat::BCC => {
-                    self.branch(!self.status.contains(CpuFlags::CARRY));
-                }
+            OpCodeCat::ASL => {
+                self.asl(&val.mode);
+            }
 
-                OpCodeCat::BCS => {
-                    self.branch(self.status.contains(CpuFlags::CARRY));
-                }
+            OpCodeCat::AND => {
+                self.and(&val.mode);
+            }
 
-                OpCodeCat::BEQ => {
-                    self.branch(self.status.contains(CpuFlags::ZERO));
-                }
+            OpCodeCat::TAX => {
+                self.tax();
+            }
 
-                OpCodeCat::BIT => {
-                    self.bit(&val.mode);
-                }
+            OpCodeCat::TAY => {
+                self.tay();
+            }
 
-                OpCodeCat::BMI => {
-                    self.branch(self.status.contains(CpuFlags::NEGATIVE));
-                }
+            OpCodeCat::INX => {
+                self.inx();
+            }
 
-                OpCodeCat::BNE => {
-                    self.branch(!self.status.contains(CpuFlags::ZERO));
-                }
+            OpCodeCat::BCC => {
+                self.branch(!self.status.contains(CpuFlags::CARRY));
+            }
 
-                OpCodeCat::BPL => {
-                    self.branch(!self.status.contains(CpuFlags::NEGATIVE));
-                }
+            OpCodeCat::BCS => {
+                self.branch(self.status.contains(CpuFlags::CARRY));
+            }
 
-                OpCodeCat::BVC => {
-                    self.branch(!self.status.contains(CpuFlags::OVERFLOW));
-                }
+            OpCodeCat::BEQ => {
+                self.branch(self.status.contains(CpuFlags::ZERO));
+            }
 
-                OpCodeCat::BVS => {
-                    self.branch(self.status.contains(CpuFlags::OVERFLOW));
-                }
+            OpCodeCat::BIT => {
+                self.bit(&val.mode);
+            }
 
-                OpCodeCat::CLC => {
-                    self.clear_carry_flag();
-                }
+            OpCodeCat::BMI => {
+                self.branch(self.status.contains(CpuFlags::NEGATIVE));
+            }
 
-                OpCodeCat::CLD => {
-                    self.clear_decimal_mode();
-                }
+            OpCodeCat::BNE => {
+                self.branch(!self.status.contains(CpuFlags::ZERO));
+            }
 
-                OpCodeCat::CLI => {
-                    self.clear_interrupt_disable();
-                }
+            OpCodeCat::BPL => {
+                self.branch(!self.status.contains(CpuFlags::NEGATIVE));
+            }
 
-                OpCodeCat::CLV => {
-                    self.clear_overflow_flag();
-                }
+            OpCodeCat::BVC => {
+                self.branch(!self.status.contains(CpuFlags::OVERFLOW));
+            }
 
-                OpCodeCat::CMP => {
-                    self.compare(&val.mode, self.register_a);
-                }
+            OpCodeCat::BVS => {
+                self.branch(self.status.contains(CpuFlags::OVERFLOW));
+            }
 
-                OpCodeCat::CPX => {
-                    self.compare(&val.mode, self.register_x);
-                }
+            OpCodeCat::CLC => {
+                self.clear_carry_flag();
+            }
 
-                OpCodeCat::CPY => {
-                    self.compare(&val.mode, self.register_y);
-                }
+            OpCodeCat::CLD => {
+                self.clear_decimal_mode();
+            }
 
-                OpCodeCat::DEC => {
-                    self.dec(&val.mode);
-                }
+            OpCodeCat::CLI => {
+                self.clear_interrupt_disable();
+            }
 
-                OpCodeCat::DEX => {
-                    self.dex();
-                }
+            OpCodeCat::CLV => {
+                self.clear_overflow_flag();
+            }
 
-                OpCodeCat::DEY => {
-                    self.dey();
-                }
+            OpCodeCat::CMP => {
+                self.compare(&val.mode, self.register_a);
+            }
 
-                OpCodeCat::EOR => {
-                    self.eor(&val.mode);
-                }
+            OpCodeCat::CPX => {
+                self.compare(&val.mode, self.register_x);
+            }
 
-                OpCodeCat::INC => {
-                    self.inc(&val.mode);
-                }
+            OpCodeCat::CPY => {
+                self.compare(&val.mode, self.register_y);
+            }
 
-                OpCodeCat::INY => {
-                    self.iny();
-                }
+            OpCodeCat::DEC => {
+                self.dec(&val.mode);
+            }
 
-                OpCodeCat::JMP_ABS => {
-                    let addr = self.mem_read_u16(self.program_counter);
-                    self.program_counter = addr;
-                }
+            OpCodeCat::DEX => {
+                self.dex();
+            }
 
-                OpCodeCat::JMP_IND => {
-                    let addr = self.mem_read_u16(self.program_counter);
+            OpCodeCat::DEY => {
+                self.dey();
+            }
 
-                    // Implement 6502 indirect jump bug
+            OpCodeCat::EOR => {
+                self.eor(&val.mode);
+            }
 
-                    let indirect_ref = if addr & 0x00FF == 0x00FF {
-                        let lo = self.mem_read(addr);
-                        let hi = self.mem_read(addr & 0xFF00);
-                        (hi as u16) << 8 | (lo as u16)
-                    } else {
-                        self.mem_read_u16(addr)
-                    };
+            OpCodeCat::INC => {
+                self.inc(&val.mode);
+            }
 
-                    self.program_counter = indirect_ref;
-                }
+            OpCodeCat::INY => {
+                self.iny();
+            }
 
-                OpCodeCat::JSR => {
-                    self.stack_push_u16(self.program_counter + 2 - 1);
-                    let target_address = self.mem_read_u16(self.program_counter);
-                    self.program_counter = target_address;
-                }
+            OpCodeCat::JMP_ABS => {
+                let addr = self.mem_read_u16(self.program_counter);
+                self.program_counter = addr;
+            }
 
-                OpCodeCat::LDX => {
-                    self.ldx(&val.mode);
-                }
+            OpCodeCat::JMP_IND => {
+                let addr = self.mem_read_u16(self.program_counter);
+                self.cdl_access(CdlFlags::DATA | CdlFlags::INDIRECT_DATA);
 
-                OpCodeCat::LDY => {
-                    self.ldy(&val.mode);
-                }
+                // Implement 6502 indirect jump bug
 
-                OpCodeCat::LSR_ACC => {
-                    self.lsr_acc();
-                }
+                let indirect_ref = if addr & 0x00FF == 0x00FF {
+                    let lo = self.mem_read(addr);
+                    let hi = self.mem_read(addr & 0xFF00);
+                    (hi as u16) << 8 | (lo as u16)
+                } else {
+                    self.mem_read_u16(addr)
+                };
 
-                OpCodeCat::LSR_MEM => {
-                    self.lsr_mem(&val.mode);
+                if let Some(cdl) = self.cdl.as_mut() {
+                    cdl.log_indirect_jump();
                 }
+                self.program_counter = indirect_ref;
+            }
 
-                OpCodeCat::NOP => {
-                    // do nothing
-                }
+            OpCodeCat::JSR => {
+                self.stack_push_u16(self.program_counter + 2 - 1);
+                let target_address = self.mem_read_u16(self.program_counter);
+                self.program_counter = target_address;
+            }
 
-                OpCodeCat::BRK => {
-                    self.status.insert(CpuFlags::BREAK);
-                    return;
-                }
-                _ => todo!(""),
+            OpCodeCat::LDX => {
+                self.ldx(&val.mode);
+            }
+
+            OpCodeCat::LDY => {
+                self.ldy(&val.mode);
+            }
+
+            OpCodeCat::LSR_ACC => {
+                self.lsr_acc();
+            }
+
+            OpCodeCat::LSR_MEM => {
+                self.lsr_mem(&val.mode);
             }
 
-            if program_counter_state == self.program_counter {
-                self.program_counter += val.bytes - 1;
+            OpCodeCat::NOP => {
+                // do nothing
             }
+
+            OpCodeCat::BRK => {
+                self.status.insert(CpuFlags::BREAK);
+                self.cycles += val.cycles as u64;
+                return false;
+            }
+            _ => {
+                self.program_counter -= 1;
+                self.unimplemented = Some(Unimplemented {
+                    opcode: opscode,
+                    pc: self.program_counter,
+                });
+                return false;
+            }
+        }
+
+        self.cycles += val.cycles as u64;
+        if self.page_crossed && Self::has_page_cross_penalty(&val.match_code) {
+            self.cycles += 1;
+        }
+
+        if program_counter_state == self.program_counter {
+            self.program_counter += val.bytes - 1;
         }
+
+        true
+    }
+
+    // Read instructions take one more cycle when indexing crosses a page.
+    fn has_page_cross_penalty(code: &OpCodeCat) -> bool {
+        matches!(
+            code,
+            OpCodeCat::LDA
+                | OpCodeCat::AND
+                | OpCodeCat::EOR
+                | OpCodeCat::CMP
+                | OpCodeCat::LDX
+                | OpCodeCat::LDY
+        )
     }
 }
 
+#[cfg(test)]
+mod klaus;
+#[cfg(test)]
+mod processor_tests;
 #[cfg(test)]
 mod test;
@@ -0,0 +1,268 @@
+//! Single instruction test vectors in the ProcessorTests / SingleStepTests
+//! JSON format, https://github.com/SingleStepTests/ProcessorTests (nes6502).
+//!
+//! The test reads `src/cpu/golden/processor_tests/xx.json` for every opcode
+//! in `EmmulationHelpers::get_op_code_struct`, the ones the CPU implements,
+//! and says which files it skipped because they are not there.
+//!
+//! Besides registers and memory each case compares the bus activity, read
+//! by read and write by write, against `CPU::bus_log`. The CPU does not
+//! perform the dummy reads and writes of a real 6502, so instructions that
+//! have them fail there.
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use super::*;
+
+fn field(state: &Value, name: &str) -> Result<u64, String> {
+    state[name]
+        .as_u64()
+        .ok_or_else(|| format!("missing field '{}'", name))
+}
+
+fn bus_cycles(case: &Value) -> Result<Vec<BusCycle>, String> {
+    case["cycles"]
+        .as_array()
+        .ok_or("missing field 'cycles'")?
+        .iter()
+        .map(|cycle| {
+            let access = match cycle[2].as_str() {
+                Some("read") => Access::READ,
+                Some("write") => Access::WRITE,
+                _ => return Err(format!("invalid cycle {}", cycle)),
+            };
+            match (cycle[0].as_u64(), cycle[1].as_u64()) {
+                (Some(addr), Some(value)) => Ok(BusCycle {
+                    addr: addr as u16,
+                    value: value as u8,
+                    access,
+                }),
+                _ => Err(format!("invalid cycle {}", cycle)),
+            }
+        })
+        .collect()
+}
+
+fn describe(cycle: Option<&BusCycle>) -> String {
+    match cycle {
+        Some(cycle) if cycle.access == Access::WRITE => {
+            format!("write ${:04X} = ${:02X}", cycle.addr, cycle.value)
+        }
+        Some(cycle) => format!("read ${:04X} = ${:02X}", cycle.addr, cycle.value),
+        None => "nothing".to_string(),
+    }
+}
+
+fn ram(state: &Value) -> Result<Vec<(u16, u8)>, String> {
+    state["ram"]
+        .as_array()
+        .ok_or("missing field 'ram'")?
+        .iter()
+        .map(|pair| match (pair[0].as_u64(), pair[1].as_u64()) {
+            (Some(addr), Some(value)) => Ok((addr as u16, value as u8)),
+            _ => Err(format!("invalid ram entry {}", pair)),
+        })
+        .collect()
+}
+
+/// Sets up `initial`, executes one instruction and compares with `final`.
+fn run_case(case: &Value) -> Result<(), String> {
+    let initial = &case["initial"];
+    let expected = &case["final"];
+
+    let mut cpu = CPU::new();
+    cpu.flat_memory = true;
+    cpu.program_counter = field(initial, "pc")? as u16;
+    cpu.stack_pointer = field(initial, "s")? as u8;
+    cpu.register_a = field(initial, "a")? as u8;
+    cpu.register_x = field(initial, "x")? as u8;
+    cpu.register_y = field(initial, "y")? as u8;
+    cpu.status = CpuFlags::from_bits_truncate(field(initial, "p")? as u8);
+    for (addr, value) in ram(initial)? {
+        cpu.load_at(addr, &[value])?;
+    }
+
+    cpu.bus_log = Some(Vec::new());
+    cpu.step();
+    if let Some(unimplemented) = cpu.unimplemented() {
+        return Err(unimplemented.to_string());
+    }
+
+    let mut errors = Vec::new();
+    let registers = [
+        ("pc", cpu.program_counter as u64),
+        ("s", cpu.stack_pointer as u64),
+        ("a", cpu.register_a as u64),
+        ("x", cpu.register_x as u64),
+        ("y", cpu.register_y as u64),
+        ("p", cpu.status.bits() as u64),
+    ];
+    for (name, actual) in registers {
+        let wanted = field(expected, name)?;
+        if actual != wanted {
+            errors.push(format!(
+                "{} is ${:02X}, expected ${:02X}",
+                name, actual, wanted
+            ));
+        }
+    }
+
+    for (addr, wanted) in ram(expected)? {
+        let actual = cpu.mem_peek(addr);
+        if actual != wanted {
+            errors.push(format!(
+                "${:04X} is ${:02X}, expected ${:02X}",
+                addr, actual, wanted
+            ));
+        }
+    }
+
+    let wanted = bus_cycles(case)?;
+    if cpu.cycles != wanted.len() as u64 {
+        errors.push(format!(
+            "took {} cycles, expected {}",
+            cpu.cycles,
+            wanted.len()
+        ));
+    }
+    let log = cpu.bus_log.take().unwrap_or_default();
+    let max = log.len().max(wanted.len());
+    if let Some(i) = (0..max).find(|&i| log.get(i) != wanted.get(i)) {
+        errors.push(format!(
+            "bus cycle {} did {}, expected {}",
+            i,
+            describe(log.get(i)),
+            describe(wanted.get(i))
+        ));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join(", "))
+    }
+}
+
+/// Runs every case in `json`, stopping at the first failure.
+fn run_file(json: &str) -> Result<usize, String> {
+    let cases: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let cases = cases.as_array().ok_or("expected an array of cases")?;
+
+    for case in cases {
+        run_case(case).map_err(|e| format!("'{}': {}", case["name"].as_str().unwrap_or("?"), e))?;
+    }
+
+    Ok(cases.len())
+}
+
+#[test]
+fn test_processor_tests() {
+    let dir: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "src",
+        "cpu",
+        "golden",
+        "processor_tests",
+    ]
+    .iter()
+    .collect();
+
+    let mut missing = Vec::new();
+    let mut failures = Vec::new();
+    for code in 0..=0xFF {
+        let opcode = EmmulationHelpers::get_op_code_struct(code);
+        if matches!(opcode.match_code, OpCodeCat::NONE) {
+            continue;
+        }
+        let path = dir.join(format!("{:02x}.json", code));
+        let json = match fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(_) => {
+                missing.push(format!("{:02x}", code));
+                continue;
+            }
+        };
+        if let Err(error) = run_file(&json) {
+            failures.push(format!("{:02X} {}: {}", code, opcode.code_name, error));
+        }
+    }
+    if !missing.is_empty() {
+        eprintln!(
+            "skipped opcodes without a file in {}: {}",
+            dir.display(),
+            missing.join(" ")
+        );
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} opcodes failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+const LDA_ZERO_PAGE: &str = r#"[
+    {
+        "name": "a5 10 00",
+        "initial": { "pc": 16384, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+                     "ram": [[16384, 165], [16385, 22], [22, 128]] },
+        "final": { "pc": 16386, "s": 253, "a": 128, "x": 0, "y": 0, "p": 164,
+                   "ram": [[16384, 165], [16385, 22], [22, 128]] },
+        "cycles": [[16384, 165, "read"], [16385, 22, "read"], [22, 128, "read"]]
+    }
+]"#;
+
+#[test]
+fn test_run_file_passes() {
+    assert_eq!(run_file(LDA_ZERO_PAGE), Ok(1));
+}
+
+#[test]
+fn test_run_file_reports_mismatch() {
+    let json = LDA_ZERO_PAGE.replace(r#""a": 128"#, r#""a": 127"#);
+
+    assert_eq!(
+        run_file(&json),
+        Err("'a5 10 00': a is $80, expected $7F".to_string())
+    );
+}
+
+#[test]
+fn test_run_file_reports_bus_mismatch() {
+    let json = LDA_ZERO_PAGE.replace(r#"[22, 128, "read"]"#, r#"[22, 128, "write"]"#);
+
+    assert_eq!(
+        run_file(&json),
+        Err("'a5 10 00': bus cycle 2 did read $0016 = $80, expected write $0016 = $80".to_string())
+    );
+
+    let json = LDA_ZERO_PAGE.replace(r#", [22, 128, "read"]"#, "");
+
+    assert_eq!(
+        run_file(&json),
+        Err(
+            "'a5 10 00': took 3 cycles, expected 2, bus cycle 2 did read $0016 = $80, expected nothing"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn test_ports_are_ram_in_flat_mode() {
+    // STA $4016 followed by a read of the same address
+    let json = r#"[{
+        "name": "8d 16 40",
+        "initial": { "pc": 512, "s": 253, "a": 90, "x": 0, "y": 0, "p": 36,
+                     "ram": [[512, 141], [513, 22], [514, 64], [16406, 0]] },
+        "final": { "pc": 515, "s": 253, "a": 90, "x": 0, "y": 0, "p": 36,
+                   "ram": [[16406, 90]] },
+        "cycles": [[512, 141, "read"], [513, 22, "read"], [514, 64, "read"],
+                   [16406, 90, "write"]]
+    }]"#;
+
+    assert_eq!(run_file(json), Ok(1));
+}
@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+
+use super::CpuFlags;
+use super::CPU;
+
+// registers, PC and cycles ahead of the memory array
+const HEADER: usize = 5 + 2 + 8;
+const SNAPSHOT_LEN: usize = HEADER + 0x10000;
+
+// equal bytes shorter than this do not end a run of changes
+const MIN_GAP: usize = 8;
+
+/// When `Rewind` takes snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interval {
+    /// Before every n-th instruction run through `Rewind::step`.
+    Instructions(u64),
+    /// At every n-th call to `Rewind::end_frame`.
+    Frames(u64),
+}
+
+// bytes that differ from the next snapshot, XORed with it
+#[derive(Debug, Clone)]
+struct Run {
+    offset: usize,
+    bytes: Vec<u8>,
+}
+
+// where a snapshot was taken; frames alone tell snapshots of a console
+// apart, as those are not stepped through `Rewind::step`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    instruction: u64,
+    frame: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Delta {
+    position: Position,
+    runs: Vec<Run>,
+}
+
+impl Delta {
+    fn between(position: Position, older: &[u8], newer: &[u8]) -> Self {
+        let mut runs: Vec<Run> = Vec::new();
+        let mut offset = 0;
+        while offset < older.len() {
+            if older[offset] == newer[offset] {
+                offset += 1;
+                continue;
+            }
+            let start = offset;
+            let mut end = offset;
+            while offset < older.len() && offset - end < MIN_GAP {
+                if older[offset] != newer[offset] {
+                    end = offset + 1;
+                }
+                offset += 1;
+            }
+            runs.push(Run {
+                offset: start,
+                bytes: (start..end).map(|i| older[i] ^ newer[i]).collect(),
+            });
+        }
+        Delta { position, runs }
+    }
+
+    fn apply(&self, snapshot: &mut [u8]) {
+        for run in &self.runs {
+            for (byte, xor) in snapshot[run.offset..].iter_mut().zip(&run.bytes) {
+                *byte ^= xor;
+            }
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.runs.iter().map(|run| run.bytes.len()).sum()
+    }
+}
+
+/// Ring buffer of CPU snapshots for stepping backwards.
+///
+/// Only the newest snapshot is kept whole. Each older one is stored as the
+/// XOR of itself and the snapshot after it, holding just the bytes that
+/// changed in between, and the oldest is dropped once `capacity` is
+/// reached.
+///
+/// Snapshots hold the CPU registers, cycle count and memory. That is the
+/// whole machine for a bare CPU, so `seek` re-executes deterministically;
+/// the PPU and APU of a bus are not captured.
+pub struct Rewind {
+    interval: Interval,
+    capacity: usize,
+    /// Instructions run through `step` so far.
+    pub instructions: u64,
+    /// Frames counted by `end_frame` so far.
+    pub frames: u64,
+    latest: Option<(Position, Vec<u8>)>,
+    history: VecDeque<Delta>,
+}
+
+impl Rewind {
+    pub fn new(interval: Interval, capacity: usize) -> Self {
+        Rewind {
+            interval,
+            capacity: capacity.max(1),
+            instructions: 0,
+            frames: 0,
+            latest: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.latest.as_ref().map_or(0, |_| self.history.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    /// Instruction counts of the snapshots held, oldest first.
+    pub fn snapshots(&self) -> Vec<u64> {
+        self.positions().map(|p| p.instruction).collect()
+    }
+
+    /// Frame counts of the snapshots held, oldest first.
+    pub fn snapshot_frames(&self) -> Vec<u64> {
+        self.positions().map(|p| p.frame).collect()
+    }
+
+    fn positions(&self) -> impl Iterator<Item = Position> + '_ {
+        self.history
+            .iter()
+            .map(|d| d.position)
+            .chain(self.latest.as_ref().map(|(position, _)| *position))
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            instruction: self.instructions,
+            frame: self.frames,
+        }
+    }
+
+    /// Bytes held by the snapshots, to check the deltas pay off.
+    pub fn memory_used(&self) -> usize {
+        self.latest.as_ref().map_or(0, |(_, data)| data.len())
+            + self.history.iter().map(Delta::size).sum::<usize>()
+    }
+
+    /// Runs one instruction, first taking a snapshot when one is due.
+    /// Returns false once BRK is reached, like `CPU::step`.
+    pub fn step(&mut self, cpu: &mut CPU) -> bool {
+        if let Interval::Instructions(n) = self.interval {
+            if self.instructions.is_multiple_of(n.max(1)) {
+                self.snapshot(cpu);
+            }
+        }
+        self.instructions += 1;
+        cpu.step()
+    }
+
+    /// Counts a finished frame, taking a snapshot when one is due. Works
+    /// for a `Nes` clocked on its own, as snapshots are told apart by frame
+    /// as well as by instruction.
+    pub fn end_frame(&mut self, cpu: &CPU) {
+        self.frames += 1;
+        if let Interval::Frames(n) = self.interval {
+            if self.frames.is_multiple_of(n.max(1)) {
+                self.snapshot(cpu);
+            }
+        }
+    }
+
+    /// Snapshots `cpu` at the current instruction and frame count,
+    /// replacing a snapshot taken at the same point.
+    pub fn snapshot(&mut self, cpu: &CPU) {
+        let data = snapshot_of(cpu);
+        let position = self.position();
+        match self.latest.take() {
+            Some((latest, _)) if latest == position => {}
+            Some((latest, previous)) => {
+                self.history
+                    .push_back(Delta::between(latest, &previous, &data));
+                if self.history.len() >= self.capacity {
+                    self.history.pop_front();
+                }
+            }
+            None => {}
+        }
+        self.latest = Some((position, data));
+    }
+
+    /// Restores the `n`-th newest snapshot, 1 being the newest, and drops
+    /// the ones after it, winding the instruction and frame counts back
+    /// too. Returns the instruction count it was taken at.
+    pub fn rewind(&mut self, cpu: &mut CPU, n: usize) -> Result<u64, String> {
+        if n == 0 || n > self.len() {
+            return Err(format!(
+                "cannot rewind {} snapshots, {} are held",
+                n,
+                self.len()
+            ));
+        }
+
+        let (mut position, mut data) = self.latest.take().unwrap();
+        for _ in 1..n {
+            let delta = self.history.pop_back().unwrap();
+            delta.apply(&mut data);
+            position = delta.position;
+        }
+
+        restore(cpu, &data);
+        self.instructions = position.instruction;
+        self.frames = position.frame;
+        self.latest = Some((position, data));
+        Ok(position.instruction)
+    }
+
+    /// Brings `cpu` to exactly `instruction` instructions: back to the
+    /// newest snapshot at or before it when it lies in the past, then
+    /// forward by re-executing.
+    pub fn seek(&mut self, cpu: &mut CPU, instruction: u64) -> Result<(), String> {
+        if instruction < self.instructions {
+            let snapshots = self.snapshots();
+            let n = snapshots
+                .iter()
+                .rev()
+                .position(|&count| count <= instruction)
+                .ok_or(format!(
+                    "instruction {} is older than the oldest snapshot",
+                    instruction
+                ))?;
+            self.rewind(cpu, n + 1)?;
+        }
+
+        while self.instructions < instruction {
+            if !self.step(cpu) {
+                return Err(format!(
+                    "CPU halted at instruction {} before reaching {}",
+                    self.instructions, instruction
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn snapshot_of(cpu: &CPU) -> Vec<u8> {
+    let mut data = Vec::with_capacity(SNAPSHOT_LEN);
+    data.extend_from_slice(&[
+        cpu.register_a,
+        cpu.register_x,
+        cpu.register_y,
+        cpu.status.bits(),
+        cpu.stack_pointer,
+    ]);
+    data.extend_from_slice(&cpu.program_counter.to_le_bytes());
+    data.extend_from_slice(&cpu.cycles.to_le_bytes());
+    data.extend_from_slice(&cpu.memory[..]);
+    data
+}
+
+fn restore(cpu: &mut CPU, data: &[u8]) {
+    cpu.register_a = data[0];
+    cpu.register_x = data[1];
+    cpu.register_y = data[2];
+    cpu.status = CpuFlags::from_bits_truncate(data[3]);
+    cpu.stack_pointer = data[4];
+    cpu.program_counter = u16::from_le_bytes([data[5], data[6]]);
+    let mut cycles = [0; 8];
+    cycles.copy_from_slice(&data[7..HEADER]);
+    cpu.cycles = u64::from_le_bytes(cycles);
+    cpu.memory.copy_from_slice(&data[HEADER..]);
+    cpu.watch_hit = None;
+    cpu.unimplemented = None;
+    cpu.page_crossed = false;
+}
@@ -0,0 +1,225 @@
+use super::zeroed_memory;
+use super::CpuFlags;
+use super::CPU;
+
+const MAGIC: [u8; 4] = *b"6502";
+
+/// # Save state format, all numbers little endian
+///
+/// ```text
+///  offset  size
+///       0     4  "6502"
+///       4     2  version that wrote the state
+///       6     2  oldest version that can read it
+///       8     1  A
+///       9     1  X
+///      10     1  Y
+///      11     1  P
+///      12     1  SP
+///      13     2  PC
+///      15     8  cycles
+///      23     1  options, bit 0 = flat memory
+///      24    32  bitmap of the 256 memory pages that are not all zero
+///      56   ...  those pages, 256 bytes each, in address order
+/// ```
+///
+/// Later versions only ever append fields and raise the oldest readable
+/// version when they change what is already there, so an older build can
+/// still load a newer state and ignore what it does not know about.
+pub const STATE_VERSION: u16 = 1;
+
+const PAGE: usize = 0x100;
+const PAGES: usize = 0x100;
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or("save state is truncated")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+impl CPU {
+    /// Registers, cycle count and the whole memory array as a compact
+    /// binary blob, see `STATE_VERSION`. Watchpoints, breakpoints and the
+    /// profiler are tooling, not machine state, and are left out.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        state.extend_from_slice(&MAGIC);
+        state.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        state.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        state.extend_from_slice(&[
+            self.register_a,
+            self.register_x,
+            self.register_y,
+            self.status.bits(),
+            self.stack_pointer,
+        ]);
+        state.extend_from_slice(&self.program_counter.to_le_bytes());
+        state.extend_from_slice(&self.cycles.to_le_bytes());
+        state.push(self.flat_memory as u8);
+
+        let pages: Vec<&[u8]> = self.memory.chunks(PAGE).collect();
+        let mut bitmap = [0u8; PAGES / 8];
+        for (i, page) in pages.iter().enumerate() {
+            if page.iter().any(|&b| b != 0) {
+                bitmap[i / 8] |= 1 << (i % 8);
+            }
+        }
+        state.extend_from_slice(&bitmap);
+        for (i, page) in pages.iter().enumerate() {
+            if bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                state.extend_from_slice(page);
+            }
+        }
+
+        state
+    }
+
+    /// Restores a state made by `save_state`. Nothing changes unless the
+    /// whole state can be read.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let mut reader = Reader {
+            data: state,
+            pos: 0,
+        };
+        if reader.bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err("not a save state".to_string());
+        }
+        let version = reader.u16()?;
+        let readable_from = reader.u16()?;
+        if readable_from > STATE_VERSION {
+            return Err(format!(
+                "save state version {} needs version {} or later, this build reads version {}",
+                version, readable_from, STATE_VERSION
+            ));
+        }
+
+        let registers = reader.bytes(5)?;
+        let program_counter = reader.u16()?;
+        let cycles = reader.u64()?;
+        let options = reader.u8()?;
+
+        let bitmap = reader.bytes(PAGES / 8)?;
+        let mut memory = zeroed_memory();
+        for (i, page) in memory.chunks_mut(PAGE).enumerate() {
+            if bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                page.copy_from_slice(reader.bytes(PAGE)?);
+            }
+        }
+
+        if version <= STATE_VERSION && reader.pos != state.len() {
+            return Err(format!(
+                "save state has {} unexpected bytes at the end",
+                state.len() - reader.pos
+            ));
+        }
+
+        self.register_a = registers[0];
+        self.register_x = registers[1];
+        self.register_y = registers[2];
+        self.status = CpuFlags::from_bits_truncate(registers[3]);
+        self.stack_pointer = registers[4];
+        self.program_counter = program_counter;
+        self.cycles = cycles;
+        self.flat_memory = options & 1 != 0;
+        self.memory = memory;
+        self.watch_hit = None;
+        self.unimplemented = None;
+        self.page_crossed = false;
+        Ok(())
+    }
+
+    /// 64-bit FNV-1a hash of the whole memory array, a cheap way to tell
+    /// whether two runs ended up in the same place.
+    pub fn memory_hash(&self) -> u64 {
+        self.memory
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+            })
+    }
+}
+
+/// `#[serde(with)]` for the memory array, which is longer than the arrays
+/// serde supports out of the box. Written as bytes, read from bytes or a
+/// sequence so formats without a byte type work too.
+#[cfg(feature = "serde")]
+pub(super) mod memory {
+    use std::fmt;
+
+    use serde::de::Error;
+    use serde::de::SeqAccess;
+    use serde::de::Visitor;
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    use super::zeroed_memory;
+
+    const LEN: usize = 0x10000;
+
+    pub fn serialize<S: Serializer>(memory: &[u8; LEN], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&memory[..])
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Box<[u8; LEN]>, D::Error> {
+        deserializer.deserialize_bytes(MemoryVisitor)
+    }
+
+    struct MemoryVisitor;
+
+    impl<'de> Visitor<'de> for MemoryVisitor {
+        type Value = Box<[u8; LEN]>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{} bytes of memory", LEN)
+        }
+
+        fn visit_bytes<E: Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+            let mut memory = zeroed_memory();
+            if bytes.len() != LEN {
+                return Err(E::invalid_length(bytes.len(), &self));
+            }
+            memory.copy_from_slice(bytes);
+            Ok(memory)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut memory = zeroed_memory();
+            for (i, byte) in memory.iter_mut().enumerate() {
+                *byte = seq
+                    .next_element()?
+                    .ok_or_else(|| A::Error::invalid_length(i, &self))?;
+            }
+            if seq.next_element::<u8>()?.is_some() {
+                return Err(A::Error::invalid_length(LEN + 1, &self));
+            }
+            Ok(memory)
+        }
+    }
+}
@@ -1,4 +1,9 @@
 use super::*;
+use crate::cpu::rewind::{Interval, Rewind};
+use crate::cpu::undo::UndoLog;
+use crate::cpu::watch::{Access, Breakpoint, WatchHit, Watchpoint};
+use crate::input::{FourScore, InputDevice, Zapper};
+use crate::joypad::JoypadButton;
 
 /* Few more Lda test cases */
 #[test]
@@ -1506,6 +1511,131 @@ fn test_jsr() {
     assert_eq!(return_counter, 0x8002);
 }
 
+#[test]
+fn test_read_watchpoint_stops_run() {
+    let mut cpu = CPU::new();
+    cpu.watchpoints
+        .push(Watchpoint::new(0x10, 0x1F, Access::READ));
+    cpu.load(vec![0xa9, 0x01, 0xa5, 0x05, 0xa5, 0x12, 0xe8, 0x00]);
+    cpu.reset();
+
+    cpu.run();
+
+    assert_eq!(
+        cpu.take_watch_hit(),
+        Some(WatchHit::Read {
+            index: 0,
+            addr: 0x12,
+            value: 0
+        })
+    );
+    // the instruction that read finishes, the next one does not run
+    assert_eq!(cpu.program_counter, 0x8006);
+    assert_eq!(cpu.register_x, 0);
+}
+
+#[test]
+fn test_read_watchpoint_ignores_code_fetches() {
+    let mut cpu = CPU::new();
+    cpu.watchpoints
+        .push(Watchpoint::new(0x8000, 0x80FF, Access::READ));
+    // LDX #$01; INX; LDA $8000; INX; BRK
+    cpu.load(vec![0xa2, 0x01, 0xe8, 0xad, 0x00, 0x80, 0xe8, 0x00]);
+    cpu.reset();
+
+    cpu.run();
+
+    assert_eq!(
+        cpu.take_watch_hit(),
+        Some(WatchHit::Read {
+            index: 0,
+            addr: 0x8000,
+            value: 0xa2
+        })
+    );
+    assert_eq!(cpu.program_counter, 0x8006);
+    assert_eq!(cpu.register_x, 2);
+}
+
+#[test]
+fn test_write_watchpoint_reports_value() {
+    let mut cpu = CPU::new();
+    cpu.watchpoints
+        .push(Watchpoint::new(0x0200, 0x0200, Access::WRITE));
+    cpu.load(vec![0xa9, 0x42, 0x8d, 0x00, 0x02, 0x00]);
+    cpu.reset();
+
+    cpu.run();
+
+    let hit = cpu.take_watch_hit().unwrap();
+    assert_eq!(hit.to_string(), "watchpoint 0: write $0200 = 42");
+    assert_eq!(cpu.take_watch_hit(), None);
+}
+
+#[test]
+fn test_conditional_breakpoint_and_resume() {
+    let mut cpu = CPU::new();
+    cpu.breakpoints
+        .push(Breakpoint::new(None, Some("X == 2")).unwrap());
+    // INX; INX; INX; BRK
+    cpu.load(vec![0xe8, 0xe8, 0xe8, 0x00]);
+    cpu.reset();
+
+    cpu.run();
+    assert_eq!(
+        cpu.take_watch_hit(),
+        Some(WatchHit::Breakpoint {
+            index: 0,
+            addr: 0x8002
+        })
+    );
+
+    // running again steps over the breakpoint it stopped at
+    cpu.run();
+    assert_eq!(cpu.take_watch_hit(), None);
+    assert_eq!(cpu.register_x, 3);
+}
+
+#[test]
+fn test_breakpoint_condition_errors() {
+    assert!(Breakpoint::new(Some(0x8000), Some("A ==")).is_err());
+    assert!(Breakpoint::new(Some(0x8000), Some("W == 1")).is_err());
+    assert!(Breakpoint::new(Some(0x8000), Some("a == $40 && c")).is_ok());
+}
+
+#[test]
+fn test_jsr_pushes_high_byte_first() {
+    let mut cpu = CPU::new();
+    cpu.load_and_run(vec![0x20, 0x04, 0x80, 0x00, 0x00]);
+
+    assert_eq!(cpu.mem_read(0x01FD), 0x80);
+    assert_eq!(cpu.mem_read(0x01FC), 0x02);
+}
+
+#[test]
+fn test_unimplemented_opcode_stops() {
+    // LDA #$01; PHP
+    let mut cpu = CPU::new();
+    cpu.load_and_run(vec![0xa9, 0x01, 0x08]);
+
+    assert_eq!(
+        cpu.unimplemented(),
+        Some(Unimplemented {
+            opcode: 0x08,
+            pc: 0x8002
+        })
+    );
+    assert_eq!(cpu.program_counter, 0x8002);
+    assert_eq!(cpu.register_a, 0x01);
+    assert_eq!(
+        cpu.unimplemented().unwrap().to_string(),
+        "unimplemented opcode $08 at $8002"
+    );
+
+    cpu.reset();
+    assert_eq!(cpu.unimplemented(), None);
+}
+
 /* LSR test cases */
 
 #[test]
@@ -1584,6 +1714,373 @@ fn test_nop() {
     assert_eq!(cpu.program_counter, 0x8002);
 }
 
+/* Joypad test cases */
+
+#[test]
+fn test_joypad_read_4016() {
+    let mut cpu = CPU::new();
+    cpu.joypad_mut(1)
+        .unwrap()
+        .set_buttons(JoypadButton::BUTTON_A | JoypadButton::START);
+
+    // strobe, then read A and B
+    cpu.load_and_run(vec![
+        0xa9, 0x01, 0x8d, 0x16, 0x40, 0xa9, 0x00, 0x8d, 0x16, 0x40, 0xad, 0x16, 0x40, 0xaa, 0xad,
+        0x16, 0x40, 0x00,
+    ]);
+
+    assert_eq!(cpu.register_x, 0x41);
+    assert_eq!(cpu.register_a, 0x40);
+    assert_eq!(cpu.mem_read(0x4016), 0x40);
+    assert_eq!(cpu.mem_read(0x4016), 0x41);
+}
+
+#[test]
+fn test_joypad_two_players() {
+    let mut cpu = CPU::new();
+    cpu.joypad_mut(2)
+        .unwrap()
+        .set_buttons(JoypadButton::BUTTON_A);
+
+    cpu.mem_write(0x4016, 1);
+    cpu.mem_write(0x4016, 0);
+
+    assert_eq!(cpu.mem_read(0x4016), 0x40);
+    assert_eq!(cpu.mem_read(0x4017), 0x41);
+}
+
+#[test]
+fn test_four_score_players_3_and_4() {
+    let mut cpu = CPU::new();
+    cpu.port1 = InputDevice::FourScore(FourScore::port1());
+    cpu.port2 = InputDevice::FourScore(FourScore::port2());
+    cpu.joypad_mut(3)
+        .unwrap()
+        .set_buttons(JoypadButton::BUTTON_A);
+    cpu.joypad_mut(4)
+        .unwrap()
+        .set_buttons(JoypadButton::BUTTON_B);
+
+    cpu.mem_write(0x4016, 1);
+    cpu.mem_write(0x4016, 0);
+
+    for _ in 0..8 {
+        cpu.mem_read(0x4016);
+        cpu.mem_read(0x4017);
+    }
+
+    assert_eq!(cpu.mem_read(0x4016), 0x41);
+    assert_eq!(cpu.mem_read(0x4017), 0x40);
+    assert_eq!(cpu.mem_read(0x4016), 0x40);
+    assert_eq!(cpu.mem_read(0x4017), 0x41);
+}
+
+#[test]
+fn test_zapper_on_port_2() {
+    let mut cpu = CPU::new();
+    let mut zapper = Zapper::new();
+    zapper.trigger = true;
+    zapper.set_light_sensed(true);
+    cpu.port2 = InputDevice::Zapper(zapper);
+
+    assert_eq!(cpu.mem_read(0x4017), 0x50);
+    assert!(cpu.joypad_mut(2).is_none());
+}
+
+fn counting_program() -> CPU {
+    let program = crate::assemble!(
+        "       LDX #0",
+        "loop:  INX",
+        "       INC $0300",
+        "       STA $0400,X",
+        "       CPX #200",
+        "       BNE loop",
+        "       BRK",
+    );
+    let mut cpu = CPU::new();
+    cpu.load_at(0x8000, &program).unwrap();
+    cpu.program_counter = 0x8000;
+    cpu.register_a = 0x5A;
+    cpu
+}
+
+#[test]
+fn test_save_state_resumes_mid_program() {
+    let mut cpu = counting_program();
+    for _ in 0..150 {
+        cpu.step();
+    }
+    let state = cpu.save_state();
+    cpu.run();
+
+    let mut resumed = CPU::new();
+    resumed.load_state(&state).unwrap();
+    assert_eq!(resumed.register_x, 30);
+    resumed.run();
+
+    assert_eq!(resumed.register_x, 200);
+    assert_eq!(resumed.mem_peek(0x0300), 200);
+    assert_eq!(resumed.cycles, cpu.cycles);
+    assert_eq!(resumed.save_state(), cpu.save_state());
+}
+
+#[test]
+fn test_save_state_is_compact() {
+    let mut cpu = counting_program();
+    cpu.flat_memory = true;
+    cpu.run();
+
+    // header, page bitmap and the pages at $0300, $0400 and $8000
+    let state = cpu.save_state();
+    assert_eq!(state.len(), 24 + 32 + 3 * 0x100);
+
+    let mut restored = CPU::new();
+    restored.load_state(&state).unwrap();
+    assert!(restored.flat_memory);
+    assert_eq!(restored.status, cpu.status);
+    assert_eq!(restored.stack_pointer, cpu.stack_pointer);
+    assert_eq!(restored.program_counter, cpu.program_counter);
+}
+
+#[test]
+fn test_load_state_checks_version_and_length() {
+    let cpu = counting_program();
+    let state = cpu.save_state();
+    let mut target = CPU::new();
+
+    assert_eq!(
+        target.load_state(b"NES\x1a"),
+        Err("not a save state".to_string())
+    );
+    assert_eq!(
+        target.load_state(&state[..state.len() - 1]),
+        Err("save state is truncated".to_string())
+    );
+
+    let mut trailing = state.clone();
+    trailing.push(0);
+    assert_eq!(
+        target.load_state(&trailing),
+        Err("save state has 1 unexpected bytes at the end".to_string())
+    );
+
+    // a newer state that older builds may read, extra fields at the end
+    let mut newer = trailing.clone();
+    newer[4..6].copy_from_slice(&(state::STATE_VERSION + 1).to_le_bytes());
+    target.load_state(&newer).unwrap();
+    assert_eq!(target.register_a, 0x5A);
+
+    // a newer state that changed the layout
+    let mut incompatible = state.clone();
+    incompatible[4..6].copy_from_slice(&3u16.to_le_bytes());
+    incompatible[6..8].copy_from_slice(&2u16.to_le_bytes());
+    let mut untouched = CPU::new();
+    assert_eq!(
+        untouched.load_state(&incompatible),
+        Err(
+            "save state version 3 needs version 2 or later, this build reads version 1".to_string()
+        )
+    );
+    assert_eq!(untouched.register_a, 0);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_round_trip() {
+    let mut cpu = counting_program();
+    for _ in 0..150 {
+        cpu.step();
+    }
+    cpu.breakpoints.push(Breakpoint::at(0x8000));
+
+    let json = serde_json::to_string(&cpu).unwrap();
+    let mut restored: CPU = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(restored.save_state(), cpu.save_state());
+    assert!(restored.breakpoints.is_empty());
+    restored.run();
+    assert_eq!(restored.register_x, 200);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_builds_partial_state() {
+    let cpu: CPU = serde_json::from_str(
+        r#"{"register_a": 66, "status": {"bits": 129}, "program_counter": 49152}"#,
+    )
+    .unwrap();
+    assert_eq!(cpu.register_a, 66);
+    assert_eq!(cpu.status, CpuFlags::NEGATIVE | CpuFlags::CARRY);
+    assert_eq!(cpu.program_counter, 0xC000);
+    assert_eq!(cpu.stack_pointer, STACK_RESET);
+
+    let error = serde_json::from_str::<CPU>(r#"{"memory": [1, 2, 3]}"#)
+        .err()
+        .unwrap();
+    assert!(
+        error
+            .to_string()
+            .starts_with("invalid length 3, expected 65536 bytes of memory"),
+        "{}",
+        error
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_opcodes() {
+    let opcode = EmmulationHelpers::get_op_code_struct(0xB1);
+    let json = serde_json::to_string(&opcode).unwrap();
+    assert_eq!(
+        json,
+        r#"{"code":177,"code_name":"LDA","match_code":"LDA","bytes":2,"cycles":5,"mode":"Indirect_Y"}"#
+    );
+
+    let back: crate::helper::OpCode = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.code_name, "LDA");
+    assert_eq!(back.mode, AddressingMode::Indirect_Y);
+    assert!(matches!(back.match_code, OpCodeCat::LDA));
+
+    let unknown = json.replace(r#""code_name":"LDA""#, r#""code_name":"XYZ""#);
+    assert!(serde_json::from_str::<crate::helper::OpCode>(&unknown).is_err());
+}
+
+#[test]
+fn test_rewind_and_seek() {
+    let mut cpu = counting_program();
+    let mut rewind = Rewind::new(Interval::Instructions(64), 8);
+
+    let mut states = Vec::new();
+    while rewind.step(&mut cpu) {
+        states.push(cpu.save_state());
+    }
+    let end = cpu.save_state();
+    assert_eq!(rewind.instructions, 1002);
+    assert_eq!(rewind.len(), 8);
+    assert_eq!(
+        rewind.snapshots(),
+        (8..16).map(|i| i * 64).collect::<Vec<_>>()
+    );
+
+    assert_eq!(rewind.rewind(&mut cpu, 3), Ok(832));
+    assert_eq!(cpu.save_state(), states[831]);
+    assert_eq!(rewind.len(), 6);
+
+    // re-executing reaches the same states, and snapshots again on the way
+    rewind.seek(&mut cpu, 900).unwrap();
+    assert_eq!(cpu.save_state(), states[899]);
+    rewind.seek(&mut cpu, 600).unwrap();
+    assert_eq!(cpu.save_state(), states[599]);
+    assert_eq!(rewind.instructions, 600);
+
+    assert_eq!(
+        rewind.seek(&mut cpu, 1500),
+        Err("CPU halted at instruction 1002 before reaching 1500".to_string())
+    );
+    assert_eq!(cpu.save_state(), end);
+    assert_eq!(
+        rewind.seek(&mut cpu, 100),
+        Err("instruction 100 is older than the oldest snapshot".to_string())
+    );
+    assert_eq!(
+        rewind.rewind(&mut cpu, 9),
+        Err("cannot rewind 9 snapshots, 8 are held".to_string())
+    );
+}
+
+#[test]
+fn test_rewind_deltas_are_small() {
+    let mut cpu = counting_program();
+    let mut rewind = Rewind::new(Interval::Instructions(10), 50);
+    while rewind.step(&mut cpu) {}
+
+    // a full snapshot is 64K, each delta only holds a few changed bytes
+    assert_eq!(rewind.len(), 50);
+    assert!(
+        rewind.memory_used() < 0x10000 + 50 * 64,
+        "{}",
+        rewind.memory_used()
+    );
+}
+
+#[test]
+fn test_rewind_by_frames() {
+    let mut cpu = counting_program();
+    let mut rewind = Rewind::new(Interval::Frames(2), 4);
+
+    for _ in 0..5 {
+        for _ in 0..10 {
+            rewind.step(&mut cpu);
+        }
+        rewind.end_frame(&cpu);
+    }
+    assert_eq!(rewind.snapshots(), vec![20, 40]);
+
+    rewind.rewind(&mut cpu, 2).unwrap();
+    assert_eq!(rewind.instructions, 20);
+    assert_eq!(cpu.register_x, 4);
+}
+
+#[test]
+fn test_step_back() {
+    let mut cpu = counting_program();
+    cpu.undo = Some(UndoLog::new(100));
+
+    let mut states = vec![cpu.save_state()];
+    for _ in 0..30 {
+        cpu.step();
+        states.push(cpu.save_state());
+    }
+
+    for expected in states.iter().rev().skip(1) {
+        assert!(cpu.step_back());
+        assert_eq!(&cpu.save_state(), expected);
+    }
+    assert!(!cpu.step_back());
+}
+
+#[test]
+fn test_undo_log_is_bounded() {
+    let mut cpu = counting_program();
+    cpu.undo = Some(UndoLog::new(8));
+    for _ in 0..100 {
+        cpu.step();
+    }
+    let undo = cpu.undo.as_ref().unwrap();
+    assert_eq!(undo.len(), 8);
+
+    // INC $0300 at $8003 and STA $0400,X at $8006 run every 5 instructions
+    assert_eq!(undo.last_writer(0x0300), Some((0x8003, 2)));
+    let x = cpu.register_x as u16;
+    assert_eq!(undo.last_writer(0x0400 + x), Some((0x8006, 1)));
+    assert_eq!(undo.last_writer(0x0400 + x - 2), None);
+
+    for _ in 0..8 {
+        assert!(cpu.step_back());
+    }
+    assert!(!cpu.step_back());
+    assert_eq!(cpu.register_x, x as u8 - 1);
+}
+
+#[test]
+fn test_unimplemented_opcode_leaves_no_undo_entry() {
+    let mut cpu = CPU::new();
+    // INX; PHP
+    cpu.load(vec![0xE8, 0x08]);
+    cpu.reset();
+    cpu.undo = Some(UndoLog::new(8));
+
+    assert!(cpu.step());
+    assert!(!cpu.step());
+    assert!(!cpu.step());
+    assert!(cpu.unimplemented().is_some());
+    assert_eq!(cpu.undo.as_ref().unwrap().len(), 1);
+
+    assert!(cpu.step_back());
+    assert_eq!(cpu.register_x, 0);
+    assert!(!cpu.step_back());
+}
+
 //    #[test]
 //    fn test_lda_b9(){
 //         let mut cpu =  CPU::new();
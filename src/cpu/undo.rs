@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+
+use super::CpuFlags;
+use super::CPU;
+
+/// Everything one instruction changed: the registers as they were before
+/// it ran and the old value of every byte it wrote, in write order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoEntry {
+    pub program_counter: u16,
+    pub register_a: u8,
+    pub register_x: u8,
+    pub register_y: u8,
+    pub status: CpuFlags,
+    pub stack_pointer: u8,
+    pub cycles: u64,
+    pub writes: Vec<(u16, u8)>,
+}
+
+/// Per-instruction history for reverse stepping, keeping the last
+/// `capacity` instructions.
+///
+/// Set `CPU::undo` to `Some(UndoLog::new(capacity))`. Writes that land on
+/// the PPU, APU or controller ports cannot be taken back; `step_back` only
+/// restores RAM, see `CPU::mem_poke`.
+#[derive(Debug, Clone, Default)]
+pub struct UndoLog {
+    capacity: usize,
+    entries: VecDeque<UndoEntry>,
+}
+
+impl UndoLog {
+    pub fn new(capacity: usize) -> Self {
+        UndoLog {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries held, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &UndoEntry> {
+        self.entries.iter()
+    }
+
+    /// PC of the most recent logged instruction that wrote `addr`, and how
+    /// many instructions ago that was, 0 being the last one.
+    pub fn last_writer(&self, addr: u16) -> Option<(u16, usize)> {
+        self.entries
+            .iter()
+            .rev()
+            .enumerate()
+            .find(|(_, entry)| entry.writes.iter().any(|&(a, _)| a == addr))
+            .map(|(age, entry)| (entry.program_counter, age))
+    }
+
+    fn begin(&mut self, cpu: &CPU) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(UndoEntry {
+            program_counter: cpu.program_counter,
+            register_a: cpu.register_a,
+            register_x: cpu.register_x,
+            register_y: cpu.register_y,
+            status: cpu.status,
+            stack_pointer: cpu.stack_pointer,
+            cycles: cpu.cycles,
+            writes: Vec::new(),
+        });
+    }
+
+    // writes between instructions, like an NMI pushing the return address,
+    // belong to the instruction before
+    fn record_write(&mut self, addr: u16, old: u8) {
+        if let Some(entry) = self.entries.back_mut() {
+            entry.writes.push((addr, old));
+        }
+    }
+}
+
+impl CPU {
+    // called by `step` before each instruction
+    pub(super) fn undo_begin(&mut self) {
+        if let Some(mut undo) = self.undo.take() {
+            undo.begin(self);
+            self.undo = Some(undo);
+        }
+    }
+
+    // called by `step` when the opcode turned out not to be implemented,
+    // nothing ran that could be undone
+    pub(super) fn undo_cancel(&mut self) {
+        if let Some(undo) = self.undo.as_mut() {
+            undo.entries.pop_back();
+        }
+    }
+
+    // called by `mem_write` before the value changes
+    pub(super) fn undo_write(&mut self, addr: u16) {
+        if self.undo.is_some() {
+            let old = self.mem_peek(addr);
+            if let Some(undo) = self.undo.as_mut() {
+                undo.record_write(addr, old);
+            }
+        }
+    }
+
+    /// Undoes the last instruction recorded in `undo`. Returns false when
+    /// there is nothing left to undo.
+    pub fn step_back(&mut self) -> bool {
+        let entry = match self.undo.as_mut().and_then(|undo| undo.entries.pop_back()) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        for &(addr, old) in entry.writes.iter().rev() {
+            self.mem_poke(addr, old);
+        }
+        self.program_counter = entry.program_counter;
+        self.register_a = entry.register_a;
+        self.register_x = entry.register_x;
+        self.register_y = entry.register_y;
+        self.status = entry.status;
+        self.stack_pointer = entry.stack_pointer;
+        self.cycles = entry.cycles;
+        self.watch_hit = None;
+        true
+    }
+}
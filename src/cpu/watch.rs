@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use bitflags::bitflags;
+
+use super::CpuFlags;
+use super::CPU;
+use crate::asm::expr;
+
+bitflags! {
+    /// Kinds of access a watchpoint reacts to.
+    pub struct Access: u8 {
+        const READ    = 0b001;
+        const WRITE   = 0b010;
+        const EXECUTE = 0b100;
+    }
+}
+
+/// Watches `start..=end` for the given kinds of access.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub access: Access,
+}
+
+impl Watchpoint {
+    pub fn new(start: u16, end: u16, access: Access) -> Self {
+        Watchpoint { start, end, access }
+    }
+
+    pub fn matches(&self, addr: u16, access: Access) -> bool {
+        self.access.intersects(access) && (self.start..=self.end).contains(&addr)
+    }
+}
+
+/// Stops before the instruction at `addr` is executed, if `condition`
+/// holds. Without an address the condition is checked before every
+/// instruction.
+///
+/// Conditions are assembler expressions over the registers `A X Y SP PC P`
+/// and the flags `N V D I Z C` (0 or 1), e.g. `A == $40 && X > 3`. Names
+/// can be written in either case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub addr: Option<u16>,
+    pub condition: Option<String>,
+}
+
+impl Breakpoint {
+    pub fn at(addr: u16) -> Self {
+        Breakpoint {
+            addr: Some(addr),
+            condition: None,
+        }
+    }
+
+    /// Checks the condition for syntax errors and unknown names.
+    pub fn new(addr: Option<u16>, condition: Option<&str>) -> Result<Self, String> {
+        if let Some(condition) = condition {
+            expr::evaluate(condition, &register_symbols(&CPU::new()), 0)?
+                .ok_or_else(|| format!("unknown name in '{}'", condition))?;
+        }
+        Ok(Breakpoint {
+            addr,
+            condition: condition.map(str::to_string),
+        })
+    }
+
+    fn triggers(&self, cpu: &CPU) -> bool {
+        if self.addr.is_some_and(|addr| addr != cpu.program_counter) {
+            return false;
+        }
+        match &self.condition {
+            None => true,
+            Some(condition) => matches!(
+                expr::evaluate(condition, &register_symbols(cpu), cpu.program_counter),
+                Ok(Some(value)) if value != 0
+            ),
+        }
+    }
+}
+
+/// What stopped execution, with the index into `CPU::watchpoints` or
+/// `CPU::breakpoints`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchHit {
+    Read { index: usize, addr: u16, value: u8 },
+    Write { index: usize, addr: u16, value: u8 },
+    Execute { index: usize, addr: u16 },
+    Breakpoint { index: usize, addr: u16 },
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchHit::Read { index, addr, value } => {
+                write!(
+                    f,
+                    "watchpoint {}: read ${:04X} = {:02X}",
+                    index, addr, value
+                )
+            }
+            WatchHit::Write { index, addr, value } => {
+                write!(
+                    f,
+                    "watchpoint {}: write ${:04X} = {:02X}",
+                    index, addr, value
+                )
+            }
+            WatchHit::Execute { index, addr } => {
+                write!(f, "watchpoint {}: execute ${:04X}", index, addr)
+            }
+            WatchHit::Breakpoint { index, addr } => {
+                write!(f, "breakpoint {} at ${:04X}", index, addr)
+            }
+        }
+    }
+}
+
+/// Registers and flags by name, for breakpoint conditions and the debugger.
+pub fn register_symbols(cpu: &CPU) -> HashMap<String, i64> {
+    let flag = |flag| cpu.status.contains(flag) as i64;
+    let values = [
+        ("a", cpu.register_a as i64),
+        ("x", cpu.register_x as i64),
+        ("y", cpu.register_y as i64),
+        ("sp", cpu.stack_pointer as i64),
+        ("pc", cpu.program_counter as i64),
+        ("p", cpu.status.bits() as i64),
+        ("n", flag(CpuFlags::NEGATIVE)),
+        ("v", flag(CpuFlags::OVERFLOW)),
+        ("d", flag(CpuFlags::DECIMAL_MODE)),
+        ("i", flag(CpuFlags::INTERRUPT_DISABLE)),
+        ("z", flag(CpuFlags::ZERO)),
+        ("c", flag(CpuFlags::CARRY)),
+    ];
+
+    let mut symbols = HashMap::new();
+    for (name, value) in values {
+        symbols.insert(name.to_string(), value);
+        symbols.insert(name.to_ascii_uppercase(), value);
+    }
+    symbols
+}
+
+impl CPU {
+    // called for every access while watchpoints are set, the first hit
+    // sticks until it is taken
+    pub(super) fn watch_access(&mut self, addr: u16, value: u8, access: Access) {
+        if self.watch_hit.is_some() {
+            return;
+        }
+        let index = match self
+            .watchpoints
+            .iter()
+            .position(|w| w.matches(addr, access))
+        {
+            Some(index) => index,
+            None => return,
+        };
+        self.watch_hit = Some(if access == Access::READ {
+            WatchHit::Read { index, addr, value }
+        } else {
+            WatchHit::Write { index, addr, value }
+        });
+    }
+
+    /// Execute watchpoints and breakpoints for the instruction at PC. The
+    /// dispatch loop calls this before each instruction but the first.
+    pub fn check_execute(&self) -> Option<WatchHit> {
+        let pc = self.program_counter;
+        let execute = self
+            .watchpoints
+            .iter()
+            .position(|w| w.matches(pc, Access::EXECUTE))
+            .map(|index| WatchHit::Execute { index, addr: pc });
+
+        execute.or_else(|| {
+            self.breakpoints
+                .iter()
+                .position(|b| b.triggers(self))
+                .map(|index| WatchHit::Breakpoint { index, addr: pc })
+        })
+    }
+
+    /// The watchpoint or breakpoint that stopped `run`, if any. Read and
+    /// write watchpoints stop after the instruction that made the access.
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+}
@@ -0,0 +1,603 @@
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+
+use crate::asm::expr;
+use crate::cheat::Cheat;
+use crate::cpu::undo::UndoLog;
+use crate::cpu::watch::register_symbols;
+use crate::cpu::watch::Access;
+use crate::cpu::watch::Breakpoint;
+use crate::cpu::watch::WatchHit;
+use crate::cpu::watch::Watchpoint;
+use crate::cpu::CpuFlags;
+use crate::cpu::Unimplemented;
+use crate::cpu::CPU;
+use crate::disasm;
+use crate::nes::Nes;
+
+const HELP: &str = "\
+step, s [N]            execute N instructions (default 1)
+back, sb [N]           undo the last N instructions (default 1)
+continue, c            run until a breakpoint or BRK, at most 1000000
+                       instructions at a time
+break, b ADDR [if C]   set a breakpoint, optionally with a condition
+break, b if C          stop wherever condition C holds, e.g. A == $40 && X > 3
+delete, d N            clear breakpoint N
+watch ADDR[..END] [rwx]
+                       stop on read, write or execute (default rw)
+unwatch N              clear watchpoint N
+breakpoints, bl        list breakpoints and watchpoints
+registers, r           show registers and flags
+set REG VALUE          set a, x, y, sp, pc, p or one flag (n v b d i z c)
+mem, x ADDR [LEN]      hex dump LEN bytes (default 64)
+write, w ADDR BYTE..   write bytes into RAM
+writer ADDR            find the instruction that last wrote ADDR
+disasm, u [ADDR] [N]   disassemble N instructions (default 10 from PC)
+cheat, ch              list cheats
+cheat add CODE         add a 6 or 8 letter Game Genie code
+cheat freeze ADDR BYTE keep ADDR at BYTE, rewritten every frame
+cheat on|off|del N     enable, disable or delete cheat N
+help, h                this text
+quit, q                leave the debugger
+
+Numbers use assembler syntax: $FF hex, %1010 binary, 255 decimal. The
+registers can be used by name, e.g. `x pc+3` or `b *+2`. An empty line
+repeats the last command.";
+
+/// Instructions kept for stepping back.
+pub const UNDO_DEPTH: usize = 10_000;
+
+/// Instructions `continue` runs before it gives the prompt back.
+pub const CONTINUE_LIMIT: u64 = 1_000_000;
+
+/// Why execution stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Ran the requested number of instructions.
+    Stepped,
+    Watch(WatchHit),
+    /// BRK was reached.
+    Halted,
+    /// PC points at an opcode the CPU does not implement.
+    Unimplemented(Unimplemented),
+}
+
+/// Interactive debugger for a console, or a bare CPU with flat memory when
+/// no cartridge is loaded.
+pub struct Debugger {
+    pub nes: Nes,
+    /// Instructions `continue` runs without hitting anything before it
+    /// stops anyway.
+    pub continue_limit: u64,
+    last_command: String,
+}
+
+impl Debugger {
+    /// Starts recording an undo log in `nes` unless it already has one.
+    pub fn new(mut nes: Nes) -> Self {
+        if nes.cpu.undo.is_none() {
+            nes.cpu.undo = Some(UndoLog::new(UNDO_DEPTH));
+        }
+        Debugger {
+            nes,
+            continue_limit: CONTINUE_LIMIT,
+            last_command: String::new(),
+        }
+    }
+
+    /// Bare CPU with `program` at `origin`, execution starting there.
+    pub fn with_program(program: &[u8], origin: u16) -> Result<Self, String> {
+        let mut nes = Nes::new();
+        nes.cpu.load_at(origin, program)?;
+        nes.cpu.program_counter = origin;
+        Ok(Debugger::new(nes))
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.nes.cpu
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.nes.cpu.breakpoints.push(Breakpoint::at(addr));
+    }
+
+    /// Removes every breakpoint at `addr`, conditional or not.
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        let breakpoints = &mut self.nes.cpu.breakpoints;
+        let before = breakpoints.len();
+        breakpoints.retain(|b| b.addr != Some(addr));
+        breakpoints.len() != before
+    }
+
+    fn has_breakpoint(&self, addr: u16) -> bool {
+        self.cpu().breakpoints.iter().any(|b| b.addr == Some(addr))
+    }
+
+    // returns false once BRK or an unimplemented opcode has been reached
+    fn step_machine(&mut self) -> bool {
+        if self.nes.cpu.bus.is_some() {
+            self.nes.step_instruction();
+            !self.nes.is_halted()
+        } else {
+            self.nes.cpu.step()
+        }
+    }
+
+    /// Executes up to `count` instructions, or without limit for `None`.
+    /// A breakpoint at the starting PC does not stop the first instruction.
+    pub fn run(&mut self, count: Option<u64>) -> StopReason {
+        self.run_checked(count, false)
+    }
+
+    /// Picks up a run that was cut into pieces, so unlike `run` a breakpoint
+    /// at the current PC stops before anything is executed.
+    pub fn run_more(&mut self, count: u64) -> StopReason {
+        self.run_checked(Some(count), true)
+    }
+
+    fn run_checked(&mut self, count: Option<u64>, check_first: bool) -> StopReason {
+        self.nes.cpu.take_watch_hit();
+        let mut executed = 0;
+        loop {
+            if count == Some(executed) {
+                return StopReason::Stepped;
+            }
+            if executed > 0 || check_first {
+                if let Some(hit) = self.nes.cpu.check_execute() {
+                    return StopReason::Watch(hit);
+                }
+            }
+            if !self.step_machine() {
+                return match self.nes.unimplemented() {
+                    Some(unimplemented) => StopReason::Unimplemented(unimplemented),
+                    None => StopReason::Halted,
+                };
+            }
+            executed += 1;
+
+            if let Some(hit) = self.nes.cpu.take_watch_hit() {
+                return StopReason::Watch(hit);
+            }
+        }
+    }
+
+    /// Runs one command line and returns what it printed.
+    pub fn execute(&mut self, line: &str) -> Result<String, String> {
+        let line = if line.trim().is_empty() {
+            self.last_command.clone()
+        } else {
+            line.trim().to_string()
+        };
+        self.last_command = line.clone();
+
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Ok(String::new()),
+        };
+        let args: Vec<&str> = words.collect();
+
+        match command {
+            "step" | "s" => {
+                let count = self.optional_number(&args, 0, 1)?;
+                let reason = self.run(Some(count as u64));
+                Ok(self.stop_report(reason))
+            }
+            "back" | "sb" => {
+                let count = self.optional_number(&args, 0, 1)?;
+                // all or nothing, a partial undo would leave the CPU somewhere
+                // nobody asked for
+                let depth = self.cpu().undo.as_ref().map_or(0, |undo| undo.len());
+                if depth == 0 {
+                    return Err("no more history to step back through".to_string());
+                }
+                if count > depth {
+                    return Err(format!(
+                        "only {} instructions of history to step back through",
+                        depth
+                    ));
+                }
+                for _ in 0..count {
+                    self.nes.cpu.step_back();
+                }
+                Ok(self.disassemble(self.cpu().program_counter, 1))
+            }
+            "continue" | "c" => {
+                let reason = self.run(Some(self.continue_limit));
+                if reason == StopReason::Stepped {
+                    return Ok(format!(
+                        "still running after {} instructions, c continues\n{}",
+                        self.continue_limit,
+                        self.disassemble(self.cpu().program_counter, 1)
+                    ));
+                }
+                Ok(self.stop_report(reason))
+            }
+            "break" | "b" => self.add_conditional_breakpoint(&args),
+            "delete" | "d" => {
+                let index = self.number(&args, 0)? as usize;
+                if index >= self.cpu().breakpoints.len() {
+                    return Err(format!("no breakpoint {}", index));
+                }
+                self.nes.cpu.breakpoints.remove(index);
+                Ok(format!("deleted breakpoint {}", index))
+            }
+            "watch" => self.add_watchpoint(&args),
+            "unwatch" => {
+                let index = self.number(&args, 0)? as usize;
+                if index >= self.cpu().watchpoints.len() {
+                    return Err(format!("no watchpoint {}", index));
+                }
+                self.nes.cpu.watchpoints.remove(index);
+                Ok(format!("deleted watchpoint {}", index))
+            }
+            "breakpoints" | "bl" => Ok(self.list_breakpoints()),
+            "registers" | "r" => Ok(registers(self.cpu())),
+            "set" => self.set(&args),
+            "mem" | "x" => {
+                let addr = self.address(&args, 0)?;
+                let len = self.optional_number(&args, 1, 64)?;
+                Ok(hex_dump(self.cpu(), addr, len))
+            }
+            "write" | "w" => {
+                let addr = self.address(&args, 0)?;
+                if args.len() < 2 {
+                    return Err("write needs at least one byte".to_string());
+                }
+                for (i, _) in args[1..].iter().enumerate() {
+                    let value = self.number(&args, i + 1)?;
+                    let target = addr.wrapping_add(i as u16);
+                    if !self.nes.cpu.mem_poke(target, value as u8) {
+                        return Err(format!("${:04X} is not RAM", target));
+                    }
+                }
+                Ok(hex_dump(self.cpu(), addr, args.len() - 1))
+            }
+            "writer" => {
+                let addr = self.address(&args, 0)?;
+                let undo = self.cpu().undo.as_ref().ok_or("no undo log is recorded")?;
+                match undo.last_writer(addr) {
+                    Some((pc, age)) => Ok(format!(
+                        "${:04X} last written by\n{}\n`sb {}` steps back to before it",
+                        addr,
+                        self.disassemble(pc, 1),
+                        age + 1
+                    )),
+                    None => Ok(format!(
+                        "${:04X} not written in the last {} instructions",
+                        addr,
+                        undo.len()
+                    )),
+                }
+            }
+            "disasm" | "u" => {
+                let pc = self.cpu().program_counter;
+                let start = match args.first() {
+                    Some(_) => self.address(&args, 0)?,
+                    None => pc,
+                };
+                let count = self.optional_number(&args, 1, 10)?;
+                Ok(self.disassemble(start, count))
+            }
+            "cheat" | "ch" => self.cheat(&args),
+            "help" | "h" => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command '{}', try help", command)),
+        }
+    }
+
+    /// Reads commands from `input` until `quit` or end of input.
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> io::Result<()> {
+        writeln!(
+            output,
+            "{}",
+            self.disassemble(self.cpu().program_counter, 1)
+        )?;
+        write!(output, "> ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            if matches!(line.trim(), "quit" | "q") {
+                break;
+            }
+            match self.execute(&line) {
+                Ok(text) if text.is_empty() => {}
+                Ok(text) => writeln!(output, "{}", text)?,
+                Err(error) => writeln!(output, "error: {}", error)?,
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn stop_report(&self, reason: StopReason) -> String {
+        let next = self.disassemble(self.cpu().program_counter, 1);
+        match reason {
+            StopReason::Stepped => next,
+            StopReason::Watch(hit) => format!("{}\n{}", hit, next),
+            StopReason::Halted => format!("halted at BRK\n{}", next),
+            StopReason::Unimplemented(unimplemented) => format!("{}\n{}", unimplemented, next),
+        }
+    }
+
+    fn disassemble(&self, start: u16, count: usize) -> String {
+        let pc = self.cpu().program_counter;
+        let mut lines = Vec::new();
+        let mut addr = start;
+        for _ in 0..count {
+            let bytes: Vec<u8> = (0..3)
+                .map(|i| self.cpu().mem_peek(addr.wrapping_add(i)))
+                .collect();
+            let instruction = disasm::decode(&bytes, addr);
+            let marker = match (addr == pc, self.has_breakpoint(addr)) {
+                (true, true) => "*>",
+                (true, false) => " >",
+                (false, true) => "* ",
+                (false, false) => "  ",
+            };
+            lines.push(format!("{} {}", marker, instruction));
+            addr = addr.wrapping_add(instruction.len());
+        }
+        lines.join("\n")
+    }
+
+    // break ADDR | break ADDR if COND | break if COND
+    fn add_conditional_breakpoint(&mut self, args: &[&str]) -> Result<String, String> {
+        let split = args.iter().position(|&word| word == "if");
+        let (addr, condition) = match split {
+            Some(0) => (None, Some(args[1..].join(" "))),
+            Some(i) => (Some(self.address(args, 0)?), Some(args[i + 1..].join(" "))),
+            None => (Some(self.address(args, 0)?), None),
+        };
+        if condition.as_deref() == Some("") {
+            return Err("missing condition after 'if'".to_string());
+        }
+
+        let breakpoint = Breakpoint::new(addr, condition.as_deref())?;
+        let text = describe_breakpoint(&breakpoint);
+        self.nes.cpu.breakpoints.push(breakpoint);
+        Ok(format!(
+            "breakpoint {}: {}",
+            self.cpu().breakpoints.len() - 1,
+            text
+        ))
+    }
+
+    // watch ADDR[..END] [rwx]
+    fn add_watchpoint(&mut self, args: &[&str]) -> Result<String, String> {
+        let range = args.first().ok_or("watch needs an address")?;
+        let (start, end) = match range.split_once("..") {
+            Some((start, end)) => (self.address(&[start], 0)?, self.address(&[end], 0)?),
+            None => {
+                let addr = self.address(args, 0)?;
+                (addr, addr)
+            }
+        };
+
+        let mut access = Access::empty();
+        for c in args.get(1).unwrap_or(&"rw").chars() {
+            access |= match c {
+                'r' => Access::READ,
+                'w' => Access::WRITE,
+                'x' => Access::EXECUTE,
+                _ => return Err(format!("unknown access '{}', use r, w or x", c)),
+            };
+        }
+
+        let watchpoint = Watchpoint::new(start.min(end), start.max(end), access);
+        let text = describe_watchpoint(&watchpoint);
+        self.nes.cpu.watchpoints.push(watchpoint);
+        Ok(format!(
+            "watchpoint {}: {}",
+            self.cpu().watchpoints.len() - 1,
+            text
+        ))
+    }
+
+    fn list_breakpoints(&self) -> String {
+        let breakpoints = self
+            .cpu()
+            .breakpoints
+            .iter()
+            .enumerate()
+            .map(|(i, b)| format!("breakpoint {}: {}", i, describe_breakpoint(b)));
+        let watchpoints = self
+            .cpu()
+            .watchpoints
+            .iter()
+            .enumerate()
+            .map(|(i, w)| format!("watchpoint {}: {}", i, describe_watchpoint(w)));
+        breakpoints
+            .chain(watchpoints)
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // cheat | cheat add CODE | cheat freeze ADDR BYTE | cheat on|off|del N
+    fn cheat(&mut self, args: &[&str]) -> Result<String, String> {
+        let cheat = match args.first().copied() {
+            None => return Ok(self.list_cheats()),
+            Some("add") => Cheat::game_genie(args.get(1).ok_or("cheat add needs a code")?)?,
+            Some("freeze") => {
+                let addr = self.address(args, 1)?;
+                let value = self.number(args, 2)? as u8;
+                Cheat::freeze(addr, value)
+            }
+            Some(action @ ("on" | "off" | "del")) => {
+                let index = self.number(args, 1)? as usize;
+                let cheats = &mut self.nes.cpu.cheats;
+                if action == "del" {
+                    cheats.remove(index)?;
+                    return Ok(format!("deleted cheat {}", index));
+                }
+                cheats.set_enabled(index, action == "on")?;
+                let text = cheats.iter().nth(index).unwrap().to_string();
+                self.nes.cpu.apply_freezes();
+                return Ok(format!("cheat {}: {}", index, text));
+            }
+            Some(other) => {
+                return Err(format!(
+                    "unknown cheat action '{}', use add, freeze, on, off or del",
+                    other
+                ))
+            }
+        };
+
+        let text = cheat.to_string();
+        let index = self.nes.cpu.cheats.add(cheat);
+        self.nes.cpu.apply_freezes();
+        Ok(format!("cheat {}: {}", index, text))
+    }
+
+    fn list_cheats(&self) -> String {
+        if self.cpu().cheats.is_empty() {
+            return "no cheats".to_string();
+        }
+        self.cpu()
+            .cheats
+            .iter()
+            .enumerate()
+            .map(|(i, cheat)| format!("cheat {}: {}", i, cheat))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn set(&mut self, args: &[&str]) -> Result<String, String> {
+        let name = args
+            .first()
+            .ok_or("set needs a register")?
+            .to_ascii_lowercase();
+        let value = self.number(args, 1)?;
+        let cpu = &mut self.nes.cpu;
+
+        let flag = match name.as_str() {
+            "n" => Some(CpuFlags::NEGATIVE),
+            "v" => Some(CpuFlags::OVERFLOW),
+            "b" => Some(CpuFlags::BREAK),
+            "d" => Some(CpuFlags::DECIMAL_MODE),
+            "i" => Some(CpuFlags::INTERRUPT_DISABLE),
+            "z" => Some(CpuFlags::ZERO),
+            "c" => Some(CpuFlags::CARRY),
+            _ => None,
+        };
+
+        match (name.as_str(), flag) {
+            (_, Some(flag)) => cpu.status.set(flag, value != 0),
+            ("a", _) => cpu.register_a = value as u8,
+            ("x", _) => cpu.register_x = value as u8,
+            ("y", _) => cpu.register_y = value as u8,
+            ("sp", _) => cpu.stack_pointer = value as u8,
+            ("pc", _) => cpu.program_counter = value as u16,
+            ("p", _) => cpu.status = CpuFlags::from_bits_truncate(value as u8),
+            _ => return Err(format!("unknown register '{}'", name)),
+        }
+
+        Ok(registers(cpu))
+    }
+
+    fn number(&self, args: &[&str], index: usize) -> Result<i64, String> {
+        let text = args.get(index).ok_or("missing argument")?;
+        expr::evaluate(
+            text,
+            &register_symbols(self.cpu()),
+            self.cpu().program_counter,
+        )?
+        .ok_or_else(|| format!("unknown name in '{}'", text))
+    }
+
+    fn optional_number(
+        &self,
+        args: &[&str],
+        index: usize,
+        default: usize,
+    ) -> Result<usize, String> {
+        match args.get(index) {
+            Some(_) => Ok(self.number(args, index)?.max(0) as usize),
+            None => Ok(default),
+        }
+    }
+
+    fn address(&self, args: &[&str], index: usize) -> Result<u16, String> {
+        let value = self.number(args, index)?;
+        if !(0..=0xFFFF).contains(&value) {
+            return Err(format!("{} is not an address", value));
+        }
+        Ok(value as u16)
+    }
+}
+
+fn describe_breakpoint(breakpoint: &Breakpoint) -> String {
+    match (breakpoint.addr, &breakpoint.condition) {
+        (Some(addr), Some(condition)) => format!("${:04X} if {}", addr, condition),
+        (Some(addr), None) => format!("${:04X}", addr),
+        (None, Some(condition)) => format!("if {}", condition),
+        (None, None) => "everywhere".to_string(),
+    }
+}
+
+fn describe_watchpoint(watchpoint: &Watchpoint) -> String {
+    let access: String = [
+        (Access::READ, 'r'),
+        (Access::WRITE, 'w'),
+        (Access::EXECUTE, 'x'),
+    ]
+    .iter()
+    .filter(|(access, _)| watchpoint.access.contains(*access))
+    .map(|(_, c)| c)
+    .collect();
+
+    if watchpoint.start == watchpoint.end {
+        format!("${:04X} {}", watchpoint.start, access)
+    } else {
+        format!(
+            "${:04X}..${:04X} {}",
+            watchpoint.start, watchpoint.end, access
+        )
+    }
+}
+
+/// `PC:8000 A:00 X:00 Y:00 SP:FD P:24 nv-bdIzc CYC:7`, set flags upper case.
+pub fn registers(cpu: &CPU) -> String {
+    let flags: String = "nv-bdizc"
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if c != '-' && cpu.status.bits() & (0x80 >> i) != 0 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    format!(
+        "PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} P:{:02X} {} CYC:{}",
+        cpu.program_counter,
+        cpu.register_a,
+        cpu.register_x,
+        cpu.register_y,
+        cpu.stack_pointer,
+        cpu.status.bits(),
+        flags,
+        cpu.cycles
+    )
+}
+
+/// Sixteen bytes per line, read without side effects.
+pub fn hex_dump(cpu: &CPU, start: u16, len: usize) -> String {
+    let mut lines = Vec::new();
+    for offset in (0..len).step_by(16) {
+        let addr = start.wrapping_add(offset as u16);
+        let bytes: Vec<String> = (offset..len.min(offset + 16))
+            .map(|i| format!("{:02X}", cpu.mem_peek(start.wrapping_add(i as u16))))
+            .collect();
+        lines.push(format!("{:04X}: {}", addr, bytes.join(" ")));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,299 @@
+use super::*;
+use crate::cartridge::test_rom::test_rom;
+
+fn debugger() -> Debugger {
+    let program = crate::assemble!(
+        "       LDX #0",
+        "loop:  INX",
+        "       STA $0200,X",
+        "       CPX #3",
+        "       BNE loop",
+        "       BRK",
+    );
+    Debugger::with_program(&program, 0x8000).unwrap()
+}
+
+#[test]
+fn test_step() {
+    let mut debugger = debugger();
+
+    assert_eq!(debugger.execute("step").unwrap(), " > 8002  E8        INX");
+    assert_eq!(
+        debugger.execute("s 2").unwrap(),
+        " > 8006  E0 03     CPX #$03"
+    );
+    assert_eq!(debugger.cpu().register_x, 1);
+}
+
+#[test]
+fn test_breakpoints() {
+    let mut debugger = debugger();
+
+    debugger.execute("b $8002").unwrap();
+    assert_eq!(
+        debugger.execute("c").unwrap(),
+        "breakpoint 0 at $8002\n*> 8002  E8        INX"
+    );
+    assert_eq!(debugger.cpu().register_x, 0);
+
+    // continuing from a breakpoint runs the loop once more
+    debugger.execute("c").unwrap();
+    assert_eq!(debugger.cpu().register_x, 1);
+
+    assert_eq!(debugger.execute("bl").unwrap(), "breakpoint 0: $8002");
+    debugger.execute("d 0").unwrap();
+    assert!(debugger.execute("d 0").is_err());
+    assert_eq!(
+        debugger.execute("c").unwrap(),
+        "halted at BRK\n > 800B  00        BRK"
+    );
+    assert_eq!(debugger.cpu().register_x, 3);
+}
+
+#[test]
+fn test_unimplemented_opcode_stops() {
+    // LDX #1; PHP
+    let mut debugger = Debugger::with_program(&[0xA2, 0x01, 0x08], 0x8000).unwrap();
+
+    assert_eq!(
+        debugger.execute("s 5").unwrap(),
+        "unimplemented opcode $08 at $8002\n > 8002  08        .byte $08"
+    );
+    assert_eq!(
+        debugger.execute("c").unwrap(),
+        "unimplemented opcode $08 at $8002\n > 8002  08        .byte $08"
+    );
+    assert_eq!(debugger.cpu().register_x, 1);
+}
+
+#[test]
+fn test_continue_is_capped() {
+    // JMP $8000
+    let mut debugger = Debugger::with_program(&[0x4C, 0x00, 0x80], 0x8000).unwrap();
+    debugger.continue_limit = 100;
+
+    assert_eq!(
+        debugger.execute("c").unwrap(),
+        "still running after 100 instructions, c continues\n > 8000  4C 00 80  JMP $8000"
+    );
+    assert_eq!(debugger.cpu().cycles, 300);
+}
+
+#[test]
+fn test_conditional_breakpoint() {
+    let mut debugger = debugger();
+
+    assert_eq!(
+        debugger.execute("b $8006 if X == 2").unwrap(),
+        "breakpoint 0: $8006 if X == 2"
+    );
+    debugger.execute("b if x >= 3 && z").unwrap();
+    assert!(debugger.execute("b if q == 1").is_err());
+    assert!(debugger.execute("b $8000 if").is_err());
+
+    assert_eq!(
+        debugger.execute("c").unwrap(),
+        "breakpoint 0 at $8006\n*> 8006  E0 03     CPX #$03"
+    );
+    assert_eq!(debugger.cpu().register_x, 2);
+
+    // X is 3 and CPX sets Z, so the second one stops at the BNE
+    assert_eq!(
+        debugger.execute("c").unwrap(),
+        "breakpoint 1 at $8008\n > 8008  D0 F8     BNE $8002"
+    );
+}
+
+#[test]
+fn test_watchpoints() {
+    let mut debugger = debugger();
+
+    assert_eq!(
+        debugger.execute("watch $0202..$0203 w").unwrap(),
+        "watchpoint 0: $0202..$0203 w"
+    );
+    debugger.execute("watch $800A x").unwrap();
+    assert_eq!(
+        debugger.execute("bl").unwrap(),
+        "watchpoint 0: $0202..$0203 w\nwatchpoint 1: $800A x"
+    );
+
+    assert_eq!(
+        debugger.execute("c").unwrap(),
+        "watchpoint 0: write $0202 = 00\n > 8006  E0 03     CPX #$03"
+    );
+    assert_eq!(
+        debugger.execute("c").unwrap(),
+        "watchpoint 0: write $0203 = 00\n > 8006  E0 03     CPX #$03"
+    );
+    assert_eq!(
+        debugger.execute("c").unwrap(),
+        "watchpoint 1: execute $800A\n > 800A  00        BRK"
+    );
+
+    debugger.execute("unwatch 0").unwrap();
+    assert!(debugger.execute("unwatch 1").is_err());
+    assert!(debugger.execute("watch $10 q").is_err());
+}
+
+#[test]
+fn test_empty_line_repeats() {
+    let mut debugger = debugger();
+
+    debugger.execute("s").unwrap();
+    debugger.execute("").unwrap();
+
+    assert_eq!(debugger.cpu().program_counter, 0x8003);
+}
+
+#[test]
+fn test_registers_and_set() {
+    let mut debugger = debugger();
+
+    assert_eq!(
+        debugger.execute("r").unwrap(),
+        "PC:8000 A:00 X:00 Y:00 SP:FD P:24 nv-bdIzc CYC:0"
+    );
+
+    debugger.execute("set a $40").unwrap();
+    debugger.execute("set c 1").unwrap();
+    debugger.execute("set i 0").unwrap();
+    assert_eq!(
+        debugger.execute("set pc pc+2").unwrap(),
+        "PC:8002 A:40 X:00 Y:00 SP:FD P:21 nv-bdizC CYC:0"
+    );
+    assert!(debugger.execute("set q 1").is_err());
+}
+
+#[test]
+fn test_memory() {
+    let mut debugger = debugger();
+
+    assert_eq!(
+        debugger.execute("w $0200 1 2 $FF").unwrap(),
+        "0200: 01 02 FF"
+    );
+    assert_eq!(
+        debugger.execute("x $01FE 20").unwrap(),
+        "01FE: 00 00 01 02 FF 00 00 00 00 00 00 00 00 00 00 00\n020E: 00 00 00 00"
+    );
+}
+
+#[test]
+fn test_disassemble_around_pc() {
+    let mut debugger = debugger();
+    debugger.execute("s").unwrap();
+    debugger.execute("b $8006").unwrap();
+
+    assert_eq!(
+        debugger.execute("u $8000 4").unwrap(),
+        [
+            "   8000  A2 00     LDX #$00",
+            " > 8002  E8        INX",
+            "   8003  9D 00 02  STA $0200,X",
+            "*  8006  E0 03     CPX #$03",
+        ]
+        .join("\n")
+    );
+}
+
+#[test]
+fn test_poke_rom_is_refused() {
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom(&[], 0xC000, 0xC000, false)).unwrap();
+    let mut debugger = Debugger::new(nes);
+
+    assert!(debugger.execute("w $C000 1").is_err());
+    debugger.execute("w $0801 7").unwrap();
+    assert_eq!(debugger.cpu().mem_peek(0x0001), 7);
+}
+
+#[test]
+fn test_repl() {
+    let mut debugger = debugger();
+    let mut output = Vec::new();
+
+    debugger
+        .repl("s\nfoo\nq\ns\n".as_bytes(), &mut output)
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        " > 8000  A2 00     LDX #$00\n>  > 8002  E8        INX\n> error: unknown command 'foo', try help\n> "
+    );
+    assert_eq!(debugger.cpu().program_counter, 0x8002);
+}
+
+#[test]
+fn test_step_back_and_writer() {
+    let mut debugger = debugger();
+    debugger.execute("set a $42").unwrap();
+    debugger.execute("s 4").unwrap();
+    assert_eq!(debugger.cpu().mem_peek(0x0201), 0x42);
+
+    assert_eq!(
+        debugger.execute("writer $0201").unwrap(),
+        "$0201 last written by\n   8003  9D 00 02  STA $0200,X\n`sb 2` steps back to before it"
+    );
+    assert_eq!(
+        debugger.execute("sb 2").unwrap(),
+        " > 8003  9D 00 02  STA $0200,X"
+    );
+    assert_eq!(debugger.cpu().mem_peek(0x0201), 0);
+    assert_eq!(debugger.cpu().register_x, 1);
+    assert_eq!(
+        debugger.execute("writer $0201").unwrap(),
+        "$0201 not written in the last 2 instructions"
+    );
+
+    assert_eq!(
+        debugger.execute("sb 3"),
+        Err("only 2 instructions of history to step back through".to_string())
+    );
+    assert_eq!(debugger.cpu().program_counter, 0x8003);
+    debugger.execute("sb 2").unwrap();
+    assert_eq!(debugger.cpu().program_counter, 0x8000);
+    assert_eq!(
+        debugger.execute("back"),
+        Err("no more history to step back through".to_string())
+    );
+}
+
+#[test]
+fn test_cheats() {
+    let mut debugger = debugger();
+
+    assert_eq!(debugger.execute("cheat").unwrap(), "no cheats");
+    assert_eq!(
+        debugger.execute("cheat add sxiopo").unwrap(),
+        "cheat 0: SXIOPO $91D9 = $AD"
+    );
+    assert_eq!(
+        debugger.execute("ch freeze $0201 $77").unwrap(),
+        "cheat 1: freeze $0201 = $77"
+    );
+    // a freeze takes effect right away
+    assert_eq!(debugger.cpu().mem_peek(0x0201), 0x77);
+    assert_eq!(debugger.cpu().mem_peek(0x91D9), 0xAD);
+
+    assert_eq!(
+        debugger.execute("cheat off 0").unwrap(),
+        "cheat 0: SXIOPO $91D9 = $AD (off)"
+    );
+    assert_eq!(debugger.cpu().mem_peek(0x91D9), 0x00);
+    assert_eq!(
+        debugger.execute("ch").unwrap(),
+        "cheat 0: SXIOPO $91D9 = $AD (off)\ncheat 1: freeze $0201 = $77"
+    );
+
+    assert_eq!(debugger.execute("cheat del 0").unwrap(), "deleted cheat 0");
+    assert_eq!(
+        debugger.execute("cheat on 5"),
+        Err("no cheat 5".to_string())
+    );
+    assert_eq!(
+        debugger.execute("cheat add APZ"),
+        Err("Game Genie codes have 6 or 8 letters, 'APZ' has 3".to_string())
+    );
+    assert!(debugger.execute("cheat swap 1").is_err());
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+
+use crate::cpu::CPU;
+use crate::helper::AddressingMode;
+use crate::helper::EmmulationHelpers;
+use crate::helper::OpCodeCat;
+
+/// One decoded instruction, or a `.byte` for opcodes the table does not know.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    pub operand: String,
+}
+
+impl Instruction {
+    pub fn len(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Assembly text only, e.g. `LDA ($20),Y`.
+    pub fn text(&self) -> String {
+        if self.operand.is_empty() {
+            self.mnemonic.to_string()
+        } else {
+            format!("{} {}", self.mnemonic, self.operand)
+        }
+    }
+}
+
+/// Listing line: address, raw bytes and assembly, e.g.
+/// `8000  B1 20     LDA ($20),Y`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex: Vec<String> = self.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        write!(
+            f,
+            "{:04X}  {:<8}  {}",
+            self.address,
+            hex.join(" "),
+            self.text()
+        )
+    }
+}
+
+/// Operand text for `mode`, `operand` holds the bytes after the opcode and
+/// `address` is where the instruction itself lives (needed for branches).
+pub fn format_operand(mode: &AddressingMode, operand: &[u8], address: u16) -> String {
+    let byte = || operand[0];
+    let word = || u16::from_le_bytes([operand[0], operand[1]]);
+
+    match mode {
+        AddressingMode::Immediate => format!("#${:02X}", byte()),
+        AddressingMode::ZeroPage => format!("${:02X}", byte()),
+        AddressingMode::ZeroPage_X => format!("${:02X},X", byte()),
+        AddressingMode::ZeroPage_Y => format!("${:02X},Y", byte()),
+        AddressingMode::Absolute => format!("${:04X}", word()),
+        AddressingMode::Absolute_X => format!("${:04X},X", word()),
+        AddressingMode::Absolute_Y => format!("${:04X},Y", word()),
+        AddressingMode::Indirect => format!("(${:04X})", word()),
+        AddressingMode::Indirect_X => format!("(${:02X},X)", byte()),
+        AddressingMode::Indirect_Y => format!("(${:02X}),Y", byte()),
+        AddressingMode::Relative => {
+            let target = address.wrapping_add(2).wrapping_add(byte() as i8 as u16);
+            format!("${:04X}", target)
+        }
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::Implied | AddressingMode::NoneAddressing => String::new(),
+    }
+}
+
+/// Decodes the instruction at the start of `bytes`, which is located at
+/// `address`. Unknown opcodes and instructions cut short by the end of the
+/// slice come back as a single `.byte`.
+///
+/// # Panics
+///
+/// If `bytes` is empty.
+pub fn decode(bytes: &[u8], address: u16) -> Instruction {
+    let opcode = EmmulationHelpers::get_op_code_struct(bytes[0]);
+    let length = opcode.bytes as usize;
+
+    if matches!(opcode.match_code, OpCodeCat::NONE) || bytes.len() < length {
+        return Instruction {
+            address,
+            bytes: vec![bytes[0]],
+            mnemonic: ".byte",
+            operand: format!("${:02X}", bytes[0]),
+        };
+    }
+
+    Instruction {
+        address,
+        bytes: bytes[..length].to_vec(),
+        mnemonic: opcode.code_name,
+        operand: format_operand(&opcode.mode, &bytes[1..length], address),
+    }
+}
+
+/// Linear sweep over `bytes` loaded at `origin`.
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<Instruction> {
+    let mut result = Vec::new();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let instruction = decode(&bytes[offset..], origin.wrapping_add(offset as u16));
+        offset += instruction.bytes.len();
+        result.push(instruction);
+    }
+
+    result
+}
+
+/// Linear sweep over CPU memory from `start` to `end` inclusive. Memory is
+/// read with `CPU::mem_peek`, so I/O registers are not disturbed.
+pub fn disassemble_memory(cpu: &CPU, start: u16, end: u16) -> Vec<Instruction> {
+    let mut result = Vec::new();
+    let mut address = start;
+
+    loop {
+        let bytes: Vec<u8> = (0..3)
+            .map(|i| cpu.mem_peek(address.wrapping_add(i)))
+            .collect();
+        let instruction = decode(&bytes, address);
+        let last = address.wrapping_add(instruction.len() - 1);
+        let wrapped = last < address;
+
+        address = address.wrapping_add(instruction.len());
+        result.push(instruction);
+
+        if wrapped || last >= end || address == 0 {
+            break;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,103 @@
+use super::*;
+
+#[test]
+fn test_addressing_modes() {
+    let program = [
+        0xa9, 0x0a, // LDA #$0A
+        0xa5, 0x20, // LDA $20
+        0xb5, 0x20, // LDA $20,X
+        0xb6, 0x20, // LDX $20,Y
+        0xad, 0x34, 0x12, // LDA $1234
+        0xbd, 0x34, 0x12, // LDA $1234,X
+        0xb9, 0x34, 0x12, // LDA $1234,Y
+        0x6c, 0xfc, 0xff, // JMP ($FFFC)
+        0xa1, 0x20, // LDA ($20,X)
+        0xb1, 0x20, // LDA ($20),Y
+        0x0a, // ASL A
+        0xaa, // TAX
+        0x00, // BRK
+    ];
+
+    let text: Vec<String> = disassemble(&program, 0x8000)
+        .iter()
+        .map(|i| i.text())
+        .collect();
+
+    assert_eq!(
+        text,
+        vec![
+            "LDA #$0A",
+            "LDA $20",
+            "LDA $20,X",
+            "LDX $20,Y",
+            "LDA $1234",
+            "LDA $1234,X",
+            "LDA $1234,Y",
+            "JMP ($FFFC)",
+            "LDA ($20,X)",
+            "LDA ($20),Y",
+            "ASL A",
+            "TAX",
+            "BRK",
+        ]
+    );
+}
+
+#[test]
+fn test_branch_targets() {
+    // BNE forward, BEQ backward
+    let program = [0xd0, 0x0e, 0xf0, 0xfc];
+
+    let instructions = disassemble(&program, 0x8000);
+
+    assert_eq!(instructions[0].text(), "BNE $8010");
+    assert_eq!(instructions[1].text(), "BEQ $8000");
+}
+
+#[test]
+fn test_unknown_and_truncated() {
+    let instructions = disassemble(&[0x02, 0xad, 0x00], 0xC000);
+
+    assert_eq!(instructions.len(), 3);
+    assert_eq!(instructions[0].text(), ".byte $02");
+    assert_eq!(instructions[1].text(), ".byte $AD");
+    assert_eq!(instructions[1].address, 0xC001);
+    assert_eq!(instructions[2].text(), "BRK");
+}
+
+#[test]
+fn test_listing_line() {
+    let instruction = decode(&[0xb1, 0x20], 0x8000);
+
+    assert_eq!(instruction.to_string(), "8000  B1 20     LDA ($20),Y");
+}
+
+#[test]
+fn test_disassemble_memory() {
+    let mut cpu = CPU::new();
+    cpu.load(vec![0xa9, 0x05, 0xd0, 0xfc, 0xe8, 0x00]);
+
+    let listing: Vec<String> = disassemble_memory(&cpu, 0x8000, 0x8004)
+        .iter()
+        .map(|i| i.to_string())
+        .collect();
+
+    assert_eq!(
+        listing,
+        vec![
+            "8000  A9 05     LDA #$05",
+            "8002  D0 FC     BNE $8000",
+            "8004  E8        INX",
+        ]
+    );
+}
+
+#[test]
+fn test_disassemble_memory_end_of_address_space() {
+    let cpu = CPU::new();
+
+    let instructions = disassemble_memory(&cpu, 0xFFFE, 0xFFFF);
+
+    assert_eq!(instructions.len(), 2);
+    assert_eq!(instructions[1].address, 0xFFFF);
+}
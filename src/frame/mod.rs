@@ -0,0 +1,60 @@
+pub mod palette;
+pub mod screenshot;
+
+use palette::SYSTEM_PALETTE;
+
+pub use screenshot::{ImageFormat, ScreenshotOptions};
+
+/// One finished picture from the PPU, stored as 6-bit palette indices so
+/// it can be compared and converted without losing what the PPU produced.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub data: Vec<u8>,
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Frame {
+    pub const WIDTH: usize = 256;
+    pub const HEIGHT: usize = 240;
+
+    pub fn new() -> Self {
+        Frame {
+            data: vec![0; Frame::WIDTH * Frame::HEIGHT],
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, colour: u8) {
+        if x < Frame::WIDTH && y < Frame::HEIGHT {
+            self.data[y * Frame::WIDTH + x] = colour & 0x3F;
+        }
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> u8 {
+        self.data[y * Frame::WIDTH + x]
+    }
+
+    /// Colour of a pixel after 2C02 palette conversion.
+    pub fn rgb(&self, x: usize, y: usize) -> (u8, u8, u8) {
+        SYSTEM_PALETTE[(self.get_pixel(x, y) & 0x3F) as usize]
+    }
+
+    /// Whole frame as packed RGB24, row by row.
+    pub fn to_rgb(&self) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(self.data.len() * 3);
+
+        for &colour in self.data.iter() {
+            let (r, g, b) = SYSTEM_PALETTE[(colour & 0x3F) as usize];
+            rgb.extend_from_slice(&[r, g, b]);
+        }
+
+        rgb
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,133 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::Frame;
+
+// NTSC TVs hide roughly the top and bottom eight scanlines.
+const NTSC_OVERSCAN_LINES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    /// Picks the format from a `.ppm` or `.png` file extension.
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenshotOptions {
+    /// Integer upscale, each pixel becomes a `scale` x `scale` block.
+    pub scale: usize,
+    /// Drop the scanlines an NTSC television would not show.
+    pub crop_overscan: bool,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        ScreenshotOptions {
+            scale: 1,
+            crop_overscan: false,
+        }
+    }
+}
+
+impl Frame {
+    /// Width, height and RGB24 pixels after cropping and scaling.
+    fn screenshot_pixels(&self, options: &ScreenshotOptions) -> (usize, usize, Vec<u8>) {
+        let scale = options.scale.max(1);
+        let (first_line, last_line) = if options.crop_overscan {
+            (NTSC_OVERSCAN_LINES, Frame::HEIGHT - NTSC_OVERSCAN_LINES)
+        } else {
+            (0, Frame::HEIGHT)
+        };
+
+        let width = Frame::WIDTH * scale;
+        let height = (last_line - first_line) * scale;
+        let mut pixels = Vec::with_capacity(width * height * 3);
+
+        for y in first_line..last_line {
+            let mut row = Vec::with_capacity(width * 3);
+            for x in 0..Frame::WIDTH {
+                let (r, g, b) = self.rgb(x, y);
+                for _ in 0..scale {
+                    row.extend_from_slice(&[r, g, b]);
+                }
+            }
+            for _ in 0..scale {
+                pixels.extend_from_slice(&row);
+            }
+        }
+
+        (width, height, pixels)
+    }
+
+    /// Binary (P6) portable pixmap.
+    pub fn to_ppm(&self, options: &ScreenshotOptions) -> Vec<u8> {
+        let (width, height, pixels) = self.screenshot_pixels(options);
+
+        let mut ppm = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+        ppm.extend_from_slice(&pixels);
+        ppm
+    }
+
+    /// 8-bit RGB PNG.
+    pub fn to_png(&self, options: &ScreenshotOptions) -> io::Result<Vec<u8>> {
+        let (width, height, pixels) = self.screenshot_pixels(options);
+
+        let mut png_data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png_data, width as u32, height as u32);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            // spelled out so the output does not follow changes of defaults
+            encoder.set_compression(png::Compression::Fast);
+            encoder.set_filter(png::FilterType::Sub);
+            encoder.set_adaptive_filter(png::AdaptiveFilterType::NonAdaptive);
+
+            let mut writer = encoder.write_header().map_err(io::Error::other)?;
+            writer.write_image_data(&pixels).map_err(io::Error::other)?;
+        }
+
+        Ok(png_data)
+    }
+
+    pub fn encode_screenshot(
+        &self,
+        format: ImageFormat,
+        options: &ScreenshotOptions,
+    ) -> io::Result<Vec<u8>> {
+        match format {
+            ImageFormat::Ppm => Ok(self.to_ppm(options)),
+            ImageFormat::Png => self.to_png(options),
+        }
+    }
+
+    /// Writes the frame to `path`, the format is taken from its extension.
+    pub fn save_screenshot<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &ScreenshotOptions,
+    ) -> io::Result<()> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported screenshot format: {}", path.display()),
+            )
+        })?;
+
+        fs::write(path, self.encode_screenshot(format, options)?)
+    }
+}
@@ -0,0 +1,148 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use super::*;
+
+/// 16x16 tiles walking through the whole palette, with a one pixel white
+/// border so cropping and scaling mistakes show up at the edges.
+fn test_pattern() -> Frame {
+    let mut frame = Frame::new();
+
+    for y in 0..Frame::HEIGHT {
+        for x in 0..Frame::WIDTH {
+            let colour = if x == 0 || y == 0 || x == Frame::WIDTH - 1 || y == Frame::HEIGHT - 1 {
+                0x30
+            } else {
+                (x / 16 + (y / 16) * 16) % 64
+            };
+            frame.set_pixel(x, y, colour as u8);
+        }
+    }
+
+    frame
+}
+
+/// Reads `src/frame/golden/<name>`. Run with `UPDATE_GOLDEN=1` to rewrite
+/// it with `actual` first.
+fn golden_file(name: &str, actual: &[u8]) -> Vec<u8> {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "src", "frame", "golden", name]
+        .iter()
+        .collect();
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+    }
+
+    fs::read(&path).unwrap()
+}
+
+/// Compares `actual` byte-for-byte against the golden file `name`.
+fn assert_golden(name: &str, actual: &[u8]) {
+    let expected = golden_file(name, actual);
+    assert_eq!(expected.len(), actual.len(), "{} size differs", name);
+    let first_diff = expected.iter().zip(actual).position(|(e, a)| e != a);
+    assert_eq!(first_diff, None, "{} differs from golden file", name);
+}
+
+/// Size, colour type and pixels of a PNG image.
+fn decode_png(data: &[u8]) -> (u32, u32, png::ColorType, Vec<u8>) {
+    let mut reader = png::Decoder::new(data).read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    pixels.truncate(info.buffer_size());
+    (info.width, info.height, info.color_type, pixels)
+}
+
+/// Compares the decoded image in `actual` against the golden PNG `name`,
+/// for images whose encoding is already pinned down by `pattern.png`.
+fn assert_golden_png(name: &str, actual: &[u8]) {
+    let (width, height, colour, pixels) = decode_png(&golden_file(name, actual));
+    let (actual_width, actual_height, actual_colour, actual_pixels) = decode_png(actual);
+
+    assert_eq!(
+        (width, height, colour),
+        (actual_width, actual_height, actual_colour),
+        "{} format differs",
+        name
+    );
+    let first_diff = pixels.iter().zip(&actual_pixels).position(|(e, a)| e != a);
+    assert_eq!(first_diff, None, "{} differs from golden file", name);
+}
+
+#[test]
+fn test_palette_conversion() {
+    let mut frame = Frame::new();
+    frame.set_pixel(0, 0, 0x30);
+    frame.set_pixel(1, 0, 0x16);
+    frame.set_pixel(2, 0, 0x4D);
+
+    assert_eq!(frame.rgb(0, 0), (0xFF, 0xFF, 0xFF));
+    assert_eq!(frame.rgb(1, 0), (0xFF, 0x22, 0x00));
+    // only 6 bits of colour exist
+    assert_eq!(frame.get_pixel(2, 0), 0x0D);
+    assert_eq!(
+        &frame.to_rgb()[0..9],
+        &[0xFF, 0xFF, 0xFF, 0xFF, 0x22, 0x00, 0, 0, 0]
+    );
+}
+
+#[test]
+fn test_ppm_header() {
+    let frame = Frame::new();
+
+    let ppm = frame.to_ppm(&ScreenshotOptions {
+        scale: 2,
+        crop_overscan: true,
+    });
+
+    assert!(ppm.starts_with(b"P6\n512 448\n255\n"));
+    assert_eq!(ppm.len(), "P6\n512 448\n255\n".len() + 512 * 448 * 3);
+}
+
+#[test]
+fn test_image_format_from_path() {
+    assert_eq!(
+        ImageFormat::from_path(std::path::Path::new("shot.PNG")),
+        Some(ImageFormat::Png)
+    );
+    assert_eq!(
+        ImageFormat::from_path(std::path::Path::new("shot.ppm")),
+        Some(ImageFormat::Ppm)
+    );
+    assert_eq!(
+        ImageFormat::from_path(std::path::Path::new("shot.bmp")),
+        None
+    );
+}
+
+#[test]
+fn test_golden_ppm_cropped() {
+    let ppm = test_pattern().to_ppm(&ScreenshotOptions {
+        scale: 1,
+        crop_overscan: true,
+    });
+
+    assert_golden("pattern_cropped.ppm", &ppm);
+}
+
+#[test]
+fn test_golden_png() {
+    let png = test_pattern()
+        .to_png(&ScreenshotOptions::default())
+        .unwrap();
+
+    assert_golden("pattern.png", &png);
+}
+
+#[test]
+fn test_golden_png_scaled_cropped() {
+    let png = test_pattern()
+        .to_png(&ScreenshotOptions {
+            scale: 2,
+            crop_overscan: true,
+        })
+        .unwrap();
+
+    assert_golden_png("pattern_x2_cropped.png", &png);
+}
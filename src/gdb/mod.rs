@@ -0,0 +1,367 @@
+use std::io;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+
+use crate::cpu::watch::Access;
+use crate::cpu::watch::Watchpoint;
+use crate::cpu::CpuFlags;
+use crate::cpu::CPU;
+use crate::debugger::Debugger;
+use crate::debugger::StopReason;
+
+/// Instructions run between checks for an interrupt from the client.
+const CHUNK: u64 = 10_000;
+
+/// Largest packet the client may send, as advertised in `qSupported`.
+const PACKET_SIZE: usize = 0x1000;
+
+const INTERRUPT: u8 = 0x03;
+
+// signal numbers for stop replies
+const SIGINT: &str = "S02";
+const SIGILL: &str = "S04";
+const SIGTRAP: &str = "S05";
+
+/// # GDB remote serial protocol stub https://sourceware.org/gdb/onlinedocs/gdb/Remote-Protocol.html
+///
+/// GDB has no 6502 target, so the register file is laid out as
+///
+/// ```text
+///  reg  0  1  2  3  4   5
+///       A  X  Y  P  SP  PC
+///  bits 8  8  8  8  8   16 (little endian)
+/// ```
+///
+/// Supported packets: `?`, `g`/`G`, `p`/`P`, `m`/`M`, `s`, `c`, `Z0`/`z0`
+/// software breakpoints, `Z2`-`Z4`/`z2`-`z4` write, read and access
+/// watchpoints, `D`, `k` and the `qSupported`/`QStartNoAckMode` handshake.
+/// Everything else gets the empty "not supported" reply.
+pub struct GdbStub {
+    pub debugger: Debugger,
+    no_ack: bool,
+}
+
+enum Incoming {
+    Packet(String),
+    Nack,
+    Interrupt,
+}
+
+impl GdbStub {
+    pub fn new(debugger: Debugger) -> Self {
+        GdbStub {
+            debugger,
+            no_ack: false,
+        }
+    }
+
+    /// Waits for one client on `listener` and serves it until it detaches,
+    /// kills the target or disconnects.
+    pub fn serve(&mut self, listener: &TcpListener) -> io::Result<()> {
+        let (stream, _) = listener.accept()?;
+        self.serve_connection(stream)
+    }
+
+    pub fn serve_connection(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        self.no_ack = false;
+        let mut last_reply = String::new();
+
+        loop {
+            let packet = match self.read_packet(&mut stream)? {
+                None => return Ok(()),
+                Some(Incoming::Nack) => {
+                    send_packet(&mut stream, &last_reply)?;
+                    continue;
+                }
+                Some(Incoming::Interrupt) => {
+                    last_reply = SIGINT.to_string();
+                    send_packet(&mut stream, &last_reply)?;
+                    continue;
+                }
+                Some(Incoming::Packet(packet)) => packet,
+            };
+
+            let (reply, close) = self.handle(&packet, &mut stream)?;
+            send_packet(&mut stream, &reply)?;
+            last_reply = reply;
+            if close {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read_packet(&self, stream: &mut TcpStream) -> io::Result<Option<Incoming>> {
+        loop {
+            match read_byte(stream)? {
+                None => return Ok(None),
+                Some(b'$') => {}
+                Some(b'-') => return Ok(Some(Incoming::Nack)),
+                Some(INTERRUPT) => return Ok(Some(Incoming::Interrupt)),
+                // acks and noise between packets
+                Some(_) => continue,
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match read_byte(stream)? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+            let mut checksum = [0; 2];
+            stream.read_exact(&mut checksum)?;
+
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            let valid = expected == Some(checksum_of(&data));
+            if !self.no_ack {
+                stream.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(Some(Incoming::Packet(
+                    String::from_utf8_lossy(&data).into_owned(),
+                )));
+            }
+        }
+    }
+
+    /// Reply to one packet, and whether the connection ends with it.
+    fn handle(&mut self, packet: &str, stream: &mut TcpStream) -> io::Result<(String, bool)> {
+        let cpu = &mut self.debugger.nes.cpu;
+        let command = match packet.as_bytes().first() {
+            Some(&byte) if byte.is_ascii() => byte,
+            _ => return Ok((String::new(), false)),
+        };
+        // an ASCII first byte always leaves a valid rest
+        let args = packet.get(1..).unwrap_or("");
+
+        let reply = match command {
+            b'?' => SIGTRAP.to_string(),
+            b'g' => read_registers(cpu),
+            b'G' => reply_ok(write_registers(cpu, args)),
+            b'p' => match usize::from_str_radix(args, 16) {
+                Ok(register) => read_register(cpu, register),
+                Err(_) => "E01".to_string(),
+            },
+            b'P' => reply_ok(args.split_once('=').and_then(|(register, value)| {
+                let register = usize::from_str_radix(register, 16).ok()?;
+                write_register(cpu, register, value)
+            })),
+            // two hex digits per byte, longer reads are cut short
+            b'm' => match parse_range(args) {
+                Some((addr, len)) => (0..len.min(PACKET_SIZE / 2))
+                    .map(|i| format!("{:02x}", cpu.mem_peek(addr.wrapping_add(i as u16))))
+                    .collect(),
+                None => "E01".to_string(),
+            },
+            b'M' => reply_ok(write_memory(cpu, args)),
+            b's' | b'c' => {
+                if let Ok(addr) = u16::from_str_radix(args, 16) {
+                    cpu.program_counter = addr;
+                }
+                if command == b's' {
+                    stop_signal(self.debugger.run(Some(1))).to_string()
+                } else {
+                    self.resume(stream)?
+                }
+            }
+            b'Z' | b'z' => reply_ok(self.set_breakpoint(command == b'Z', args)),
+            b'D' => return Ok(("OK".to_string(), true)),
+            b'k' => return Ok((String::new(), true)),
+            b'H' => "OK".to_string(),
+            _ => match packet.split(':').next().unwrap_or("") {
+                "qSupported" => format!("PacketSize={:x}", PACKET_SIZE),
+                "qAttached" => "1".to_string(),
+                "QStartNoAckMode" => {
+                    self.no_ack = true;
+                    "OK".to_string()
+                }
+                _ => String::new(),
+            },
+        };
+
+        Ok((reply, false))
+    }
+
+    // continue until a breakpoint, BRK or an interrupt from the client
+    fn resume(&mut self, stream: &mut TcpStream) -> io::Result<String> {
+        let mut reason = self.debugger.run(Some(1));
+        while reason == StopReason::Stepped {
+            if interrupted(stream)? {
+                return Ok(SIGINT.to_string());
+            }
+            reason = self.debugger.run_more(CHUNK);
+        }
+        Ok(stop_signal(reason).to_string())
+    }
+
+    // type,addr,kind
+    fn set_breakpoint(&mut self, insert: bool, args: &str) -> Option<()> {
+        let mut fields = args.split(',');
+        let kind = fields.next()?;
+        let addr = u16::from_str_radix(fields.next()?, 16).ok()?;
+
+        let access = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.debugger.add_breakpoint(addr);
+                } else {
+                    self.debugger.remove_breakpoint(addr);
+                }
+                return Some(());
+            }
+            "2" => Access::WRITE,
+            "3" => Access::READ,
+            "4" => Access::READ | Access::WRITE,
+            _ => return None,
+        };
+
+        let len = u16::from_str_radix(fields.next().unwrap_or("1"), 16).ok()?;
+        let watchpoint = Watchpoint::new(addr, addr.wrapping_add(len.max(1) - 1), access);
+        let watchpoints = &mut self.debugger.nes.cpu.watchpoints;
+        if insert {
+            watchpoints.push(watchpoint);
+        } else {
+            watchpoints.retain(|w| *w != watchpoint);
+        }
+        Some(())
+    }
+}
+
+fn read_byte(stream: &mut TcpStream) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match stream.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+fn send_packet(stream: &mut TcpStream, data: &str) -> io::Result<()> {
+    write!(stream, "${}#{:02x}", data, checksum_of(data.as_bytes()))?;
+    stream.flush()
+}
+
+// a 0x03 byte sent while the target runs; a closed connection counts too
+fn interrupted(stream: &mut TcpStream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+    let result = match read_byte(stream) {
+        Ok(Some(byte)) => Ok(byte == INTERRUPT),
+        Ok(None) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    };
+    stream.set_nonblocking(false)?;
+    result
+}
+
+fn stop_signal(reason: StopReason) -> &'static str {
+    match reason {
+        StopReason::Unimplemented(_) => SIGILL,
+        _ => SIGTRAP,
+    }
+}
+
+fn reply_ok(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".to_string(),
+        None => "E01".to_string(),
+    }
+}
+
+fn hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// addr,len
+fn parse_range(args: &str) -> Option<(u16, usize)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        u16::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+fn read_registers(cpu: &CPU) -> String {
+    (0..6)
+        .map(|register| read_register(cpu, register))
+        .collect()
+}
+
+fn read_register(cpu: &CPU, register: usize) -> String {
+    match register {
+        0 => format!("{:02x}", cpu.register_a),
+        1 => format!("{:02x}", cpu.register_x),
+        2 => format!("{:02x}", cpu.register_y),
+        3 => format!("{:02x}", cpu.status.bits()),
+        4 => format!("{:02x}", cpu.stack_pointer),
+        5 => format!(
+            "{:02x}{:02x}",
+            cpu.program_counter & 0xFF,
+            cpu.program_counter >> 8
+        ),
+        _ => "E01".to_string(),
+    }
+}
+
+fn write_registers(cpu: &mut CPU, hex: &str) -> Option<()> {
+    let bytes = hex_bytes(hex)?;
+    if bytes.len() != 7 {
+        return None;
+    }
+    cpu.register_a = bytes[0];
+    cpu.register_x = bytes[1];
+    cpu.register_y = bytes[2];
+    cpu.status = CpuFlags::from_bits_truncate(bytes[3]);
+    cpu.stack_pointer = bytes[4];
+    cpu.program_counter = u16::from_le_bytes([bytes[5], bytes[6]]);
+    Some(())
+}
+
+fn write_register(cpu: &mut CPU, register: usize, hex: &str) -> Option<()> {
+    let bytes = hex_bytes(hex)?;
+    match (register, bytes.as_slice()) {
+        (0, [value]) => cpu.register_a = *value,
+        (1, [value]) => cpu.register_x = *value,
+        (2, [value]) => cpu.register_y = *value,
+        (3, [value]) => cpu.status = CpuFlags::from_bits_truncate(*value),
+        (4, [value]) => cpu.stack_pointer = *value,
+        (5, [lo, hi]) => cpu.program_counter = u16::from_le_bytes([*lo, *hi]),
+        _ => return None,
+    }
+    Some(())
+}
+
+// addr,len:XX...
+fn write_memory(cpu: &mut CPU, args: &str) -> Option<()> {
+    let (range, data) = args.split_once(':')?;
+    let (addr, len) = parse_range(range)?;
+    let bytes = hex_bytes(data)?;
+    if bytes.len() != len {
+        return None;
+    }
+    for (i, byte) in bytes.into_iter().enumerate() {
+        if !cpu.mem_poke(addr.wrapping_add(i as u16), byte) {
+            return None;
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod test;
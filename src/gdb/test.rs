@@ -0,0 +1,200 @@
+use std::thread;
+use std::thread::JoinHandle;
+
+use super::*;
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn send(&mut self, data: &str) {
+        send_packet(&mut self.stream, data).unwrap();
+        assert_eq!(self.read_byte(), b'+', "no ack for '{}'", data);
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        read_byte(&mut self.stream).unwrap().expect("stub hung up")
+    }
+
+    fn reply(&mut self) -> String {
+        while self.read_byte() != b'$' {}
+        let mut data = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => data.push(byte),
+            }
+        }
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        assert_eq!(
+            u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap(),
+            checksum_of(&data)
+        );
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    fn request(&mut self, data: &str) -> String {
+        self.send(data);
+        self.reply()
+    }
+}
+
+fn connect(program: &[u8]) -> (Client, JoinHandle<Debugger>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let debugger = Debugger::with_program(program, 0x8000).unwrap();
+
+    let server = thread::spawn(move || {
+        let mut stub = GdbStub::new(debugger);
+        stub.serve(&listener).unwrap();
+        stub.debugger
+    });
+    let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    (Client { stream }, server)
+}
+
+fn program() -> Vec<u8> {
+    crate::assemble!(
+        "       LDX #0",
+        "loop:  INX",
+        "       INC $0200",
+        "       CPX #3",
+        "       BNE loop",
+        "       BRK",
+    )
+}
+
+#[test]
+fn test_registers_and_memory() {
+    let (mut client, server) = connect(&program());
+
+    assert_eq!(client.request("qSupported:swbreak+"), "PacketSize=1000");
+    assert_eq!(client.request("?"), "S05");
+    assert_eq!(client.request("g"), "00000024fd0080");
+
+    assert_eq!(client.request("P0=42"), "OK");
+    assert_eq!(client.request("p0"), "42");
+    assert_eq!(client.request("P5=0280"), "OK");
+    assert_eq!(client.request("p5"), "0280");
+    assert_eq!(client.request("G01020324fd0080"), "OK");
+    assert_eq!(client.request("g"), "01020324fd0080");
+    assert_eq!(client.request("G0102"), "E01");
+
+    assert_eq!(client.request("m8000,3"), "a200e8");
+    assert_eq!(client.request("M0300,2:beef"), "OK");
+    assert_eq!(client.request("m0300,2"), "beef");
+    assert_eq!(client.request("M0300,2:be"), "E01");
+
+    assert_eq!(client.request("vMustReplyEmpty"), "");
+    assert_eq!(client.request("k"), "");
+
+    let debugger = server.join().unwrap();
+    assert_eq!(debugger.cpu().register_x, 2);
+    assert_eq!(debugger.cpu().mem_peek(0x0301), 0xEF);
+}
+
+#[test]
+fn test_step_breakpoint_continue() {
+    let (mut client, server) = connect(&program());
+
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p5"), "0280");
+
+    assert_eq!(client.request("Z0,8006,1"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p5"), "0680");
+    assert_eq!(client.request("p1"), "01");
+
+    // continuing from the breakpoint goes round the loop once more
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p1"), "02");
+    assert_eq!(client.request("m0200,1"), "02");
+
+    // without the breakpoint the program runs to BRK
+    assert_eq!(client.request("z0,8006,1"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p1"), "03");
+
+    assert_eq!(client.request("D"), "OK");
+    server.join().unwrap();
+}
+
+#[test]
+fn test_unimplemented_opcode_is_sigill() {
+    // INX; PHP
+    let (mut client, server) = connect(&[0xE8, 0x08]);
+
+    assert_eq!(client.request("c"), "S04");
+    assert_eq!(client.request("p5"), "0180");
+    assert_eq!(client.request("s"), "S04");
+
+    assert_eq!(client.request("k"), "");
+    server.join().unwrap();
+}
+
+#[test]
+fn test_watchpoint() {
+    let (mut client, server) = connect(&program());
+
+    assert_eq!(client.request("Z2,0200,1"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    // stopped after the instruction that wrote
+    assert_eq!(client.request("p5"), "0680");
+    assert_eq!(client.request("m0200,1"), "01");
+    assert_eq!(client.request("z2,0200,1"), "OK");
+
+    assert_eq!(client.request("k"), "");
+    let debugger = server.join().unwrap();
+    assert!(debugger.cpu().watchpoints.is_empty());
+}
+
+#[test]
+fn test_interrupt_and_no_ack() {
+    let program = crate::assemble!("loop: JMP loop");
+    let (mut client, server) = connect(&program);
+
+    assert_eq!(client.request("QStartNoAckMode"), "OK");
+    send_packet(&mut client.stream, "c").unwrap();
+    client.stream.write_all(&[INTERRUPT]).unwrap();
+    assert_eq!(client.reply(), "S02");
+
+    send_packet(&mut client.stream, "p5").unwrap();
+    assert_eq!(client.reply(), "0080");
+
+    send_packet(&mut client.stream, "k").unwrap();
+    server.join().unwrap();
+}
+
+#[test]
+fn test_malformed_packets() {
+    let (mut client, server) = connect(&program());
+
+    assert_eq!(client.request("\u{e9}1"), "");
+    assert_eq!(client.request("m0200"), "E01");
+    assert_eq!(client.request("m0200,fffffffffffffffffff"), "E01");
+    // reads longer than a packet come back short
+    assert_eq!(client.request("m0000,ffffffff").len(), PACKET_SIZE);
+    assert_eq!(client.request("p1"), "00");
+
+    assert_eq!(client.request("k"), "");
+    server.join().unwrap();
+}
+
+#[test]
+fn test_bad_checksum_is_nacked() {
+    let (mut client, server) = connect(&program());
+
+    client.stream.write_all(b"$g#00").unwrap();
+    assert_eq!(client.read_byte(), b'-');
+
+    // a nack from the client gets the last reply again
+    assert_eq!(client.request("p1"), "00");
+    client.stream.write_all(b"-").unwrap();
+    assert_eq!(client.reply(), "00");
+
+    assert_eq!(client.request("k"), "");
+    server.join().unwrap();
+}
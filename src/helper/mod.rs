@@ -1,4 +1,5 @@
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct OpCode {
     pub code: u8,
     pub code_name: &'static str,
@@ -9,6 +10,8 @@ pub struct OpCode {
 }
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(non_camel_case_types)]
 pub enum OpCodeCat {
     ASL,
     BCC,
@@ -51,7 +54,8 @@ pub enum OpCodeCat {
     NONE,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_camel_case_types)]
 pub enum AddressingMode {
     Accumulator,
@@ -70,6 +74,40 @@ pub enum AddressingMode {
     NoneAddressing,
 }
 
+// The derived impl would borrow `code_name` from the input, which only works
+// for 'static input, so the mnemonic is looked up in the opcode table.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for OpCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        struct Fields {
+            code: u8,
+            code_name: String,
+            match_code: OpCodeCat,
+            bytes: u16,
+            cycles: u8,
+            mode: AddressingMode,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        let code_name = (0..=0xFF)
+            .map(|code| EmmulationHelpers::get_op_code_struct(code).code_name)
+            .find(|mnemonic| *mnemonic == fields.code_name)
+            .ok_or_else(|| D::Error::custom(format!("unknown mnemonic '{}'", fields.code_name)))?;
+
+        Ok(OpCode {
+            code: fields.code,
+            code_name,
+            match_code: fields.match_code,
+            bytes: fields.bytes,
+            cycles: fields.cycles,
+            mode: fields.mode,
+        })
+    }
+}
+
 pub struct EmmulationHelpers {}
 
 impl EmmulationHelpers {
@@ -613,7 +651,7 @@ impl EmmulationHelpers {
                 mode: AddressingMode::ZeroPage,
             },
             0xD6 => OpCode {
-                code: 0xC6,
+                code: 0xD6,
                 code_name: "DEC",
                 match_code: OpCodeCat::DEC,
                 bytes: 2,
@@ -621,7 +659,7 @@ impl EmmulationHelpers {
                 mode: AddressingMode::ZeroPage_X,
             },
             0xCE => OpCode {
-                code: 0xC6,
+                code: 0xCE,
                 code_name: "DEC",
                 match_code: OpCodeCat::DEC,
                 bytes: 3,
@@ -629,7 +667,7 @@ impl EmmulationHelpers {
                 mode: AddressingMode::Absolute,
             },
             0xDE => OpCode {
-                code: 0xC6,
+                code: 0xDE,
                 code_name: "DEC",
                 match_code: OpCodeCat::DEC,
                 bytes: 3,
@@ -848,7 +886,7 @@ impl EmmulationHelpers {
                 match_code: OpCodeCat::INY,
                 bytes: 1,
                 cycles: 2,
-                mode: AddressingMode::Immediate,
+                mode: AddressingMode::Implied,
             },
 
             /* JMP Absolute opcodes */
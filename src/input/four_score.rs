@@ -0,0 +1,70 @@
+use crate::joypad::Joypad;
+
+// Signatures as shifted out on reads 17-24, least significant bit first.
+const SIGNATURE_PORT1: u8 = 0b0000_1000;
+const SIGNATURE_PORT2: u8 = 0b0000_0100;
+
+/// # Four Score multitap https://www.nesdev.org/wiki/Four_player_adapters
+///
+/// Each port of the Four Score serializes two controllers followed by an
+/// identifying signature, so one is plugged into each console port:
+/// port 1 carries players 1 and 3, port 2 carries players 2 and 4.
+///
+///  reads  1-8   first controller
+///  reads  9-16  second controller
+///  reads 17-24  signature
+///  reads 25+    1
+///
+pub struct FourScore {
+    pub pads: [Joypad; 2],
+    signature: u8,
+    strobe: bool,
+    read_index: u8,
+}
+
+impl FourScore {
+    pub fn port1() -> Self {
+        Self::with_signature(SIGNATURE_PORT1)
+    }
+
+    pub fn port2() -> Self {
+        Self::with_signature(SIGNATURE_PORT2)
+    }
+
+    fn with_signature(signature: u8) -> Self {
+        FourScore {
+            pads: [Joypad::new(), Joypad::new()],
+            signature,
+            strobe: false,
+            read_index: 0,
+        }
+    }
+
+    pub fn write(&mut self, data: u8) {
+        self.strobe = data & 1 == 1;
+        if self.strobe {
+            self.read_index = 0;
+        }
+    }
+
+    pub fn read(&mut self) -> u8 {
+        let response = self.peek();
+
+        if !self.strobe && self.read_index < 24 {
+            self.read_index += 1;
+        }
+
+        response
+    }
+
+    pub fn peek(&self) -> u8 {
+        let bit = self.read_index % 8;
+
+        match self.read_index / 8 {
+            0 => (self.pads[0].buttons().bits() >> bit) & 1,
+            1 => (self.pads[1].buttons().bits() >> bit) & 1,
+            2 => (self.signature >> bit) & 1,
+            _ => 1,
+        }
+    }
+}
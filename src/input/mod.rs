@@ -0,0 +1,78 @@
+pub mod four_score;
+pub mod vaus;
+pub mod zapper;
+
+use crate::frame::Frame;
+use crate::joypad::Joypad;
+
+pub use four_score::FourScore;
+pub use vaus::Vaus;
+pub use zapper::Zapper;
+
+/// Whatever is plugged into one of the $4016/$4017 controller ports.
+///
+/// Devices only drive the low five data bits; open bus on the upper bits
+/// is left to the CPU.
+pub enum InputDevice {
+    Disconnected,
+    Joypad(Joypad),
+    Zapper(Zapper),
+    FourScore(FourScore),
+    Vaus(Vaus),
+}
+
+impl InputDevice {
+    /// Handles a $4016 write, which is wired to both ports.
+    pub fn write(&mut self, data: u8) {
+        match self {
+            InputDevice::Disconnected => {}
+            InputDevice::Joypad(joypad) => joypad.write(data),
+            InputDevice::Zapper(_) => {}
+            InputDevice::FourScore(four_score) => four_score.write(data),
+            InputDevice::Vaus(vaus) => vaus.write(data),
+        }
+    }
+
+    pub fn read(&mut self) -> u8 {
+        match self {
+            InputDevice::Disconnected => 0,
+            InputDevice::Joypad(joypad) => joypad.read(),
+            InputDevice::Zapper(zapper) => zapper.read(),
+            InputDevice::FourScore(four_score) => four_score.read(),
+            InputDevice::Vaus(vaus) => vaus.read(),
+        }
+    }
+
+    /// Same as `read` but without advancing any shift register.
+    pub fn peek(&self) -> u8 {
+        match self {
+            InputDevice::Disconnected => 0,
+            InputDevice::Joypad(joypad) => joypad.peek(),
+            InputDevice::Zapper(zapper) => zapper.read(),
+            InputDevice::FourScore(four_score) => four_score.peek(),
+            InputDevice::Vaus(vaus) => vaus.peek(),
+        }
+    }
+
+    /// Controller in the given slot of this port: slot 0 is the controller
+    /// plugged in directly (or the Four Score's first), slot 1 only exists
+    /// on a Four Score.
+    pub fn joypad_mut(&mut self, slot: usize) -> Option<&mut Joypad> {
+        match (self, slot) {
+            (InputDevice::Joypad(joypad), 0) => Some(joypad),
+            (InputDevice::FourScore(four_score), 0..=1) => Some(&mut four_score.pads[slot]),
+            _ => None,
+        }
+    }
+
+    /// Lets a light gun look at the frame the PPU just finished. Other
+    /// devices ignore the picture.
+    pub fn sense_light(&mut self, frame: &Frame) {
+        if let InputDevice::Zapper(zapper) = self {
+            zapper.sense_light(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,75 @@
+use super::*;
+use crate::frame::Frame;
+use crate::joypad::JoypadButton;
+
+#[test]
+fn test_zapper_light_sense() {
+    let mut frame = Frame::new();
+    frame.set_pixel(50, 100, 0x30);
+
+    let mut zapper = Zapper::new();
+    zapper.aim(50, 100);
+    zapper.sense_light(&frame);
+    assert!(zapper.light_sensed());
+    assert_eq!(zapper.read(), 0b0000_0000);
+
+    zapper.aim(51, 100);
+    zapper.sense_light(&frame);
+    assert!(!zapper.light_sensed());
+    assert_eq!(zapper.read(), 0b0000_1000);
+
+    zapper.aim(300, 100);
+    zapper.sense_light(&frame);
+    assert!(!zapper.light_sensed());
+}
+
+#[test]
+fn test_zapper_trigger() {
+    let mut zapper = Zapper::new();
+    zapper.trigger = true;
+
+    assert_eq!(zapper.read(), 0b0001_1000);
+}
+
+#[test]
+fn test_four_score_serial_order() {
+    let mut four_score = FourScore::port2();
+    four_score.pads[0].set_buttons(JoypadButton::START);
+    four_score.pads[1].set_buttons(JoypadButton::RIGHT);
+    four_score.write(1);
+    four_score.write(0);
+
+    let bits: Vec<u8> = (0..26).map(|_| four_score.read()).collect();
+
+    assert_eq!(
+        bits,
+        vec![
+            0, 0, 0, 1, 0, 0, 0, 0, // player 2
+            0, 0, 0, 0, 0, 0, 0, 1, // player 4
+            0, 0, 1, 0, 0, 0, 0, 0, // signature
+            1, 1,
+        ]
+    );
+}
+
+#[test]
+fn test_vaus_serial_position() {
+    let mut vaus = Vaus::new();
+    vaus.position = 0b1010_0000;
+    vaus.fire = true;
+    vaus.write(1);
+    vaus.write(0);
+
+    let bits: Vec<u8> = (0..8).map(|_| vaus.read()).collect();
+
+    assert_eq!(bits, vec![0x08, 0x18, 0x08, 0x18, 0x18, 0x18, 0x18, 0x18]);
+}
+
+#[test]
+fn test_disconnected_port() {
+    let mut port = InputDevice::Disconnected;
+    port.write(1);
+
+    assert_eq!(port.read(), 0);
+    assert!(port.joypad_mut(0).is_none());
+}
@@ -0,0 +1,51 @@
+/// # Arkanoid "Vaus" paddle https://www.nesdev.org/wiki/Arkanoid_controller
+///
+///  4 3
+///  D F
+///  | +--- Fire button (1: pressed)
+///  +----- Potentiometer, serial, inverted, most significant bit first
+///
+/// Writing 1 to $4016 latches the knob position into the shift register.
+pub struct Vaus {
+    pub position: u8,
+    pub fire: bool,
+    shift: u8,
+}
+
+impl Default for Vaus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vaus {
+    pub fn new() -> Self {
+        Vaus {
+            position: 0,
+            fire: false,
+            shift: 0,
+        }
+    }
+
+    pub fn write(&mut self, data: u8) {
+        if data & 1 == 1 {
+            self.shift = !self.position;
+        }
+    }
+
+    pub fn read(&mut self) -> u8 {
+        let response = self.peek();
+        self.shift <<= 1;
+        response
+    }
+
+    pub fn peek(&self) -> u8 {
+        let mut result = ((self.shift >> 7) & 1) << 4;
+
+        if self.fire {
+            result |= 0b0000_1000;
+        }
+
+        result
+    }
+}
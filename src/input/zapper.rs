@@ -0,0 +1,74 @@
+use crate::frame::Frame;
+
+// Sum of the RGB channels a pixel needs before the photodiode reacts,
+// roughly "white-ish" as drawn by light gun games on their hit frames.
+const LIGHT_THRESHOLD: u16 = 0x200;
+
+/// # Zapper light gun https://www.nesdev.org/wiki/Zapper
+///
+///  4 3
+///  T L
+///  | +--- Light sense (0: light detected, 1: no light)
+///  +----- Trigger (1: pulled)
+///
+pub struct Zapper {
+    pub x: usize,
+    pub y: usize,
+    pub trigger: bool,
+    light_sensed: bool,
+}
+
+impl Default for Zapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Zapper {
+    pub fn new() -> Self {
+        Zapper {
+            x: 0,
+            y: 0,
+            trigger: false,
+            light_sensed: false,
+        }
+    }
+
+    pub fn aim(&mut self, x: usize, y: usize) {
+        self.x = x;
+        self.y = y;
+    }
+
+    pub fn light_sensed(&self) -> bool {
+        self.light_sensed
+    }
+
+    pub fn set_light_sensed(&mut self, sensed: bool) {
+        self.light_sensed = sensed;
+    }
+
+    /// Updates the light sensor by looking at the pixel the gun is aimed
+    /// at in the finished frame. Aiming off screen never sees light.
+    pub fn sense_light(&mut self, frame: &Frame) {
+        self.light_sensed = if self.x < Frame::WIDTH && self.y < Frame::HEIGHT {
+            let (r, g, b) = frame.rgb(self.x, self.y);
+            r as u16 + g as u16 + b as u16 >= LIGHT_THRESHOLD
+        } else {
+            false
+        };
+    }
+
+    pub fn read(&self) -> u8 {
+        let mut result = 0;
+
+        if !self.light_sensed {
+            result |= 0b0000_1000;
+        }
+
+        if self.trigger {
+            result |= 0b0001_0000;
+        }
+
+        result
+    }
+}
@@ -0,0 +1,100 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// # Standard controller buttons https://www.nesdev.org/wiki/Standard_controller
+    ///
+    ///  7 6 5 4 3 2 1 0
+    ///  R L D U T S B A
+    ///  | | | | | | | +--- A
+    ///  | | | | | | +----- B
+    ///  | | | | | +------- Select
+    ///  | | | | +--------- Start
+    ///  | | | +----------- Up
+    ///  | | +------------- Down
+    ///  | +--------------- Left
+    ///  +----------------- Right
+    ///
+    /// Bit order matches the order buttons are shifted out of $4016/$4017.
+    pub struct JoypadButton: u8 {
+        const BUTTON_A = 0b00000001;
+        const BUTTON_B = 0b00000010;
+        const SELECT   = 0b00000100;
+        const START    = 0b00001000;
+        const UP       = 0b00010000;
+        const DOWN     = 0b00100000;
+        const LEFT     = 0b01000000;
+        const RIGHT    = 0b10000000;
+    }
+}
+
+pub struct Joypad {
+    strobe: bool,
+    button_index: u8,
+    button_status: JoypadButton,
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Joypad {
+            strobe: false,
+            button_index: 0,
+            button_status: JoypadButton::empty(),
+        }
+    }
+
+    /// Bit 0 of a $4016 write is the strobe latch. While it is high the
+    /// shift register keeps reloading, so reads always report button A.
+    pub fn write(&mut self, data: u8) {
+        self.strobe = data & 1 == 1;
+        if self.strobe {
+            self.button_index = 0;
+        }
+    }
+
+    /// Shifts out the next button in the serial order. After all eight
+    /// buttons have been read an official controller reports 1.
+    pub fn read(&mut self) -> u8 {
+        if self.button_index > 7 {
+            return 1;
+        }
+
+        let response = (self.button_status.bits() >> self.button_index) & 1;
+
+        if !self.strobe {
+            self.button_index += 1;
+        }
+
+        response
+    }
+
+    /// Same as `read` but leaves the shift register untouched.
+    pub fn peek(&self) -> u8 {
+        if self.button_index > 7 {
+            return 1;
+        }
+
+        (self.button_status.bits() >> self.button_index) & 1
+    }
+
+    pub fn buttons(&self) -> JoypadButton {
+        self.button_status
+    }
+
+    /// Replaces the whole button state, e.g. once per frame from the host.
+    pub fn set_buttons(&mut self, buttons: JoypadButton) {
+        self.button_status = buttons;
+    }
+
+    pub fn set_button_pressed_status(&mut self, button: JoypadButton, pressed: bool) {
+        self.button_status.set(button, pressed);
+    }
+}
+
+#[cfg(test)]
+mod test;
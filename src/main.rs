@@ -1,6 +1,32 @@
+pub mod apu;
+pub mod asm;
+pub mod cartridge;
+pub mod cdl;
+pub mod cheat;
+pub mod cli;
 pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+pub mod frame;
+pub mod gdb;
 pub mod helper;
+pub mod input;
+pub mod joypad;
+pub mod movie;
+pub mod nes;
+pub mod ppu;
+pub mod profiler;
+pub mod terminal;
+pub mod trace;
+
+use std::env;
+use std::process;
 
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(error) = cli::run(&args) {
+        eprintln!("{}", error);
+        process::exit(error.code);
+    }
 }
@@ -0,0 +1,174 @@
+//! # FCEUX movie files https://fceux.com/web/FM2.html
+//!
+//! A text header of `key value` lines followed by one line per frame:
+//!
+//! ```text
+//! version 3
+//! romFilename game
+//! port0 1
+//! port1 1
+//! port2 0
+//! |0|R.D.T..A|........||
+//! ```
+//!
+//! The first field is the commands, then one field per port with the
+//! buttons in `RLDUTSBA` order, any character but `.` and space meaning
+//! held. With `fourscore 1` the ports are replaced by four controllers.
+
+use super::Commands;
+use super::Movie;
+use super::MovieFrame;
+use crate::joypad::JoypadButton;
+
+// highest bit first, as in JoypadButton
+const BUTTONS: &[u8; 8] = b"RLDUTSBA";
+
+fn parse_buttons(field: &str) -> Result<JoypadButton, String> {
+    if field.is_empty() {
+        return Ok(JoypadButton::empty());
+    }
+    if field.len() != BUTTONS.len() {
+        return Err(format!(
+            "expected {} button columns, found '{}'",
+            BUTTONS.len(),
+            field
+        ));
+    }
+    let bits = field
+        .bytes()
+        .fold(0u8, |bits, c| (bits << 1) | (c != b'.' && c != b' ') as u8);
+    Ok(JoypadButton::from_bits_truncate(bits))
+}
+
+fn format_buttons(buttons: JoypadButton) -> String {
+    BUTTONS
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            if buttons.bits() & (0x80 >> i) != 0 {
+                c as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+fn flag(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(format!("{} should be 0 or 1, found '{}'", key, value)),
+    }
+}
+
+impl Movie {
+    /// Reads an FM2 movie. Only gamepads are supported, and FDS and VS
+    /// System commands are dropped.
+    pub fn from_fm2(text: &str) -> Result<Self, String> {
+        let mut movie = Movie::new(0);
+        let mut version = None;
+        let mut fourscore = false;
+        let mut ports = [false; 2];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            let at = |e: String| format!("line {}: {}", number + 1, e);
+
+            if let Some(fields) = line.strip_prefix('|') {
+                let mut fields = fields.split('|');
+                let commands = fields.next().unwrap_or_default();
+                let commands: u8 = commands
+                    .trim()
+                    .parse()
+                    .map_err(|_| at(format!("'{}' is not a command number", commands)))?;
+
+                let mut frame = MovieFrame {
+                    commands: Commands::from_bits_truncate(commands),
+                    ..MovieFrame::default()
+                };
+                let pads = if fourscore { 4 } else { 2 };
+                for (buttons, field) in frame.buttons.iter_mut().zip(fields.take(pads)) {
+                    *buttons = parse_buttons(field).map_err(at)?;
+                }
+                movie.frames.push(frame);
+                continue;
+            }
+
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "version" => version = Some(value.to_string()),
+                "binary" if flag(key, value).map_err(at)? => {
+                    return Err("binary FM2 input is not supported".to_string())
+                }
+                "binary" => {}
+                "palFlag" => movie.pal = flag(key, value).map_err(at)?,
+                "fourscore" => fourscore = flag(key, value).map_err(at)?,
+                "port0" | "port1" => {
+                    let port = (key == "port1") as usize;
+                    ports[port] = match value {
+                        "0" => false,
+                        "1" => true,
+                        _ => {
+                            return Err(at(format!(
+                                "{} device {} is not supported, only gamepads are",
+                                key, value
+                            )))
+                        }
+                    };
+                }
+                "port2" => {}
+                _ => movie.header.push((key.to_string(), value.to_string())),
+            }
+        }
+
+        match version.as_deref() {
+            Some("3") => {}
+            Some(v) => return Err(format!("FM2 version {} is not supported", v)),
+            None => return Err("not an FM2 movie, the version line is missing".to_string()),
+        }
+        movie.players = match (fourscore, ports) {
+            (true, _) => 4,
+            (false, [_, true]) => 2,
+            (false, [true, false]) => 1,
+            (false, [false, false]) => 0,
+        };
+        Ok(movie)
+    }
+
+    pub fn to_fm2(&self) -> String {
+        let fourscore = self.players > 2;
+        let mut text = String::from("version 3\n");
+        for (key, value) in &self.header {
+            text.push_str(&format!("{} {}\n", key, value));
+        }
+        text.push_str(&format!("palFlag {}\n", self.pal as u8));
+        text.push_str(&format!("fourscore {}\n", fourscore as u8));
+        text.push_str(&format!(
+            "port0 {}\n",
+            (!fourscore && self.players >= 1) as u8
+        ));
+        text.push_str(&format!(
+            "port1 {}\n",
+            (!fourscore && self.players >= 2) as u8
+        ));
+        text.push_str("port2 0\n");
+
+        let pads = if fourscore { 4 } else { 2 };
+        for frame in &self.frames {
+            text.push_str(&format!("|{}|", frame.commands.bits()));
+            for (player, &buttons) in frame.buttons[..pads].iter().enumerate() {
+                if player < self.players {
+                    text.push_str(&format_buttons(buttons));
+                }
+                text.push('|');
+            }
+            // the expansion port
+            text.push_str("|\n");
+        }
+        text
+    }
+}
@@ -0,0 +1,260 @@
+pub mod fm2;
+
+use std::fs;
+use std::path::Path;
+
+use bitflags::bitflags;
+
+use crate::frame::Frame;
+use crate::input::FourScore;
+use crate::input::InputDevice;
+use crate::joypad::Joypad;
+use crate::joypad::JoypadButton;
+use crate::nes::region::Region;
+use crate::nes::Nes;
+
+const MAGIC: [u8; 4] = *b"NESM";
+
+/// # Native movie format, all numbers little endian
+///
+/// ```text
+///  offset  size
+///       0     4  "NESM"
+///       4     1  version, 1
+///       5     1  players, 0-4
+///       6     1  options, bit 0 = PAL
+///       7     4  number of frames
+///      11   ...  per frame the commands byte, then one byte of buttons
+///                per player in `JoypadButton` order
+/// ```
+///
+/// The FM2 header lines kept in `Movie::header` are not stored.
+pub const MOVIE_VERSION: u8 = 1;
+
+bitflags! {
+    /// Console buttons pressed at the start of a frame, numbered as in FM2.
+    pub struct Commands: u8 {
+        const RESET = 0b01;
+        const POWER = 0b10;
+    }
+}
+
+/// Input for one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieFrame {
+    pub commands: Commands,
+    /// Buttons players 1-4 hold throughout the frame.
+    pub buttons: [JoypadButton; 4],
+}
+
+impl Default for MovieFrame {
+    fn default() -> Self {
+        MovieFrame {
+            commands: Commands::empty(),
+            buttons: [JoypadButton::empty(); 4],
+        }
+    }
+}
+
+/// Controller input recorded frame by frame, replayed from power on.
+///
+/// The console is deterministic, so playing a movie back on the same ROM
+/// ends in exactly the state it was recorded in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    /// Controllers recorded, more than two need a Four Score.
+    pub players: usize,
+    pub pal: bool,
+    /// FM2 header lines this emulator has no use for, e.g. `romFilename`,
+    /// kept in order so they are written back.
+    pub header: Vec<(String, String)>,
+    pub frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    pub fn new(players: usize) -> Self {
+        Movie {
+            players: players.min(4),
+            pal: false,
+            header: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Value of an FM2 header line kept in `header`.
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.header
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Plugs in the controllers the movie needs, switches to PAL timing
+    /// when it was recorded on PAL and powers the console on, which is
+    /// where every movie starts. Call before the first `record`.
+    pub fn begin(&self, nes: &mut Nes) {
+        if self.pal {
+            nes.set_region(Region::Pal);
+        }
+        let cpu = &mut nes.cpu;
+        if self.players > 2 {
+            if !matches!(cpu.port1, InputDevice::FourScore(_)) {
+                cpu.port1 = InputDevice::FourScore(FourScore::port1());
+            }
+            if !matches!(cpu.port2, InputDevice::FourScore(_)) {
+                cpu.port2 = InputDevice::FourScore(FourScore::port2());
+            }
+        } else {
+            for port in [&mut cpu.port1, &mut cpu.port2]
+                .into_iter()
+                .take(self.players)
+            {
+                if !matches!(port, InputDevice::Joypad(_)) {
+                    *port = InputDevice::Joypad(Joypad::new());
+                }
+            }
+        }
+        nes.power_on();
+    }
+
+    fn apply(&self, nes: &mut Nes, frame: &MovieFrame) -> Result<(), String> {
+        if frame.commands.contains(Commands::POWER) {
+            nes.power_on();
+        } else if frame.commands.contains(Commands::RESET) {
+            nes.reset();
+        }
+        for player in 1..=self.players {
+            nes.set_buttons(player, frame.buttons[player - 1])?;
+        }
+        Ok(())
+    }
+
+    /// Runs one frame with the given input and appends it to the movie,
+    /// which takes the console's region along.
+    pub fn record<'a>(
+        &mut self,
+        nes: &'a mut Nes,
+        frame: MovieFrame,
+    ) -> Result<(&'a Frame, Vec<f32>), String> {
+        self.apply(nes, &frame)?;
+        self.pal = nes.region() == Region::Pal;
+        self.frames.push(frame);
+        Ok(nes.run_frame())
+    }
+
+    /// Runs frame `index` of the movie, for hosts that show playback.
+    /// Start with `begin`.
+    pub fn play_frame<'a>(
+        &self,
+        nes: &'a mut Nes,
+        index: usize,
+    ) -> Result<(&'a Frame, Vec<f32>), String> {
+        let frame = self.frames.get(index).ok_or(format!(
+            "movie has {} frames, there is no frame {}",
+            self.frames.len(),
+            index
+        ))?;
+        self.apply(nes, frame)?;
+        Ok(nes.run_frame())
+    }
+
+    /// Replays the whole movie from power on.
+    pub fn play(&self, nes: &mut Nes) -> Result<(), String> {
+        self.begin(nes);
+        for index in 0..self.frames.len() {
+            self.play_frame(nes, index)?;
+        }
+        Ok(())
+    }
+
+    /// The movie in the native format, see `MOVIE_VERSION`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(11 + self.frames.len() * (1 + self.players));
+        data.extend_from_slice(&MAGIC);
+        data.push(MOVIE_VERSION);
+        data.push(self.players as u8);
+        data.push(self.pal as u8);
+        data.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for frame in &self.frames {
+            data.push(frame.commands.bits());
+            data.extend(frame.buttons[..self.players].iter().map(|b| b.bits()));
+        }
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 11 || data[..4] != MAGIC {
+            return Err("not a movie file".to_string());
+        }
+        if data[4] != MOVIE_VERSION {
+            return Err(format!(
+                "movie version {} is not supported, this build reads version {}",
+                data[4], MOVIE_VERSION
+            ));
+        }
+        let players = data[5] as usize;
+        if players > 4 {
+            return Err(format!(
+                "movie has {} players, at most 4 are supported",
+                players
+            ));
+        }
+
+        let mut movie = Movie::new(players);
+        movie.pal = data[6] & 1 != 0;
+        let count = u32::from_le_bytes([data[7], data[8], data[9], data[10]]) as usize;
+        let records = &data[11..];
+        if records.len() != count * (1 + players) {
+            return Err(format!(
+                "movie should have {} bytes of input for {} frames, found {}",
+                count * (1 + players),
+                count,
+                records.len()
+            ));
+        }
+        for record in records.chunks(1 + players) {
+            let mut frame = MovieFrame {
+                commands: Commands::from_bits_truncate(record[0]),
+                ..MovieFrame::default()
+            };
+            for (buttons, &bits) in frame.buttons.iter_mut().zip(&record[1..]) {
+                *buttons = JoypadButton::from_bits_truncate(bits);
+            }
+            movie.frames.push(frame);
+        }
+        Ok(movie)
+    }
+
+    /// Reads a movie, FM2 when the file name ends in `.fm2` and the native
+    /// format otherwise.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        if is_fm2(path) {
+            let text = String::from_utf8(data)
+                .map_err(|_| format!("{} is not a text file", path.display()))?;
+            Self::from_fm2(&text)
+        } else {
+            Self::from_bytes(&data)
+        }
+    }
+
+    /// Writes the movie in the format `load` picks for the file name.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let data = if is_fm2(path) {
+            self.to_fm2().into_bytes()
+        } else {
+            self.to_bytes()
+        };
+        fs::write(path, data).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+    }
+}
+
+fn is_fm2(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("fm2"))
+}
+
+#[cfg(test)]
+mod test;
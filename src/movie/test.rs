@@ -0,0 +1,243 @@
+use super::*;
+use crate::asm;
+use crate::cartridge::test_rom::test_rom;
+use crate::nes::region::Region;
+
+// counts in $0300-$0307 and $0308-$030F how often each button of players
+// 1 and 2 was seen held, and in $0310 how often the pads were polled
+fn button_counter_rom() -> Vec<u8> {
+    let program = asm::assemble(
+        "        .org $C000
+         poll:   LDA #1
+                 STA $4016
+                 LDA #0
+                 STA $4016
+                 LDX #0
+         read:   LDA $4016
+                 AND #1
+                 BEQ second
+                 INC $0300,X
+         second: LDA $4017
+                 AND #1
+                 BEQ next
+                 INC $0308,X
+         next:   INX
+                 CPX #8
+                 BNE read
+                 INC $0310
+                 JMP poll",
+    )
+    .unwrap();
+
+    test_rom(&program.bytes, 0xC000, 0xC000, false)
+}
+
+fn frame(commands: Commands, p1: JoypadButton, p2: JoypadButton) -> MovieFrame {
+    MovieFrame {
+        commands,
+        buttons: [p1, p2, JoypadButton::empty(), JoypadButton::empty()],
+    }
+}
+
+fn recorded() -> (Movie, u64) {
+    recorded_in(Region::Ntsc)
+}
+
+fn recorded_in(region: Region) -> (Movie, u64) {
+    let mut nes = Nes::new();
+    nes.set_region(region);
+    nes.load_rom(&button_counter_rom()).unwrap();
+
+    let mut movie = Movie::new(2);
+    movie.begin(&mut nes);
+    let none = Commands::empty();
+    let inputs = [
+        frame(none, JoypadButton::RIGHT, JoypadButton::empty()),
+        frame(
+            none,
+            JoypadButton::RIGHT | JoypadButton::BUTTON_A,
+            JoypadButton::START,
+        ),
+        frame(Commands::RESET, JoypadButton::empty(), JoypadButton::UP),
+        frame(
+            none,
+            JoypadButton::LEFT | JoypadButton::BUTTON_B,
+            JoypadButton::UP,
+        ),
+        frame(Commands::POWER, JoypadButton::SELECT, JoypadButton::empty()),
+        frame(none, JoypadButton::DOWN, JoypadButton::BUTTON_A),
+    ];
+    for input in inputs {
+        movie.record(&mut nes, input).unwrap();
+    }
+    (movie, nes.cpu.memory_hash())
+}
+
+#[test]
+fn test_replay_matches_recording() {
+    let (movie, hash) = recorded();
+
+    let mut nes = Nes::new();
+    nes.load_rom(&button_counter_rom()).unwrap();
+    // whatever ran before playback does not matter
+    nes.set_buttons(1, JoypadButton::START).unwrap();
+    nes.run_frame();
+
+    movie.play(&mut nes).unwrap();
+    assert_eq!(nes.cpu.memory_hash(), hash);
+
+    // power on cleared the counts of the frames before it
+    let polls = nes.cpu.mem_peek(0x0310);
+    let down = nes.cpu.mem_peek(0x0305);
+    assert!(down > 0);
+    assert_eq!(nes.cpu.mem_peek(0x0302) + down, polls);
+    assert_eq!(nes.cpu.mem_peek(0x0308), down);
+    assert_eq!(nes.cpu.mem_peek(0x0300), 0);
+}
+
+#[test]
+fn test_different_input_diverges() {
+    let (mut movie, hash) = recorded();
+    movie.frames[5].buttons[0] = JoypadButton::UP;
+
+    let mut nes = Nes::new();
+    nes.load_rom(&button_counter_rom()).unwrap();
+    movie.play(&mut nes).unwrap();
+    assert_ne!(nes.cpu.memory_hash(), hash);
+}
+
+#[test]
+fn test_pal_round_trip() {
+    let (movie, hash) = recorded_in(Region::Pal);
+    assert!(movie.pal);
+
+    let read = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(read, movie);
+
+    let mut nes = Nes::new();
+    nes.load_rom(&button_counter_rom()).unwrap();
+    read.play(&mut nes).unwrap();
+    assert_eq!(nes.region(), Region::Pal);
+    assert_eq!(nes.cpu.memory_hash(), hash);
+
+    // the same input at NTSC speed polls a different number of times
+    let (ntsc, ntsc_hash) = recorded();
+    assert!(!ntsc.pal);
+    assert_ne!(ntsc_hash, hash);
+}
+
+#[test]
+fn test_fm2_round_trip() {
+    let (mut movie, hash) = recorded();
+    movie
+        .header
+        .push(("romFilename".to_string(), "counter".to_string()));
+
+    let text = movie.to_fm2();
+    assert!(text.starts_with("version 3\nromFilename counter\n"));
+    assert!(text.contains("\n|0|R......A|....T...||\n|1|........|...U....||\n"));
+
+    let read = Movie::from_fm2(&text).unwrap();
+    assert_eq!(read, movie);
+    assert_eq!(read.header("romFilename"), Some("counter"));
+
+    let mut nes = Nes::new();
+    nes.load_rom(&button_counter_rom()).unwrap();
+    read.play(&mut nes).unwrap();
+    assert_eq!(nes.cpu.memory_hash(), hash);
+}
+
+#[test]
+fn test_fm2_from_fceux() {
+    let text = "version 3\r\n\
+                emuVersion 22020\r\n\
+                rerecordCount 4\r\n\
+                palFlag 0\r\n\
+                romFilename smb\r\n\
+                guid 452DE2C3-EF43-2FA9-77AC-0677FC51543B\r\n\
+                fourscore 0\r\n\
+                port0 1\r\n\
+                port1 0\r\n\
+                port2 0\r\n\
+                comment author someone\r\n\
+                |2|........|||\r\n\
+                |0|....T...|||\r\n\
+                |0|R  U  BA|||\r\n";
+
+    let movie = Movie::from_fm2(text).unwrap();
+    assert_eq!(movie.players, 1);
+    assert_eq!(movie.header("comment"), Some("author someone"));
+    assert_eq!(movie.header("rerecordCount"), Some("4"));
+    assert_eq!(movie.frames.len(), 3);
+    assert_eq!(movie.frames[0].commands, Commands::POWER);
+    assert_eq!(movie.frames[1].buttons[0], JoypadButton::START);
+    assert_eq!(
+        movie.frames[2].buttons[0],
+        JoypadButton::RIGHT | JoypadButton::UP | JoypadButton::BUTTON_B | JoypadButton::BUTTON_A
+    );
+}
+
+#[test]
+fn test_fm2_errors() {
+    assert_eq!(
+        Movie::from_fm2("version 2\n"),
+        Err("FM2 version 2 is not supported".to_string())
+    );
+    assert_eq!(
+        Movie::from_fm2("version 3\nport0 2\n"),
+        Err("line 2: port0 device 2 is not supported, only gamepads are".to_string())
+    );
+    assert_eq!(
+        Movie::from_fm2("version 3\nport0 1\n|0|RLDU|||\n"),
+        Err("line 3: expected 8 button columns, found 'RLDU'".to_string())
+    );
+    assert_eq!(
+        Movie::from_fm2("version 3\nbinary 1\n"),
+        Err("binary FM2 input is not supported".to_string())
+    );
+    assert!(Movie::from_fm2("|0|........|||\n").is_err());
+}
+
+#[test]
+fn test_four_score_fm2() {
+    let mut movie = Movie::new(4);
+    movie.frames.push(MovieFrame {
+        commands: Commands::empty(),
+        buttons: [
+            JoypadButton::BUTTON_A,
+            JoypadButton::BUTTON_B,
+            JoypadButton::SELECT,
+            JoypadButton::START,
+        ],
+    });
+
+    let text = movie.to_fm2();
+    assert!(text.contains("fourscore 1\n"));
+    assert!(text.ends_with("|0|.......A|......B.|.....S..|....T...||\n"));
+    assert_eq!(Movie::from_fm2(&text).unwrap(), movie);
+
+    let mut nes = Nes::new();
+    nes.load_rom(&button_counter_rom()).unwrap();
+    movie.play(&mut nes).unwrap();
+    assert!(matches!(nes.cpu.port1, InputDevice::FourScore(_)));
+    assert_eq!(nes.buttons(4), Some(JoypadButton::START));
+}
+
+#[test]
+fn test_native_round_trip() {
+    let (movie, _) = recorded();
+
+    let bytes = movie.to_bytes();
+    assert_eq!(&bytes[..4], b"NESM");
+    assert_eq!(bytes.len(), 11 + 6 * 3);
+    assert_eq!(Movie::from_bytes(&bytes), Ok(movie));
+
+    assert_eq!(
+        Movie::from_bytes(&bytes[..bytes.len() - 1]),
+        Err("movie should have 18 bytes of input for 6 frames, found 17".to_string())
+    );
+    assert_eq!(
+        Movie::from_bytes(b"version 3\n"),
+        Err("not a movie file".to_string())
+    );
+}
@@ -0,0 +1,92 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::cpu::CPU;
+
+/// Where battery backed cartridges keep their work RAM.
+pub const BATTERY_RAM: RangeInclusive<u16> = 0x6000..=0x7FFF;
+
+/// CPU memory that survives power cycles, kept in a save file.
+///
+/// The bytes themselves stay in the CPU's memory array, so games, save
+/// states and the debugger see them like any other RAM; this only tracks
+/// which range to persist and what the file last held.
+pub struct Battery {
+    pub path: PathBuf,
+    pub range: RangeInclusive<u16>,
+    saved: Vec<u8>,
+}
+
+impl Battery {
+    pub fn new<P: AsRef<Path>>(path: P, range: RangeInclusive<u16>) -> Self {
+        Battery {
+            path: path.as_ref().to_path_buf(),
+            range,
+            saved: Vec::new(),
+        }
+    }
+
+    /// The save file that goes with a ROM, `game.nes` keeps `game.sav`.
+    pub fn for_rom<P: AsRef<Path>>(rom_path: P) -> Self {
+        Self::new(rom_path.as_ref().with_extension("sav"), BATTERY_RAM)
+    }
+
+    fn len(&self) -> usize {
+        (*self.range.end() as usize + 1).saturating_sub(*self.range.start() as usize)
+    }
+
+    fn read(&self, cpu: &CPU) -> Vec<u8> {
+        self.range.clone().map(|addr| cpu.mem_peek(addr)).collect()
+    }
+
+    fn write(&self, cpu: &mut CPU, data: &[u8]) {
+        for (addr, &byte) in self.range.clone().zip(data) {
+            cpu.mem_poke(addr, byte);
+        }
+    }
+
+    /// Copies the save file into memory. A missing file is a fresh
+    /// cartridge and leaves memory as it is.
+    pub fn load(&mut self, cpu: &mut CPU) -> Result<(), String> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                self.saved = self.read(cpu);
+                return Ok(());
+            }
+            Err(e) => return Err(format!("cannot read {}: {}", self.path.display(), e)),
+        };
+        if data.len() != self.len() {
+            return Err(format!(
+                "save file {} is {} bytes, expected {}",
+                self.path.display(),
+                data.len(),
+                self.len()
+            ));
+        }
+        self.write(cpu, &data);
+        self.saved = data;
+        Ok(())
+    }
+
+    /// Writes memory to the save file if it changed since the last load
+    /// or flush.
+    pub fn flush(&mut self, cpu: &CPU) -> Result<(), String> {
+        let data = self.read(cpu);
+        if data == self.saved {
+            return Ok(());
+        }
+        fs::write(&self.path, &data)
+            .map_err(|e| format!("cannot write {}: {}", self.path.display(), e))?;
+        self.saved = data;
+        Ok(())
+    }
+
+    /// Carries the range over from the CPU `power_on` replaces.
+    pub(super) fn keep(&self, from: &CPU, to: &mut CPU) {
+        self.write(to, &self.read(from));
+    }
+}
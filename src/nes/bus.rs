@@ -0,0 +1,55 @@
+use crate::apu::Apu;
+use crate::cartridge::Rom;
+use crate::ppu::NesPPU;
+
+/// Everything the CPU reaches through memory mapped I/O on a real console.
+pub struct NesBus {
+    pub ppu: NesPPU,
+    pub apu: Apu,
+    pub rom: Rom,
+}
+
+impl NesBus {
+    pub fn new(rom: Rom) -> Self {
+        let ppu = NesPPU::new(rom.chr_rom.clone(), rom.screen_mirroring);
+        NesBus {
+            ppu,
+            apu: Apu::new(),
+            rom,
+        }
+    }
+
+    /// $2000-$3FFF, the eight PPU registers mirrored every 8 bytes.
+    pub fn ppu_register_read(&mut self, addr: u16) -> u8 {
+        match addr & 0x2007 {
+            0x2002 => self.ppu.read_status(),
+            0x2004 => self.ppu.read_oam_data(),
+            0x2007 => self.ppu.read_data(),
+            // write-only registers
+            _ => 0,
+        }
+    }
+
+    /// Same as `ppu_register_read` without clearing flags or moving addresses.
+    pub fn ppu_register_peek(&self, addr: u16) -> u8 {
+        match addr & 0x2007 {
+            0x2002 => self.ppu.status.snapshot(),
+            0x2004 => self.ppu.read_oam_data(),
+            0x2007 => self.ppu.peek_data(),
+            _ => 0,
+        }
+    }
+
+    pub fn ppu_register_write(&mut self, addr: u16, data: u8) {
+        match addr & 0x2007 {
+            0x2000 => self.ppu.write_to_ctrl(data),
+            0x2001 => self.ppu.write_to_mask(data),
+            0x2002 => {}
+            0x2003 => self.ppu.write_to_oam_addr(data),
+            0x2004 => self.ppu.write_to_oam_data(data),
+            0x2005 => self.ppu.write_to_scroll(data),
+            0x2006 => self.ppu.write_to_ppu_addr(data),
+            _ => self.ppu.write_to_data(data),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+pub mod battery;
+pub mod bus;
+pub mod region;
+
+use std::fs;
+use std::path::Path;
+
+use crate::cartridge::Rom;
+use crate::cpu::Unimplemented;
+use crate::cpu::CPU;
+use crate::frame::Frame;
+use crate::input::InputDevice;
+use crate::joypad::JoypadButton;
+
+use battery::Battery;
+use bus::NesBus;
+use region::Region;
+
+/// The whole console: CPU with PPU, APU and cartridge on its bus, clocked
+/// together one instruction at a time.
+pub struct Nes {
+    pub cpu: CPU,
+    halted: bool,
+    region: Region,
+    region_override: Option<Region>,
+    // PAL runs a fractional number of PPU dots per CPU cycle, the leftover
+    // is carried over in units of 1/denominator dots
+    dot_remainder: u32,
+    battery: Option<Battery>,
+}
+
+impl Default for Nes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Nes {
+    pub fn new() -> Self {
+        Nes {
+            cpu: CPU::new(),
+            halted: false,
+            region: Region::Ntsc,
+            region_override: None,
+            dot_remainder: 0,
+            battery: None,
+        }
+    }
+
+    /// Inserts an iNES cartridge and powers the console on. The battery of
+    /// the previous cartridge, if any, is flushed and detached.
+    pub fn load_rom(&mut self, raw: &[u8]) -> Result<(), String> {
+        let rom = Rom::new(raw)?;
+        if rom.mapper != 0 {
+            return Err(format!("Mapper {} is not supported", rom.mapper));
+        }
+        self.detach_battery()?;
+
+        self.region = self.region_override.or(rom.region).unwrap_or_default();
+        self.cpu.bus = Some(NesBus::new(rom));
+        self.power_on();
+        Ok(())
+    }
+
+    /// Loads a cartridge from disk. When its header has the battery flag,
+    /// $6000-$7FFF is restored from the `.sav` next to it and written back
+    /// by `flush_battery` and when the console is dropped.
+    pub fn load_rom_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let raw = fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        self.load_rom(&raw)?;
+        if self.cpu.bus.as_ref().is_some_and(|bus| bus.rom.battery) {
+            self.attach_battery(Battery::for_rom(path))?;
+        }
+        Ok(())
+    }
+
+    /// Keeps `battery.range` in `battery.path` from now on, loading what
+    /// the file holds. Replaces the battery attached before, flushing it.
+    pub fn attach_battery(&mut self, mut battery: Battery) -> Result<(), String> {
+        self.detach_battery()?;
+        battery.load(&mut self.cpu)?;
+        self.battery = Some(battery);
+        Ok(())
+    }
+
+    /// Flushes and stops persisting the battery RAM.
+    pub fn detach_battery(&mut self) -> Result<Option<Battery>, String> {
+        if let Some(battery) = self.battery.as_mut() {
+            battery.flush(&self.cpu)?;
+        }
+        Ok(self.battery.take())
+    }
+
+    pub fn battery(&self) -> Option<&Battery> {
+        self.battery.as_ref()
+    }
+
+    /// Writes the battery RAM to its save file now, e.g. every few seconds
+    /// so a crash does not lose the game's progress. Does nothing without
+    /// a battery or when nothing changed since the last flush.
+    pub fn flush_battery(&mut self) -> Result<(), String> {
+        match self.battery.as_mut() {
+            Some(battery) => battery.flush(&self.cpu),
+            None => Ok(()),
+        }
+    }
+
+    /// Cold boot: RAM, PPU and APU start from scratch, the cartridge and
+    /// its battery RAM stay.
+    pub fn power_on(&mut self) {
+        let mut cpu = CPU::new();
+        // whatever is plugged into the ports stays plugged in
+        cpu.port1 = std::mem::replace(&mut self.cpu.port1, InputDevice::Disconnected);
+        cpu.port2 = std::mem::replace(&mut self.cpu.port2, InputDevice::Disconnected);
+        cpu.bus = self.cpu.bus.take().map(|bus| NesBus::new(bus.rom));
+        if let Some(battery) = self.battery.as_ref() {
+            battery.keep(&self.cpu, &mut cpu);
+        }
+        // cheats stay in effect, like a Game Genie left in the slot
+        cpu.cheats = std::mem::take(&mut self.cpu.cheats);
+
+        self.cpu = cpu;
+        self.halted = false;
+        self.dot_remainder = 0;
+        self.apply_region();
+        self.cpu.reset();
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Forces a region instead of the one from the cartridge header. The
+    /// choice sticks for cartridges loaded afterwards.
+    pub fn set_region(&mut self, region: Region) {
+        self.region_override = Some(region);
+        self.region = region;
+        self.apply_region();
+    }
+
+    /// Goes back to detecting the region from the cartridge header, which
+    /// takes effect on the next `load_rom`.
+    pub fn clear_region_override(&mut self) {
+        self.region_override = None;
+    }
+
+    fn apply_region(&mut self) {
+        let region = self.region;
+        if let Some(bus) = self.cpu.bus.as_mut() {
+            bus.ppu.set_region(region);
+            bus.apu.set_region(region);
+        }
+    }
+
+    /// Reset button: memory survives, the CPU restarts at the reset vector.
+    pub fn reset(&mut self) {
+        if let Some(bus) = self.cpu.bus.as_mut() {
+            bus.ppu.reset();
+            bus.apu.reset();
+        }
+        self.halted = false;
+        self.cpu.reset();
+    }
+
+    /// Holds down exactly `buttons` on player `player`'s controller (1-4,
+    /// 3 and 4 need a Four Score) until the next call. Games usually poll
+    /// once per frame, so hosts set the buttons before `run_frame`.
+    pub fn set_buttons(&mut self, player: usize, buttons: JoypadButton) -> Result<(), String> {
+        let joypad = self
+            .cpu
+            .joypad_mut(player)
+            .ok_or(format!("player {} has no controller plugged in", player))?;
+        joypad.set_buttons(buttons);
+        Ok(())
+    }
+
+    /// Buttons currently held on player `player`'s controller.
+    pub fn buttons(&mut self, player: usize) -> Option<JoypadButton> {
+        self.cpu.joypad_mut(player).map(|joypad| joypad.buttons())
+    }
+
+    /// True once the CPU has hit BRK or an opcode it does not implement,
+    /// after that only the PPU and APU run. An NMI wakes it up from BRK.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// The opcode that halted the CPU, when it is one the CPU does not
+    /// implement.
+    pub fn unimplemented(&self) -> Option<Unimplemented> {
+        self.cpu.unimplemented()
+    }
+
+    /// Runs one CPU instruction (or a pending NMI) and clocks the PPU and
+    /// APU for the same number of cycles. Returns the CPU cycles spent and
+    /// whether the PPU finished a frame meanwhile, in which case a Zapper in
+    /// either port senses light from it.
+    ///
+    /// # Panics
+    ///
+    /// If no cartridge has been loaded.
+    pub fn step_instruction(&mut self) -> (u16, bool) {
+        let cycles_before = self.cpu.cycles;
+
+        let nmi = self.bus_mut().ppu.poll_nmi_interrupt();
+        // there is no sensible way to go on after an unimplemented opcode
+        if nmi.is_some() && self.cpu.unimplemented().is_none() {
+            self.cpu.nmi();
+            self.halted = false;
+        }
+
+        if self.halted {
+            // a halted CPU still lets the rest of the console tick
+            self.cpu.cycles += 1;
+        } else if !self.cpu.step() {
+            self.halted = true;
+        }
+
+        let cycles = (self.cpu.cycles - cycles_before) as u16;
+
+        let (numerator, denominator) = self.region.ppu_dots_per_cpu_cycle();
+        let scaled_dots = cycles as u32 * numerator + self.dot_remainder;
+        self.dot_remainder = scaled_dots % denominator;
+        let dots = (scaled_dots / denominator) as usize;
+
+        let cpu = &mut self.cpu;
+        let bus = cpu.bus.as_mut().expect("no cartridge loaded");
+        let frame_finished = bus.ppu.tick(dots);
+        bus.apu.tick(cycles);
+        if frame_finished {
+            cpu.port1.sense_light(&bus.ppu.frame);
+            cpu.port2.sense_light(&bus.ppu.frame);
+            cpu.apply_freezes();
+        }
+
+        (cycles, frame_finished)
+    }
+
+    /// Runs until the PPU finishes the next frame and returns it together
+    /// with the audio samples generated on the way.
+    ///
+    /// # Panics
+    ///
+    /// If no cartridge has been loaded.
+    pub fn run_frame(&mut self) -> (&Frame, Vec<f32>) {
+        loop {
+            let (_, frame_finished) = self.step_instruction();
+            if frame_finished {
+                break;
+            }
+        }
+
+        let bus = self.bus_mut();
+        let audio = bus.apu.take_samples();
+        (&bus.ppu.frame, audio)
+    }
+
+    fn bus_mut(&mut self) -> &mut NesBus {
+        self.cpu.bus.as_mut().expect("no cartridge loaded")
+    }
+}
+
+impl Drop for Nes {
+    fn drop(&mut self) {
+        // nobody is left to hand the error to
+        if let Err(message) = self.flush_battery() {
+            eprintln!("{}", message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
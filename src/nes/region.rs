@@ -0,0 +1,76 @@
+/// TV system the console was built for. The CPU core is the same, what
+/// changes is how fast it runs relative to the PPU and APU.
+/// https://www.nesdev.org/wiki/Cycle_reference_chart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    /// Famiclone timing: PAL frame rate with NTSC-like CPU speed and APU.
+    Dendy,
+}
+
+impl Region {
+    /// CPU clock in Hz.
+    pub fn cpu_clock(&self) -> f64 {
+        match self {
+            Region::Ntsc => 1_789_773.0,
+            Region::Pal => 1_662_607.0,
+            Region::Dendy => 1_773_448.0,
+        }
+    }
+
+    /// PPU dots per CPU cycle as a fraction, PAL runs 3.2 dots per cycle.
+    pub fn ppu_dots_per_cpu_cycle(&self) -> (u32, u32) {
+        match self {
+            Region::Ntsc | Region::Dendy => (3, 1),
+            Region::Pal => (16, 5),
+        }
+    }
+
+    pub fn scanlines_per_frame(&self) -> u16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    /// Scanline on which vertical blank (and the NMI) starts.
+    pub fn vblank_scanline(&self) -> u16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    /// CPU cycles of the APU frame counter steps, the last one is only
+    /// used in five step mode.
+    pub fn apu_frame_steps(&self) -> &'static [u32; 5] {
+        match self {
+            Region::Ntsc | Region::Dendy => &[7457, 14913, 22371, 29829, 37281],
+            Region::Pal => &[8313, 16627, 24939, 33253, 41565],
+        }
+    }
+
+    pub fn noise_periods(&self) -> &'static [u16; 16] {
+        match self {
+            Region::Ntsc | Region::Dendy => &[
+                4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+            ],
+            Region::Pal => &[
+                4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+            ],
+        }
+    }
+
+    pub fn dmc_rates(&self) -> &'static [u16; 16] {
+        match self {
+            Region::Ntsc | Region::Dendy => &[
+                428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+            ],
+            Region::Pal => &[
+                398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+            ],
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use super::*;
+use crate::asm;
+use crate::cartridge::test_rom::{test_rom, test_rom_with_header};
+use crate::cheat::Cheat;
+use crate::cpu::rewind::{Interval, Rewind};
+use crate::input::{InputDevice, Zapper};
+use tempfile::TempDir;
+
+// $C000: LDA #$80; STA $2000; JMP $C005
+// $C008: INX; JMP $C005
+const NMI_COUNTER: [u8; 12] = [
+    0xa9, 0x80, 0x8d, 0x00, 0x20, 0x4c, 0x05, 0xc0, 0xe8, 0x4c, 0x05, 0xc0,
+];
+
+#[test]
+fn test_load_rom_power_on() {
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom(&NMI_COUNTER, 0xC008, 0xC000, false))
+        .unwrap();
+
+    assert_eq!(nes.cpu.program_counter, 0xC000);
+    assert_eq!(nes.cpu.cycles, 7);
+}
+
+#[test]
+fn test_unsupported_mapper() {
+    let mut raw = test_rom(&NMI_COUNTER, 0xC008, 0xC000, false);
+    raw[6] = 0x10;
+
+    let mut nes = Nes::new();
+    assert!(nes.load_rom(&raw).is_err());
+}
+
+#[test]
+fn test_step_instruction_clocks_ppu() {
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom(&NMI_COUNTER, 0xC008, 0xC000, false))
+        .unwrap();
+
+    let (cycles, frame_finished) = nes.step_instruction();
+
+    assert_eq!(cycles, 2);
+    assert!(!frame_finished);
+    assert_eq!(nes.cpu.bus.as_ref().unwrap().ppu.dot, 6);
+}
+
+#[test]
+fn test_run_frame_nmi() {
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom(&NMI_COUNTER, 0xC008, 0xC000, false))
+        .unwrap();
+
+    // power on starts at the top of the picture, so the first frame is short
+    let (_, audio) = nes.run_frame();
+    assert!(!audio.is_empty());
+    for _ in 0..2 {
+        let (_, audio) = nes.run_frame();
+        assert!(audio.len() >= 733);
+    }
+
+    // the NMI of the last frame is serviced at the start of the next one
+    assert_eq!(nes.cpu.register_x, 2);
+    assert_eq!(nes.cpu.bus.as_ref().unwrap().ppu.frame_count, 3);
+}
+
+#[test]
+fn test_run_frame_backdrop() {
+    // LDA #$3F; STA $2006; LDA #$00; STA $2006; LDA #$21; STA $2007; BRK
+    let program = [
+        0xa9, 0x3f, 0x8d, 0x06, 0x20, 0xa9, 0x00, 0x8d, 0x06, 0x20, 0xa9, 0x21, 0x8d, 0x07, 0x20,
+        0x00,
+    ];
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom(&program, 0xC000, 0xC000, false))
+        .unwrap();
+
+    let (frame, _) = nes.run_frame();
+
+    assert!(frame.data.iter().all(|&pixel| pixel == 0x21));
+    assert!(nes.is_halted());
+}
+
+/// Fills the backdrop with `colour` and stops.
+fn backdrop_program(colour: u8) -> [u8; 16] {
+    // LDA #$3F; STA $2006; LDA #$00; STA $2006; LDA #colour; STA $2007; BRK
+    [
+        0xa9, 0x3f, 0x8d, 0x06, 0x20, 0xa9, 0x00, 0x8d, 0x06, 0x20, 0xa9, colour, 0x8d, 0x07, 0x20,
+        0x00,
+    ]
+}
+
+#[test]
+fn test_run_frame_zapper_senses_light() {
+    for (colour, light) in [(0x30, true), (0x0F, false)] {
+        let mut nes = Nes::new();
+        nes.load_rom(&test_rom(&backdrop_program(colour), 0xC000, 0xC000, false))
+            .unwrap();
+        let mut zapper = Zapper::new();
+        zapper.aim(128, 120);
+        nes.cpu.port2 = InputDevice::Zapper(zapper);
+
+        nes.run_frame();
+
+        // bit 3 clear means the photodiode sees light
+        assert_eq!(
+            nes.cpu.mem_peek(0x4017) & 0x08 == 0,
+            light,
+            "colour ${:02X}",
+            colour
+        );
+    }
+}
+
+#[test]
+fn test_step_instruction_zapper_senses_light() {
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom(&backdrop_program(0x30), 0xC000, 0xC000, false))
+        .unwrap();
+    let mut zapper = Zapper::new();
+    zapper.aim(128, 120);
+    nes.cpu.port1 = InputDevice::Zapper(zapper);
+
+    // one instruction at a time, as the debugger does
+    while !nes.step_instruction().1 {
+        assert_ne!(nes.cpu.mem_peek(0x4016) & 0x08, 0);
+    }
+
+    assert_eq!(nes.cpu.mem_peek(0x4016) & 0x08, 0);
+}
+
+#[test]
+fn test_rewind_by_frames() {
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom(&NMI_COUNTER, 0xC008, 0xC000, false))
+        .unwrap();
+    let mut rewind = Rewind::new(Interval::Frames(1), 8);
+
+    let mut states = Vec::new();
+    for _ in 0..5 {
+        nes.run_frame();
+        rewind.end_frame(&nes.cpu);
+        states.push(nes.cpu.save_state());
+    }
+    assert_eq!(rewind.snapshot_frames(), vec![1, 2, 3, 4, 5]);
+
+    rewind.rewind(&mut nes.cpu, 3).unwrap();
+    assert_eq!(nes.cpu.save_state(), states[2]);
+    assert_eq!(rewind.frames, 3);
+    assert_eq!(rewind.len(), 3);
+}
+
+#[test]
+fn test_reset_keeps_ram() {
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom(&NMI_COUNTER, 0xC008, 0xC000, false))
+        .unwrap();
+    nes.run_frame();
+    nes.run_frame();
+
+    nes.reset();
+    assert_eq!(nes.cpu.program_counter, 0xC000);
+    assert_eq!(nes.cpu.register_x, 0);
+
+    nes.power_on();
+    assert_eq!(nes.cpu.bus.as_ref().unwrap().ppu.frame_count, 0);
+}
+
+#[test]
+fn test_region_from_nes2_header() {
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom_with_header(
+        &NMI_COUNTER,
+        0xC008,
+        0xC000,
+        false,
+        &[0, 0, 0, 0, 0x01, 0, 0, 0],
+    ))
+    .unwrap();
+
+    assert_eq!(nes.region(), Region::Pal);
+
+    nes.load_rom(&test_rom(&NMI_COUNTER, 0xC008, 0xC000, false))
+        .unwrap();
+    assert_eq!(nes.region(), Region::Ntsc);
+}
+
+#[test]
+fn test_region_override() {
+    let mut nes = Nes::new();
+    nes.set_region(Region::Dendy);
+    nes.load_rom(&test_rom_with_header(
+        &NMI_COUNTER,
+        0xC008,
+        0xC000,
+        false,
+        &[0, 0, 0, 0, 0x01, 0, 0, 0],
+    ))
+    .unwrap();
+
+    assert_eq!(nes.region(), Region::Dendy);
+
+    nes.clear_region_override();
+    nes.load_rom(&test_rom(&NMI_COUNTER, 0xC008, 0xC000, false))
+        .unwrap();
+    assert_eq!(nes.region(), Region::Ntsc);
+}
+
+#[test]
+fn test_pal_frame_length() {
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom(&NMI_COUNTER, 0xC008, 0xC000, false))
+        .unwrap();
+    nes.set_region(Region::Pal);
+
+    nes.run_frame();
+    let start = nes.cpu.cycles;
+    nes.run_frame();
+    let cycles = nes.cpu.cycles - start;
+
+    // 312 scanlines of 341 dots at 3.2 dots per cycle, give or take an instruction
+    assert!((33245..=33252).contains(&cycles), "{}", cycles);
+}
+
+#[test]
+fn test_dendy_frame_length() {
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom(&NMI_COUNTER, 0xC008, 0xC000, false))
+        .unwrap();
+    nes.set_region(Region::Dendy);
+
+    nes.run_frame();
+    let start = nes.cpu.cycles;
+    nes.run_frame();
+    let cycles = nes.cpu.cycles - start;
+
+    // 312 scanlines of 341 dots at 3 dots per cycle
+    assert!((35460..=35470).contains(&cycles), "{}", cycles);
+}
+
+#[test]
+fn test_set_buttons() {
+    let mut nes = Nes::new();
+    nes.load_rom(&test_rom(&NMI_COUNTER, 0xC008, 0xC000, false))
+        .unwrap();
+
+    nes.set_buttons(2, JoypadButton::START).unwrap();
+    assert_eq!(nes.buttons(2), Some(JoypadButton::START));
+    assert_eq!(nes.buttons(1), Some(JoypadButton::empty()));
+
+    // players 3 and 4 need a Four Score
+    assert_eq!(
+        nes.set_buttons(3, JoypadButton::BUTTON_A),
+        Err("player 3 has no controller plugged in".to_string())
+    );
+}
+
+// bumps $6000 and marks the end of the battery RAM once per power on
+fn battery_rom(battery: bool) -> Vec<u8> {
+    let program = asm::assemble(
+        "        .org $C000
+                 INC $6000
+                 LDA #$42
+                 STA $7FFF
+                 INC $0300
+         idle:   JMP idle",
+    )
+    .unwrap();
+    test_rom(&program.bytes, 0xC000, 0xC000, battery)
+}
+
+fn write_rom(dir: &TempDir, battery: bool) -> std::path::PathBuf {
+    let path = dir.path().join("game.nes");
+    fs::write(&path, battery_rom(battery)).unwrap();
+    path
+}
+
+#[test]
+fn test_battery_persists_across_sessions() {
+    let dir = TempDir::new().unwrap();
+    let rom = write_rom(&dir, true);
+    let sav = dir.path().join("game.sav");
+
+    let mut nes = Nes::new();
+    nes.load_rom_file(&rom).unwrap();
+    assert_eq!(nes.battery().unwrap().path, sav);
+    nes.run_frame();
+    assert!(!sav.exists());
+    drop(nes);
+
+    let saved = fs::read(&sav).unwrap();
+    assert_eq!(saved.len(), 0x2000);
+    assert_eq!((saved[0], saved[0x1FFF]), (1, 0x42));
+
+    let mut nes = Nes::new();
+    nes.load_rom_file(&rom).unwrap();
+    nes.run_frame();
+    assert_eq!(nes.cpu.mem_peek(0x6000), 2);
+    nes.flush_battery().unwrap();
+    assert_eq!(fs::read(&sav).unwrap()[0], 2);
+}
+
+#[test]
+fn test_power_on_keeps_battery_ram() {
+    let dir = TempDir::new().unwrap();
+    let mut nes = Nes::new();
+    nes.load_rom_file(write_rom(&dir, true)).unwrap();
+    nes.run_frame();
+
+    nes.power_on();
+    nes.run_frame();
+    assert_eq!(nes.cpu.mem_peek(0x6000), 2);
+    // ordinary RAM does start over
+    assert_eq!(nes.cpu.mem_peek(0x0300), 1);
+}
+
+#[test]
+fn test_flush_skips_unchanged_ram() {
+    let dir = TempDir::new().unwrap();
+    let sav = dir.path().join("game.sav");
+    let mut nes = Nes::new();
+    nes.load_rom_file(write_rom(&dir, true)).unwrap();
+    nes.run_frame();
+
+    nes.flush_battery().unwrap();
+    fs::remove_file(&sav).unwrap();
+    nes.flush_battery().unwrap();
+    assert!(!sav.exists());
+}
+
+#[test]
+fn test_no_battery_no_save_file() {
+    let dir = TempDir::new().unwrap();
+    let mut nes = Nes::new();
+    nes.load_rom_file(write_rom(&dir, false)).unwrap();
+    assert!(nes.battery().is_none());
+    nes.run_frame();
+    drop(nes);
+
+    assert!(!dir.path().join("game.sav").exists());
+}
+
+#[test]
+fn test_battery_custom_range() {
+    let dir = TempDir::new().unwrap();
+    let sav = dir.path().join("custom.sav");
+    fs::write(&sav, [7, 8]).unwrap();
+
+    let mut nes = Nes::new();
+    nes.load_rom_file(write_rom(&dir, false)).unwrap();
+    nes.attach_battery(Battery::new(&sav, 0x7FFE..=0x7FFF))
+        .unwrap();
+    assert_eq!(nes.cpu.mem_peek(0x7FFE), 7);
+    nes.run_frame();
+
+    let battery = nes.detach_battery().unwrap().unwrap();
+    assert_eq!(battery.range, 0x7FFE..=0x7FFF);
+    assert_eq!(fs::read(&sav).unwrap(), [7, 0x42]);
+}
+
+#[test]
+fn test_battery_wrong_size() {
+    let dir = TempDir::new().unwrap();
+    let rom = write_rom(&dir, true);
+    let sav = dir.path().join("game.sav");
+    fs::write(&sav, [0; 100]).unwrap();
+
+    let mut nes = Nes::new();
+    assert_eq!(
+        nes.load_rom_file(&rom),
+        Err(format!(
+            "save file {} is 100 bytes, expected 8192",
+            sav.display()
+        ))
+    );
+}
+
+#[test]
+fn test_freeze_every_frame() {
+    let mut nes = Nes::new();
+    nes.load_rom(&battery_rom(false)).unwrap();
+    nes.cpu.cheats.add(Cheat::freeze(0x0300, 0x10));
+
+    nes.run_frame();
+    assert_eq!(nes.cpu.mem_peek(0x0300), 0x10);
+
+    // the cheats survive a power cycle, RAM does not
+    nes.power_on();
+    nes.step_instruction();
+    assert_eq!(nes.cpu.cheats.len(), 1);
+    assert_eq!(nes.cpu.mem_peek(0x0300), 0);
+    nes.run_frame();
+    assert_eq!(nes.cpu.mem_peek(0x0300), 0x10);
+}
@@ -0,0 +1,279 @@
+pub mod registers;
+pub mod render;
+
+use crate::cartridge::Mirroring;
+use crate::frame::Frame;
+use crate::nes::region::Region;
+
+use registers::AddrRegister;
+use registers::ControlRegister;
+use registers::MaskRegister;
+use registers::ScrollRegister;
+use registers::StatusRegister;
+
+pub const DOTS_PER_SCANLINE: usize = 341;
+
+/// # 2C02 picture processing unit https://www.nesdev.org/wiki/PPU
+///
+/// Timing is kept per dot and scanline, but the picture itself is drawn
+/// in one go from nametables and OAM when vertical blank starts.
+pub struct NesPPU {
+    pub chr_rom: Vec<u8>,
+    chr_is_ram: bool,
+    pub mirroring: Mirroring,
+    pub palette_table: [u8; 32],
+    pub vram: [u8; 2048],
+    pub oam_data: [u8; 256],
+    pub oam_addr: u8,
+
+    pub addr: AddrRegister,
+    pub ctrl: ControlRegister,
+    pub mask: MaskRegister,
+    pub status: StatusRegister,
+    pub scroll: ScrollRegister,
+    internal_data_buf: u8,
+
+    pub scanline: u16,
+    pub dot: usize,
+    pub frame_count: u64,
+    scanlines_per_frame: u16,
+    vblank_scanline: u16,
+    nmi_interrupt: Option<u8>,
+    pub frame: Frame,
+}
+
+impl NesPPU {
+    /// Cartridges without CHR ROM come with 8K of CHR RAM instead.
+    pub fn new(chr_rom: Vec<u8>, mirroring: Mirroring) -> Self {
+        let chr_is_ram = chr_rom.is_empty();
+        NesPPU {
+            chr_rom: if chr_is_ram { vec![0; 0x2000] } else { chr_rom },
+            chr_is_ram,
+            mirroring,
+            palette_table: [0; 32],
+            vram: [0; 2048],
+            oam_data: [0; 256],
+            oam_addr: 0,
+            addr: AddrRegister::new(),
+            ctrl: ControlRegister::empty(),
+            mask: MaskRegister::empty(),
+            status: StatusRegister::empty(),
+            scroll: ScrollRegister::new(),
+            internal_data_buf: 0,
+            scanline: 0,
+            dot: 0,
+            frame_count: 0,
+            scanlines_per_frame: Region::Ntsc.scanlines_per_frame(),
+            vblank_scanline: Region::Ntsc.vblank_scanline(),
+            nmi_interrupt: None,
+            frame: Frame::new(),
+        }
+    }
+
+    pub fn new_empty_rom() -> Self {
+        NesPPU::new(vec![0; 0x2000], Mirroring::Horizontal)
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.scanlines_per_frame = region.scanlines_per_frame();
+        self.vblank_scanline = region.vblank_scanline();
+    }
+
+    /// Reset line: the control registers are cleared, memory is kept.
+    pub fn reset(&mut self) {
+        self.ctrl = ControlRegister::empty();
+        self.mask = MaskRegister::empty();
+        self.scroll = ScrollRegister::new();
+        self.addr.reset_latch();
+        self.internal_data_buf = 0;
+        self.scanline = 0;
+        self.dot = 0;
+        self.nmi_interrupt = None;
+    }
+
+    /// Advances the PPU by `dots`. Returns true when a frame has just been
+    /// finished, which happens as vertical blank starts.
+    pub fn tick(&mut self, dots: usize) -> bool {
+        let mut frame_finished = false;
+        self.dot += dots;
+
+        while self.dot >= DOTS_PER_SCANLINE {
+            if self.is_sprite_0_hit(self.dot) {
+                self.status.set_sprite_zero_hit(true);
+            }
+
+            self.dot -= DOTS_PER_SCANLINE;
+            self.scanline += 1;
+
+            if self.scanline == self.vblank_scanline {
+                self.status.set_vblank_status(true);
+                self.status.set_sprite_zero_hit(false);
+                if self.ctrl.generate_vblank_nmi() {
+                    self.nmi_interrupt = Some(1);
+                }
+
+                render::render(self);
+                self.frame_count += 1;
+                frame_finished = true;
+            }
+
+            if self.scanline >= self.scanlines_per_frame {
+                self.scanline = 0;
+                self.nmi_interrupt = None;
+                self.status.set_sprite_zero_hit(false);
+                self.status.set_vblank_status(false);
+            }
+        }
+
+        frame_finished
+    }
+
+    fn is_sprite_0_hit(&self, dot: usize) -> bool {
+        let y = self.oam_data[0] as usize;
+        let x = self.oam_data[3] as usize;
+        (y == self.scanline as usize) && x <= dot && self.mask.contains(MaskRegister::SHOW_SPRITES)
+    }
+
+    pub fn poll_nmi_interrupt(&mut self) -> Option<u8> {
+        self.nmi_interrupt.take()
+    }
+
+    pub fn write_to_ctrl(&mut self, value: u8) {
+        let before_nmi_status = self.ctrl.generate_vblank_nmi();
+        self.ctrl.update(value);
+        if !before_nmi_status && self.ctrl.generate_vblank_nmi() && self.status.is_in_vblank() {
+            self.nmi_interrupt = Some(1);
+        }
+    }
+
+    pub fn write_to_mask(&mut self, value: u8) {
+        self.mask.update(value);
+    }
+
+    pub fn read_status(&mut self) -> u8 {
+        let data = self.status.snapshot();
+        self.status.set_vblank_status(false);
+        self.addr.reset_latch();
+        self.scroll.reset_latch();
+        data
+    }
+
+    pub fn write_to_oam_addr(&mut self, value: u8) {
+        self.oam_addr = value;
+    }
+
+    pub fn write_to_oam_data(&mut self, value: u8) {
+        self.oam_data[self.oam_addr as usize] = value;
+        self.oam_addr = self.oam_addr.wrapping_add(1);
+    }
+
+    pub fn read_oam_data(&self) -> u8 {
+        self.oam_data[self.oam_addr as usize]
+    }
+
+    /// $4014 OAM DMA: one full page copied starting at the current OAM address.
+    pub fn write_oam_dma(&mut self, data: &[u8; 256]) {
+        for x in data.iter() {
+            self.oam_data[self.oam_addr as usize] = *x;
+            self.oam_addr = self.oam_addr.wrapping_add(1);
+        }
+    }
+
+    pub fn write_to_scroll(&mut self, value: u8) {
+        self.scroll.write(value);
+    }
+
+    pub fn write_to_ppu_addr(&mut self, value: u8) {
+        self.addr.update(value);
+    }
+
+    fn increment_vram_addr(&mut self) {
+        self.addr.increment(self.ctrl.vram_addr_increment());
+    }
+
+    pub fn write_to_data(&mut self, value: u8) {
+        let addr = self.addr.get();
+        match addr {
+            0..=0x1fff => {
+                if self.chr_is_ram {
+                    self.chr_rom[addr as usize] = value;
+                }
+            }
+            0x2000..=0x3eff => {
+                self.vram[self.mirror_vram_addr(addr) as usize] = value;
+            }
+            0x3f00..=0x3fff => {
+                self.palette_table[Self::mirror_palette_addr(addr)] = value;
+            }
+            _ => unreachable!("unexpected access to mirrored space {:#06x}", addr),
+        }
+        self.increment_vram_addr();
+    }
+
+    /// $2007 reads below the palette go through a one byte delay buffer.
+    pub fn read_data(&mut self) -> u8 {
+        let addr = self.addr.get();
+        self.increment_vram_addr();
+
+        match addr {
+            0..=0x1fff => {
+                let result = self.internal_data_buf;
+                self.internal_data_buf = self.chr_rom[addr as usize];
+                result
+            }
+            0x2000..=0x3eff => {
+                let result = self.internal_data_buf;
+                self.internal_data_buf = self.vram[self.mirror_vram_addr(addr) as usize];
+                result
+            }
+            0x3f00..=0x3fff => {
+                self.internal_data_buf = self.vram[self.mirror_vram_addr(addr - 0x1000) as usize];
+                self.palette_table[Self::mirror_palette_addr(addr)]
+            }
+            _ => unreachable!("unexpected access to mirrored space {:#06x}", addr),
+        }
+    }
+
+    /// What `read_data` would return, without touching the buffer or address.
+    pub fn peek_data(&self) -> u8 {
+        let addr = self.addr.get();
+        match addr {
+            0x3f00..=0x3fff => self.palette_table[Self::mirror_palette_addr(addr)],
+            _ => self.internal_data_buf,
+        }
+    }
+
+    // $3F10/$3F14/$3F18/$3F1C are mirrors of $3F00/$3F04/$3F08/$3F0C
+    fn mirror_palette_addr(addr: u16) -> usize {
+        let index = (addr - 0x3f00) as usize % 32;
+        match index {
+            0x10 | 0x14 | 0x18 | 0x1c => index - 0x10,
+            _ => index,
+        }
+    }
+
+    // Horizontal:
+    //   [ A ] [ a ]
+    //   [ B ] [ b ]
+
+    // Vertical:
+    //   [ A ] [ B ]
+    //   [ a ] [ b ]
+    pub fn mirror_vram_addr(&self, addr: u16) -> u16 {
+        let mirrored_vram = addr & 0b10111111111111; // mirror down 0x3000-0x3eff to 0x2000 - 0x2eff
+        let vram_index = mirrored_vram - 0x2000; // to vram vector
+        let name_table = vram_index / 0x400; // to the name table index
+        match (&self.mirroring, name_table) {
+            (Mirroring::Vertical, 2) | (Mirroring::Vertical, 3) => vram_index - 0x800,
+            (Mirroring::Horizontal, 2) => vram_index - 0x400,
+            (Mirroring::Horizontal, 1) => vram_index - 0x400,
+            (Mirroring::Horizontal, 3) => vram_index - 0x800,
+            // only 2K of nametable RAM exists, four screen falls back to vertical
+            (Mirroring::FourScreen, 2) | (Mirroring::FourScreen, 3) => vram_index - 0x800,
+            _ => vram_index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
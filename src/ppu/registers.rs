@@ -0,0 +1,252 @@
+use bitflags::bitflags;
+
+/// # PPUADDR $2006, written high byte first https://www.nesdev.org/wiki/PPU_registers#PPUADDR
+pub struct AddrRegister {
+    value: (u8, u8),
+    hi_ptr: bool,
+}
+
+impl Default for AddrRegister {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AddrRegister {
+    pub fn new() -> Self {
+        AddrRegister {
+            value: (0, 0), // high byte first, lo byte second
+            hi_ptr: true,
+        }
+    }
+
+    fn set(&mut self, data: u16) {
+        self.value.0 = (data >> 8) as u8;
+        self.value.1 = (data & 0xff) as u8;
+    }
+
+    pub fn update(&mut self, data: u8) {
+        if self.hi_ptr {
+            self.value.0 = data;
+        } else {
+            self.value.1 = data;
+        }
+
+        if self.get() > 0x3fff {
+            // mirror down addr above 0x3fff
+            self.set(self.get() & 0b11111111111111);
+        }
+        self.hi_ptr = !self.hi_ptr;
+    }
+
+    pub fn increment(&mut self, inc: u8) {
+        let lo = self.value.1;
+        self.value.1 = self.value.1.wrapping_add(inc);
+        if lo > self.value.1 {
+            self.value.0 = self.value.0.wrapping_add(1);
+        }
+        if self.get() > 0x3fff {
+            self.set(self.get() & 0b11111111111111);
+        }
+    }
+
+    pub fn reset_latch(&mut self) {
+        self.hi_ptr = true;
+    }
+
+    pub fn get(&self) -> u16 {
+        ((self.value.0 as u16) << 8) | (self.value.1 as u16)
+    }
+}
+
+bitflags! {
+    /// # PPUCTRL $2000 https://www.nesdev.org/wiki/PPU_registers#PPUCTRL
+    ///
+    ///  7  bit  0
+    ///  ---- ----
+    ///  VPHB SINN
+    ///  |||| ||||
+    ///  |||| ||++- Base nametable address
+    ///  |||| ||    (0 = $2000; 1 = $2400; 2 = $2800; 3 = $2C00)
+    ///  |||| |+--- VRAM address increment per CPU read/write of PPUDATA
+    ///  |||| |     (0: add 1, going across; 1: add 32, going down)
+    ///  |||| +---- Sprite pattern table address for 8x8 sprites
+    ///  ||||       (0: $0000; 1: $1000; ignored in 8x16 mode)
+    ///  |||+------ Background pattern table address (0: $0000; 1: $1000)
+    ///  ||+------- Sprite size (0: 8x8 pixels; 1: 8x16 pixels)
+    ///  |+-------- PPU master/slave select
+    ///  +--------- Generate an NMI at the start of the vertical blanking interval
+    ///
+    pub struct ControlRegister: u8 {
+        const NAMETABLE1              = 0b00000001;
+        const NAMETABLE2              = 0b00000010;
+        const VRAM_ADD_INCREMENT      = 0b00000100;
+        const SPRITE_PATTERN_ADDR     = 0b00001000;
+        const BACKROUND_PATTERN_ADDR  = 0b00010000;
+        const SPRITE_SIZE             = 0b00100000;
+        const MASTER_SLAVE_SELECT     = 0b01000000;
+        const GENERATE_NMI            = 0b10000000;
+    }
+}
+
+impl ControlRegister {
+    pub fn vram_addr_increment(&self) -> u8 {
+        if !self.contains(ControlRegister::VRAM_ADD_INCREMENT) {
+            1
+        } else {
+            32
+        }
+    }
+
+    pub fn sprt_pattern_addr(&self) -> u16 {
+        if !self.contains(ControlRegister::SPRITE_PATTERN_ADDR) {
+            0
+        } else {
+            0x1000
+        }
+    }
+
+    pub fn bknd_pattern_addr(&self) -> u16 {
+        if !self.contains(ControlRegister::BACKROUND_PATTERN_ADDR) {
+            0
+        } else {
+            0x1000
+        }
+    }
+
+    pub fn sprite_size(&self) -> u8 {
+        if !self.contains(ControlRegister::SPRITE_SIZE) {
+            8
+        } else {
+            16
+        }
+    }
+
+    pub fn nametable_index(&self) -> u8 {
+        self.bits & 0b11
+    }
+
+    pub fn generate_vblank_nmi(&self) -> bool {
+        self.contains(ControlRegister::GENERATE_NMI)
+    }
+
+    pub fn update(&mut self, data: u8) {
+        self.bits = data;
+    }
+}
+
+bitflags! {
+    /// # PPUMASK $2001 https://www.nesdev.org/wiki/PPU_registers#PPUMASK
+    ///
+    ///  7  bit  0
+    ///  ---- ----
+    ///  BGRs bMmG
+    ///  |||| ||||
+    ///  |||| |||+- Greyscale (0: normal color, 1: produce a greyscale display)
+    ///  |||| ||+-- 1: Show background in leftmost 8 pixels of screen, 0: Hide
+    ///  |||| |+--- 1: Show sprites in leftmost 8 pixels of screen, 0: Hide
+    ///  |||| +---- 1: Show background
+    ///  |||+------ 1: Show sprites
+    ///  ||+------- Emphasize red
+    ///  |+-------- Emphasize green
+    ///  +--------- Emphasize blue
+    ///
+    pub struct MaskRegister: u8 {
+        const GREYSCALE               = 0b00000001;
+        const LEFTMOST_8PXL_BACKGROUND = 0b00000010;
+        const LEFTMOST_8PXL_SPRITE    = 0b00000100;
+        const SHOW_BACKGROUND         = 0b00001000;
+        const SHOW_SPRITES            = 0b00010000;
+        const EMPHASISE_RED           = 0b00100000;
+        const EMPHASISE_GREEN         = 0b01000000;
+        const EMPHASISE_BLUE          = 0b10000000;
+    }
+}
+
+impl MaskRegister {
+    pub fn update(&mut self, data: u8) {
+        self.bits = data;
+    }
+}
+
+bitflags! {
+    /// # PPUSTATUS $2002 https://www.nesdev.org/wiki/PPU_registers#PPUSTATUS
+    ///
+    ///  7  bit  0
+    ///  ---- ----
+    ///  VSO. ....
+    ///  |||| ||||
+    ///  |||+-++++- Open bus
+    ///  ||+------- Sprite overflow
+    ///  |+-------- Sprite 0 hit
+    ///  +--------- Vertical blank has started
+    ///
+    pub struct StatusRegister: u8 {
+        const NOTUSED          = 0b00000001;
+        const NOTUSED2         = 0b00000010;
+        const NOTUSED3         = 0b00000100;
+        const NOTUSED4         = 0b00001000;
+        const NOTUSED5         = 0b00010000;
+        const SPRITE_OVERFLOW  = 0b00100000;
+        const SPRITE_ZERO_HIT  = 0b01000000;
+        const VBLANK_STARTED   = 0b10000000;
+    }
+}
+
+impl StatusRegister {
+    pub fn set_vblank_status(&mut self, status: bool) {
+        self.set(StatusRegister::VBLANK_STARTED, status);
+    }
+
+    pub fn set_sprite_zero_hit(&mut self, status: bool) {
+        self.set(StatusRegister::SPRITE_ZERO_HIT, status);
+    }
+
+    pub fn set_sprite_overflow(&mut self, status: bool) {
+        self.set(StatusRegister::SPRITE_OVERFLOW, status);
+    }
+
+    pub fn is_in_vblank(&self) -> bool {
+        self.contains(StatusRegister::VBLANK_STARTED)
+    }
+
+    pub fn snapshot(&self) -> u8 {
+        self.bits
+    }
+}
+
+/// # PPUSCROLL $2005, written X first https://www.nesdev.org/wiki/PPU_registers#PPUSCROLL
+pub struct ScrollRegister {
+    pub scroll_x: u8,
+    pub scroll_y: u8,
+    latch: bool,
+}
+
+impl Default for ScrollRegister {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScrollRegister {
+    pub fn new() -> Self {
+        ScrollRegister {
+            scroll_x: 0,
+            scroll_y: 0,
+            latch: false,
+        }
+    }
+
+    pub fn write(&mut self, data: u8) {
+        if !self.latch {
+            self.scroll_x = data;
+        } else {
+            self.scroll_y = data;
+        }
+        self.latch = !self.latch;
+    }
+
+    pub fn reset_latch(&mut self) {
+        self.latch = false;
+    }
+}
@@ -0,0 +1,136 @@
+use super::registers::MaskRegister;
+use super::NesPPU;
+use crate::frame::Frame;
+
+/// Draws the whole frame from the current nametables, scroll and OAM.
+pub fn render(ppu: &mut NesPPU) {
+    let mut background_opaque = vec![false; Frame::WIDTH * Frame::HEIGHT];
+    let mut frame = Frame::new();
+
+    let universal_background = ppu.palette_table[0];
+    for pixel in frame.data.iter_mut() {
+        *pixel = universal_background;
+    }
+
+    if ppu.mask.contains(MaskRegister::SHOW_BACKGROUND) {
+        render_background(ppu, &mut frame, &mut background_opaque);
+    }
+
+    if ppu.mask.contains(MaskRegister::SHOW_SPRITES) {
+        render_sprites(ppu, &mut frame, &background_opaque);
+    }
+
+    if ppu.mask.contains(MaskRegister::GREYSCALE) {
+        for pixel in frame.data.iter_mut() {
+            *pixel &= 0x30;
+        }
+    }
+
+    ppu.frame = frame;
+}
+
+fn render_background(ppu: &NesPPU, frame: &mut Frame, background_opaque: &mut [bool]) {
+    let bank = ppu.ctrl.bknd_pattern_addr() as usize;
+    let nametable = ppu.ctrl.nametable_index() as usize;
+
+    // position of the screen inside the 512x480 area covered by the four nametables
+    let origin_x = (nametable & 1) * 256 + ppu.scroll.scroll_x as usize;
+    let origin_y = (nametable >> 1) * 240 + ppu.scroll.scroll_y as usize;
+
+    let show_left = ppu.mask.contains(MaskRegister::LEFTMOST_8PXL_BACKGROUND);
+
+    for y in 0..Frame::HEIGHT {
+        let world_y = (origin_y + y) % 480;
+        for x in 0..Frame::WIDTH {
+            if x < 8 && !show_left {
+                continue;
+            }
+
+            let world_x = (origin_x + x) % 512;
+            let table = (world_y / 240) * 2 + world_x / 256;
+            let table_base = 0x2000 + table as u16 * 0x400;
+
+            let tile_column = (world_x % 256) / 8;
+            let tile_row = (world_y % 240) / 8;
+
+            let tile_addr = table_base + (tile_row * 32 + tile_column) as u16;
+            let tile = ppu.vram[ppu.mirror_vram_addr(tile_addr) as usize] as usize;
+
+            let attr_addr = table_base + 0x3c0 + ((tile_row / 4) * 8 + tile_column / 4) as u16;
+            let attr_byte = ppu.vram[ppu.mirror_vram_addr(attr_addr) as usize];
+            let shift = ((tile_row % 4) / 2) * 4 + ((tile_column % 4) / 2) * 2;
+            let palette = (attr_byte >> shift) & 0b11;
+
+            let fine_y = world_y % 8;
+            let fine_x = world_x % 8;
+            let lower = ppu.chr_rom[bank + tile * 16 + fine_y];
+            let upper = ppu.chr_rom[bank + tile * 16 + fine_y + 8];
+            let value = (((upper >> (7 - fine_x)) & 1) << 1) | ((lower >> (7 - fine_x)) & 1);
+
+            if value != 0 {
+                frame.set_pixel(x, y, ppu.palette_table[(palette * 4 + value) as usize]);
+                background_opaque[y * Frame::WIDTH + x] = true;
+            }
+        }
+    }
+}
+
+fn render_sprites(ppu: &NesPPU, frame: &mut Frame, background_opaque: &[bool]) {
+    let sprite_height = ppu.ctrl.sprite_size() as usize;
+    let show_left = ppu.mask.contains(MaskRegister::LEFTMOST_8PXL_SPRITE);
+
+    // lower OAM indexes have priority, so draw them last
+    for i in (0..ppu.oam_data.len()).step_by(4).rev() {
+        let sprite_y = ppu.oam_data[i] as usize + 1;
+        let tile_index = ppu.oam_data[i + 1] as usize;
+        let attributes = ppu.oam_data[i + 2];
+        let sprite_x = ppu.oam_data[i + 3] as usize;
+
+        let flip_vertical = attributes & 0b1000_0000 != 0;
+        let flip_horizontal = attributes & 0b0100_0000 != 0;
+        let behind_background = attributes & 0b0010_0000 != 0;
+        let palette = 0x10 + (attributes & 0b11) as usize * 4;
+
+        let (bank, first_tile) = if sprite_height == 16 {
+            ((tile_index & 1) * 0x1000, tile_index & 0xFE)
+        } else {
+            (ppu.ctrl.sprt_pattern_addr() as usize, tile_index)
+        };
+
+        for row in 0..sprite_height {
+            let y = sprite_y + row;
+            if y >= Frame::HEIGHT {
+                break;
+            }
+
+            let pattern_row = if flip_vertical {
+                sprite_height - 1 - row
+            } else {
+                row
+            };
+            let tile = first_tile + pattern_row / 8;
+            let fine_y = pattern_row % 8;
+            let lower = ppu.chr_rom[bank + tile * 16 + fine_y];
+            let upper = ppu.chr_rom[bank + tile * 16 + fine_y + 8];
+
+            for column in 0..8 {
+                let x = sprite_x + column;
+                if x >= Frame::WIDTH || (x < 8 && !show_left) {
+                    continue;
+                }
+
+                let bit = if flip_horizontal { column } else { 7 - column };
+                let value = (((upper >> bit) & 1) << 1) | ((lower >> bit) & 1);
+                if value == 0 {
+                    continue;
+                }
+
+                if behind_background && background_opaque[y * Frame::WIDTH + x] {
+                    continue;
+                }
+
+                frame.set_pixel(x, y, ppu.palette_table[palette + value as usize]);
+            }
+        }
+    }
+}
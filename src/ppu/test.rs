@@ -0,0 +1,282 @@
+use super::*;
+
+#[test]
+fn test_ppu_vram_writes() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.write_to_ppu_addr(0x23);
+    ppu.write_to_ppu_addr(0x05);
+    ppu.write_to_data(0x66);
+
+    assert_eq!(ppu.vram[0x0305], 0x66);
+}
+
+#[test]
+fn test_ppu_vram_reads() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.write_to_ctrl(0);
+    ppu.vram[0x0305] = 0x66;
+
+    ppu.write_to_ppu_addr(0x23);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.read_data(); //load_into_buffer
+    assert_eq!(ppu.addr.get(), 0x2306);
+    assert_eq!(ppu.read_data(), 0x66);
+}
+
+#[test]
+fn test_ppu_vram_reads_cross_page() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.write_to_ctrl(0);
+    ppu.vram[0x01ff] = 0x66;
+    ppu.vram[0x0200] = 0x77;
+
+    ppu.write_to_ppu_addr(0x21);
+    ppu.write_to_ppu_addr(0xff);
+
+    ppu.read_data(); //load_into_buffer
+    assert_eq!(ppu.read_data(), 0x66);
+    assert_eq!(ppu.read_data(), 0x77);
+}
+
+#[test]
+fn test_ppu_vram_reads_step_32() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.write_to_ctrl(0b100);
+    ppu.vram[0x01ff] = 0x66;
+    ppu.vram[0x01ff + 32] = 0x77;
+    ppu.vram[0x01ff + 64] = 0x88;
+
+    ppu.write_to_ppu_addr(0x21);
+    ppu.write_to_ppu_addr(0xff);
+
+    ppu.read_data(); //load_into_buffer
+    assert_eq!(ppu.read_data(), 0x66);
+    assert_eq!(ppu.read_data(), 0x77);
+    assert_eq!(ppu.read_data(), 0x88);
+}
+
+// Horizontal: https://wiki.nesdev.com/w/index.php/Mirroring
+//   [0x2000 A ] [0x2400 a ]
+//   [0x2800 B ] [0x2C00 b ]
+#[test]
+fn test_vram_horizontal_mirror() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.write_to_ppu_addr(0x24);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.write_to_data(0x66); //write to a
+
+    ppu.write_to_ppu_addr(0x28);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.write_to_data(0x77); //write to B
+
+    ppu.write_to_ppu_addr(0x20);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.read_data(); //load into buffer
+    assert_eq!(ppu.read_data(), 0x66); //read from A
+
+    ppu.write_to_ppu_addr(0x2C);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.read_data(); //load into buffer
+    assert_eq!(ppu.read_data(), 0x77); //read from b
+}
+
+// Vertical: https://wiki.nesdev.com/w/index.php/Mirroring
+//   [0x2000 A ] [0x2400 B ]
+//   [0x2800 a ] [0x2C00 b ]
+#[test]
+fn test_vram_vertical_mirror() {
+    let mut ppu = NesPPU::new(vec![0; 2048], Mirroring::Vertical);
+
+    ppu.write_to_ppu_addr(0x20);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.write_to_data(0x66); //write to A
+
+    ppu.write_to_ppu_addr(0x2C);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.write_to_data(0x77); //write to b
+
+    ppu.write_to_ppu_addr(0x28);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.read_data(); //load into buffer
+    assert_eq!(ppu.read_data(), 0x66); //read from a
+
+    ppu.write_to_ppu_addr(0x24);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.read_data(); //load into buffer
+    assert_eq!(ppu.read_data(), 0x77); //read from B
+}
+
+#[test]
+fn test_read_status_resets_latch() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.vram[0x0305] = 0x66;
+
+    ppu.write_to_ppu_addr(0x21);
+    ppu.write_to_ppu_addr(0x23);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.read_data(); //load_into_buffer
+    assert_ne!(ppu.read_data(), 0x66);
+
+    ppu.read_status();
+
+    ppu.write_to_ppu_addr(0x23);
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.read_data(); //load_into_buffer
+    assert_eq!(ppu.read_data(), 0x66);
+}
+
+#[test]
+fn test_ppu_vram_mirroring() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.write_to_ctrl(0);
+    ppu.vram[0x0305] = 0x66;
+
+    ppu.write_to_ppu_addr(0x63); //0x6305 -> 0x2305
+    ppu.write_to_ppu_addr(0x05);
+
+    ppu.read_data(); //load into_buffer
+    assert_eq!(ppu.read_data(), 0x66);
+}
+
+#[test]
+fn test_read_status_resets_vblank() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.status.set_vblank_status(true);
+
+    let status = ppu.read_status();
+
+    assert_eq!(status >> 7, 1);
+    assert_eq!(ppu.status.snapshot() >> 7, 0);
+}
+
+#[test]
+fn test_palette_mirroring() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.write_to_ppu_addr(0x3f);
+    ppu.write_to_ppu_addr(0x10);
+    ppu.write_to_data(0x2c);
+
+    assert_eq!(ppu.palette_table[0], 0x2c);
+
+    ppu.write_to_ppu_addr(0x3f);
+    ppu.write_to_ppu_addr(0x00);
+    assert_eq!(ppu.read_data(), 0x2c);
+}
+
+#[test]
+fn test_oam_read_write() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.write_to_oam_addr(0x10);
+    ppu.write_to_oam_data(0x66);
+    ppu.write_to_oam_data(0x77);
+
+    ppu.write_to_oam_addr(0x10);
+    assert_eq!(ppu.read_oam_data(), 0x66);
+
+    ppu.write_to_oam_addr(0x11);
+    assert_eq!(ppu.read_oam_data(), 0x77);
+}
+
+#[test]
+fn test_oam_dma() {
+    let mut ppu = NesPPU::new_empty_rom();
+
+    let mut data = [0x66; 256];
+    data[0] = 0x77;
+    data[255] = 0x88;
+
+    ppu.write_to_oam_addr(0x10);
+    ppu.write_oam_dma(&data);
+
+    ppu.write_to_oam_addr(0xf); //wrap around
+    assert_eq!(ppu.read_oam_data(), 0x88);
+
+    ppu.write_to_oam_addr(0x10);
+    assert_eq!(ppu.read_oam_data(), 0x77);
+
+    ppu.write_to_oam_addr(0x11);
+    assert_eq!(ppu.read_oam_data(), 0x66);
+}
+
+#[test]
+fn test_vblank_nmi_and_frame_timing() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.write_to_ctrl(0b1000_0000);
+
+    let vblank_scanline = Region::Ntsc.vblank_scanline() as usize;
+    let scanlines = Region::Ntsc.scanlines_per_frame() as usize;
+
+    assert!(!ppu.tick(DOTS_PER_SCANLINE * vblank_scanline - 1));
+    assert!(ppu.poll_nmi_interrupt().is_none());
+
+    assert!(ppu.tick(1));
+    assert!(ppu.status.is_in_vblank());
+    assert_eq!(ppu.poll_nmi_interrupt(), Some(1));
+    assert_eq!(ppu.frame_count, 1);
+
+    ppu.tick(DOTS_PER_SCANLINE * (scanlines - vblank_scanline));
+    assert_eq!(ppu.scanline, 0);
+    assert!(!ppu.status.is_in_vblank());
+}
+
+#[test]
+fn test_render_background_tile() {
+    let mut chr_rom = vec![0; 0x2000];
+    // tile 1: first row solid colour 1, second row solid colour 3
+    chr_rom[16] = 0xFF;
+    chr_rom[17] = 0xFF;
+    chr_rom[16 + 9] = 0xFF;
+    let mut ppu = NesPPU::new(chr_rom, Mirroring::Vertical);
+    ppu.palette_table[0] = 0x0F;
+    ppu.palette_table[1] = 0x16;
+    ppu.palette_table[3] = 0x30;
+    ppu.vram[0] = 1;
+    ppu.write_to_mask(0b0000_1010);
+
+    render::render(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(0, 0), 0x16);
+    assert_eq!(ppu.frame.get_pixel(7, 1), 0x30);
+    assert_eq!(ppu.frame.get_pixel(8, 0), 0x0F);
+    assert_eq!(ppu.frame.get_pixel(0, 2), 0x0F);
+}
+
+#[test]
+fn test_render_sprite_flipped() {
+    let mut chr_rom = vec![0; 0x2000];
+    // tile 2: single pixel in the top left corner
+    chr_rom[32] = 0b1000_0000;
+    let mut ppu = NesPPU::new(chr_rom, Mirroring::Vertical);
+    ppu.palette_table[0x11] = 0x21;
+    ppu.oam_data[0..4].copy_from_slice(&[9, 2, 0b0100_0000, 20]);
+    ppu.write_to_mask(0b0001_0100);
+
+    render::render(&mut ppu);
+
+    assert_eq!(ppu.frame.get_pixel(27, 10), 0x21);
+    assert_eq!(ppu.frame.get_pixel(20, 10), 0x00);
+}
+
+#[test]
+fn test_dendy_vblank_timing() {
+    let mut ppu = NesPPU::new_empty_rom();
+    ppu.set_region(Region::Dendy);
+
+    assert!(!ppu.tick(DOTS_PER_SCANLINE * 290));
+    assert!(ppu.tick(DOTS_PER_SCANLINE));
+    assert_eq!(ppu.scanline, 291);
+
+    ppu.tick(DOTS_PER_SCANLINE * 21);
+    assert_eq!(ppu.scanline, 0);
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+
+use crate::cpu::CPU;
+use crate::disasm;
+
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+const RTI: u8 = 0x40;
+
+// bottom of every collapsed stack, for code outside any subroutine
+const ROOT: &str = "main";
+
+/// Instructions executed and the cycles they took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub instructions: u64,
+    pub cycles: u64,
+}
+
+impl Counts {
+    fn add(&mut self, instructions: u64, cycles: u64) {
+        self.instructions += instructions;
+        self.cycles += cycles;
+    }
+}
+
+/// Totals for one JSR target or interrupt handler. `inclusive` counts
+/// everything run until the matching return, `exclusive` only the code
+/// running while it was the innermost frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Routine {
+    pub calls: u64,
+    /// Entered by an interrupt rather than JSR.
+    pub interrupt: bool,
+    pub exclusive: Counts,
+    pub inclusive: Counts,
+}
+
+/// A call stack entry: the address jumped to, and whether an interrupt
+/// rather than JSR got there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Frame {
+    pub addr: u16,
+    pub interrupt: bool,
+}
+
+impl Frame {
+    fn name(&self) -> String {
+        match self.interrupt {
+            true => format!("nmi_{:04X}", self.addr),
+            false => format!("${:04X}", self.addr),
+        }
+    }
+}
+
+/// Counts instructions and cycles per PC and per subroutine.
+///
+/// Set `CPU::profiler` to `Some(Profiler::new())` and run as usual; the CPU
+/// reports every instruction and interrupt. JSR pushes a frame, RTS pops
+/// it, an NMI pushes an interrupt frame and RTI unwinds up to it.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    pub total: Counts,
+    pub by_pc: HashMap<u16, Counts>,
+    pub routines: HashMap<u16, Routine>,
+    /// Cycles per call stack, innermost frame last.
+    pub stacks: HashMap<Vec<Frame>, u64>,
+    stack: Vec<Frame>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    /// The current call stack, innermost frame last.
+    pub fn stack(&self) -> &[Frame] {
+        &self.stack
+    }
+
+    /// Called by the CPU after executing the instruction at `pc`, with the
+    /// PC it moved on to.
+    pub fn record(&mut self, pc: u16, opcode: u8, cycles: u64, next_pc: u16) {
+        self.by_pc.entry(pc).or_default().add(1, cycles);
+        self.charge(1, cycles);
+
+        match opcode {
+            JSR => self.enter(next_pc, false),
+            RTS if self.stack.last().is_some_and(|frame| !frame.interrupt) => {
+                self.stack.pop();
+            }
+            RTI => {
+                if let Some(index) = self.stack.iter().rposition(|frame| frame.interrupt) {
+                    self.stack.truncate(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Called by the CPU when an interrupt jumps to `handler`, `cycles`
+    /// being the cost of the interrupt sequence itself.
+    pub fn interrupt(&mut self, handler: u16, cycles: u64) {
+        self.enter(handler, true);
+        self.charge(0, cycles);
+    }
+
+    fn enter(&mut self, addr: u16, interrupt: bool) {
+        let routine = self.routines.entry(addr).or_default();
+        routine.calls += 1;
+        routine.interrupt |= interrupt;
+        self.stack.push(Frame { addr, interrupt });
+    }
+
+    fn charge(&mut self, instructions: u64, cycles: u64) {
+        self.total.add(instructions, cycles);
+
+        match self.stacks.get_mut(self.stack.as_slice()) {
+            Some(total) => *total += cycles,
+            None => {
+                self.stacks.insert(self.stack.clone(), cycles);
+            }
+        }
+
+        // recursive routines are only charged once per instruction
+        for (i, frame) in self.stack.iter().enumerate() {
+            if self.stack[..i].iter().any(|outer| outer.addr == frame.addr) {
+                continue;
+            }
+            let routine = self.routines.entry(frame.addr).or_default();
+            routine.inclusive.add(instructions, cycles);
+            if i == self.stack.len() - 1 {
+                routine.exclusive.add(instructions, cycles);
+            }
+        }
+    }
+
+    /// Routines and then the `limit` hottest instructions, by cycles spent.
+    /// Instructions are disassembled from `cpu`'s memory as it is now.
+    pub fn report(&self, cpu: &CPU, limit: usize) -> String {
+        let percent = |cycles: u64| match self.total.cycles {
+            0 => 0.0,
+            total => cycles as f64 * 100.0 / total as f64,
+        };
+
+        let mut lines = vec![format!(
+            "{} instructions, {} cycles",
+            self.total.instructions, self.total.cycles
+        )];
+
+        let mut routines: Vec<_> = self.routines.iter().collect();
+        routines
+            .sort_by_key(|(addr, routine)| (std::cmp::Reverse(routine.inclusive.cycles), **addr));
+        if !routines.is_empty() {
+            lines.push(String::new());
+            lines.push(format!(
+                "{:<10} {:>8} {:>18} {:>18}",
+                "routine", "calls", "self cycles", "total cycles"
+            ));
+            for (addr, routine) in routines.into_iter().take(limit) {
+                let frame = Frame {
+                    addr: *addr,
+                    interrupt: routine.interrupt,
+                };
+                lines.push(format!(
+                    "{:<10} {:>8} {:>10} {:>6.2}% {:>10} {:>6.2}%",
+                    frame.name(),
+                    routine.calls,
+                    routine.exclusive.cycles,
+                    percent(routine.exclusive.cycles),
+                    routine.inclusive.cycles,
+                    percent(routine.inclusive.cycles)
+                ));
+            }
+        }
+
+        let mut hot: Vec<_> = self.by_pc.iter().collect();
+        hot.sort_by_key(|(pc, counts)| (std::cmp::Reverse(counts.cycles), **pc));
+        if !hot.is_empty() {
+            lines.push(String::new());
+            lines.push(format!(
+                "{:<4} {:>12} {:>18}  {}",
+                "pc", "instructions", "cycles", "instruction"
+            ));
+            for (pc, counts) in hot.into_iter().take(limit) {
+                let bytes: Vec<u8> = (0..3).map(|i| cpu.mem_peek(pc.wrapping_add(i))).collect();
+                lines.push(format!(
+                    "{:04X} {:>12} {:>10} {:>6.2}%  {}",
+                    pc,
+                    counts.instructions,
+                    counts.cycles,
+                    percent(counts.cycles),
+                    disasm::decode(&bytes, *pc).text()
+                ));
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    /// Cycles per call stack in the collapsed format read by flamegraph.pl
+    /// and inferno, one `main;$8010;$8123 1234` line per stack.
+    pub fn write_collapsed<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .filter(|(_, cycles)| **cycles > 0)
+            .map(|(stack, cycles)| {
+                let mut names = vec![ROOT.to_string()];
+                names.extend(stack.iter().map(Frame::name));
+                format!("{} {}", names.join(";"), cycles)
+            })
+            .collect();
+        lines.sort();
+
+        for line in lines {
+            writeln!(out, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,121 @@
+use super::*;
+
+fn profiled_run() -> CPU {
+    let program = crate::assemble!(
+        "       LDX #0",
+        "       JSR work",
+        "work:  INX",
+        "       CPX #3",
+        "       BNE work",
+        "       BRK",
+    );
+    let mut cpu = CPU::new();
+    cpu.load_at(0x8000, &program).unwrap();
+    cpu.program_counter = 0x8000;
+    cpu.profiler = Some(Profiler::new());
+    cpu.run();
+    cpu
+}
+
+#[test]
+fn test_counts_per_pc_and_routine() {
+    let cpu = profiled_run();
+    let profiler = cpu.profiler.as_ref().unwrap();
+
+    assert_eq!(profiler.total.instructions, 12);
+    assert_eq!(profiler.total.cycles, cpu.cycles);
+    assert_eq!(profiler.by_pc[&0x8005].instructions, 3);
+    assert_eq!(profiler.by_pc[&0x8000].cycles, 2);
+
+    let work = profiler.routines[&0x8005];
+    assert_eq!(work.calls, 1);
+    assert_eq!(work.inclusive.instructions, 10);
+    assert_eq!(work.exclusive, work.inclusive);
+
+    let outside = profiler.by_pc[&0x8000].cycles + profiler.by_pc[&0x8002].cycles;
+    assert_eq!(profiler.stacks[&vec![]], outside);
+    assert_eq!(profiler.stacks.values().sum::<u64>(), cpu.cycles);
+    assert_eq!(
+        profiler.stack(),
+        &[Frame {
+            addr: 0x8005,
+            interrupt: false
+        }]
+    );
+}
+
+#[test]
+fn test_returns_and_interrupts() {
+    let mut profiler = Profiler::new();
+
+    profiler.record(0x8000, JSR, 6, 0x9000);
+    profiler.record(0x9000, JSR, 6, 0x9100);
+    profiler.record(0x9100, 0xEA, 2, 0x9101);
+    profiler.interrupt(0xA000, 7);
+    profiler.record(0xA000, JSR, 6, 0xA100);
+    profiler.record(0xA100, 0xEA, 2, 0xA101);
+    // RTI unwinds the handler even with its subroutine still on the stack
+    profiler.record(0xA101, RTI, 6, 0x9101);
+    assert_eq!(profiler.stack().len(), 2);
+
+    profiler.record(0x9101, RTS, 6, 0x9003);
+    profiler.record(0x9003, RTS, 6, 0x8003);
+    assert!(profiler.stack().is_empty());
+    // unbalanced returns leave the stack alone
+    profiler.record(0x8003, RTS, 6, 0x8004);
+    assert!(profiler.stack().is_empty());
+
+    let outer = profiler.routines[&0x9000];
+    assert_eq!(outer.calls, 1);
+    assert_eq!(outer.exclusive.cycles, 6 + 6);
+    assert_eq!(outer.inclusive.cycles, 6 + 2 + 7 + 6 + 2 + 6 + 6 + 6);
+
+    let handler = profiler.routines[&0xA000];
+    assert!(handler.interrupt);
+    assert_eq!(handler.exclusive.cycles, 7 + 6);
+    assert_eq!(handler.inclusive.instructions, 3);
+}
+
+#[test]
+fn test_recursion_is_charged_once() {
+    let mut profiler = Profiler::new();
+
+    profiler.record(0x8000, JSR, 6, 0x9000);
+    profiler.record(0x9000, JSR, 6, 0x9000);
+    profiler.record(0x9000, 0xEA, 2, 0x9001);
+
+    let routine = profiler.routines[&0x9000];
+    assert_eq!(routine.calls, 2);
+    assert_eq!(routine.inclusive.cycles, 6 + 2);
+    assert_eq!(profiler.total.cycles, 6 + 6 + 2);
+}
+
+#[test]
+fn test_write_collapsed() {
+    let mut profiler = Profiler::new();
+    profiler.record(0x8000, JSR, 6, 0x9000);
+    profiler.record(0x9000, 0xEA, 2, 0x9001);
+    profiler.interrupt(0xA000, 7);
+    profiler.record(0xA000, 0xEA, 2, 0xA001);
+
+    let mut out = Vec::new();
+    profiler.write_collapsed(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "main 6\nmain;$9000 2\nmain;$9000;nmi_A000 9\n"
+    );
+}
+
+#[test]
+fn test_report() {
+    let cpu = profiled_run();
+    let report = cpu.profiler.as_ref().unwrap().report(&cpu, 2);
+    let lines: Vec<&str> = report.lines().collect();
+
+    assert_eq!(lines[0], format!("12 instructions, {} cycles", cpu.cycles));
+    assert!(lines[3].starts_with("$8005             1"), "{}", lines[3]);
+    // the taken branches cost a cycle more than INX and CPX
+    assert!(lines[6].starts_with("8008            3"), "{}", lines[6]);
+    assert!(lines[6].ends_with("BNE $8005"), "{}", lines[6]);
+    assert_eq!(lines.len(), 8);
+}
@@ -0,0 +1,263 @@
+pub mod raw;
+
+use std::io;
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use crate::cpu::CPU;
+
+use raw::RawMode;
+
+/// Colours of the easy6502 display, indexed by the low nibble of a byte.
+pub const PALETTE: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00), // black
+    (0xFF, 0xFF, 0xFF), // white
+    (0x88, 0x00, 0x00), // red
+    (0xAA, 0xFF, 0xEE), // cyan
+    (0xCC, 0x44, 0xCC), // purple
+    (0x00, 0xCC, 0x55), // green
+    (0x00, 0x00, 0xAA), // blue
+    (0xEE, 0xEE, 0x77), // yellow
+    (0xDD, 0x88, 0x55), // orange
+    (0x66, 0x44, 0x00), // brown
+    (0xFF, 0x77, 0x77), // light red
+    (0x33, 0x33, 0x33), // dark grey
+    (0x77, 0x77, 0x77), // grey
+    (0xAA, 0xFF, 0x66), // light green
+    (0x00, 0x88, 0xFF), // light blue
+    (0xBB, 0xBB, 0xBB), // light grey
+];
+
+const CTRL_C: u8 = 0x03;
+const FRAME: Duration = Duration::from_micros(16_667);
+
+/// A screen of one byte per pixel in CPU memory, row after row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryDisplay {
+    pub base: u16,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl MemoryDisplay {
+    /// The 32x32 screen at $0200-$05FF of easy6502 and its snake game.
+    pub fn easy6502() -> Self {
+        MemoryDisplay {
+            base: 0x0200,
+            width: 32,
+            height: 32,
+        }
+    }
+
+    pub fn pixels(&self, cpu: &CPU) -> Vec<u8> {
+        (0..self.width * self.height)
+            .map(|i| cpu.mem_peek(self.base.wrapping_add(i as u16)))
+            .collect()
+    }
+
+    /// ANSI text drawing `pixels` from the top left corner of the terminal.
+    /// Each character cell is an upper half block with the top pixel as
+    /// foreground and the one below as background, which keeps the pixels
+    /// about square.
+    pub fn render(&self, pixels: &[u8]) -> String {
+        let colour = |x: usize, y: usize| PALETTE[(pixels[y * self.width + x] & 0x0F) as usize];
+
+        let mut text = String::from("\x1b[H");
+        for y in (0..self.height).step_by(2) {
+            let mut current = None;
+            for x in 0..self.width {
+                let top = colour(x, y);
+                let bottom = (y + 1 < self.height).then(|| colour(x, y + 1));
+                if current != Some((top, bottom)) {
+                    let (r, g, b) = top;
+                    text.push_str(&format!("\x1b[38;2;{};{};{}m", r, g, b));
+                    match bottom {
+                        Some((r, g, b)) => text.push_str(&format!("\x1b[48;2;{};{};{}m", r, g, b)),
+                        None => text.push_str("\x1b[49m"),
+                    }
+                    current = Some((top, bottom));
+                }
+                text.push('▀');
+            }
+            text.push_str("\x1b[0m\n");
+        }
+        text
+    }
+}
+
+/// What a burst of terminal input means to `TerminalHost`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// ASCII code to hand to the program.
+    Press(u8),
+    Quit,
+}
+
+/// Splits raw terminal input into keys. Arrow keys become w, a, s and d,
+/// which is what the easy6502 games read.
+pub fn decode_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let key = match &bytes[i..] {
+            [0x1B, b'[', arrow @ b'A'..=b'D', ..] => {
+                i += 2;
+                Key::Press(b"wsda"[(arrow - b'A') as usize])
+            }
+            [CTRL_C, ..] => Key::Quit,
+            [byte, ..] => Key::Press(*byte),
+            [] => unreachable!(),
+        };
+        keys.push(key);
+        i += 1;
+    }
+    keys
+}
+
+/// Runs a program on a bare `CPU` in the terminal: draws its memory mapped
+/// display with 24-bit colour and writes keypresses into memory, for
+/// machines without a window system, e.g. over SSH.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalHost {
+    pub display: MemoryDisplay,
+    /// Where the ASCII code of the last key pressed goes.
+    pub input: u16,
+    /// Gets a new random byte before every instruction.
+    pub random: Option<u16>,
+    /// Instructions run per screen update, there are 60 updates a second.
+    pub speed: u64,
+}
+
+impl Default for TerminalHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TerminalHost {
+    /// The easy6502 conventions: display at $0200, last key at $FF and a
+    /// random number at $FE.
+    pub fn new() -> Self {
+        TerminalHost {
+            display: MemoryDisplay::easy6502(),
+            input: 0x00FF,
+            random: Some(0x00FE),
+            speed: 1000,
+        }
+    }
+
+    /// Runs `cpu` until BRK or Ctrl-C. The terminal is switched to raw mode
+    /// and the alternate screen meanwhile, and restored afterwards.
+    pub fn run(&self, cpu: &mut CPU) -> io::Result<()> {
+        let mut raw = RawMode::enable()?;
+        // dropped before `raw`, so the screen is back before cooked mode
+        let _screen = AlternateScreen::enter()?;
+        self.run_raw(cpu, &mut raw, &mut io::stdout())
+    }
+
+    fn run_raw<W: Write>(&self, cpu: &mut CPU, raw: &mut RawMode, out: &mut W) -> io::Result<()> {
+        let mut random = seed();
+        let mut shown = Vec::new();
+        let mut last_key = None;
+
+        loop {
+            let started = Instant::now();
+            for key in decode_keys(&raw.read_available()?) {
+                match key {
+                    Key::Quit => return Ok(()),
+                    Key::Press(byte) => {
+                        cpu.mem_poke(self.input, byte);
+                        last_key = Some(byte);
+                    }
+                }
+            }
+
+            let mut running = true;
+            for _ in 0..self.speed {
+                if let Some(addr) = self.random {
+                    // xorshift64
+                    random ^= random << 13;
+                    random ^= random >> 7;
+                    random ^= random << 17;
+                    cpu.mem_poke(addr, random as u8);
+                }
+                if !cpu.step() {
+                    running = false;
+                    break;
+                }
+            }
+
+            let pixels = self.display.pixels(cpu);
+            if pixels != shown || !running {
+                write!(out, "{}", self.display.render(&pixels))?;
+                write!(out, "\x1b[K{}", status(cpu, running, last_key))?;
+                out.flush()?;
+                shown = pixels;
+            }
+
+            if !running {
+                // leave the last picture up until a key is pressed
+                while raw.read_available()?.is_empty() {
+                    thread::sleep(FRAME);
+                }
+                return Ok(());
+            }
+            if let Some(rest) = FRAME.checked_sub(started.elapsed()) {
+                thread::sleep(rest);
+            }
+        }
+    }
+}
+
+/// The line under the picture: why the program stopped, or the last key.
+fn status(cpu: &CPU, running: bool, last_key: Option<u8>) -> String {
+    match (running, last_key) {
+        (false, _) => match cpu.unimplemented() {
+            Some(unimplemented) => format!("{}, press a key to leave", unimplemented),
+            None => format!(
+                "halted at BRK, ${:04X}, press a key to leave",
+                cpu.program_counter
+            ),
+        },
+        (true, Some(key)) if key.is_ascii_graphic() => {
+            format!("last key '{}', Ctrl-C quits", key as char)
+        }
+        (true, Some(key)) => format!("last key ${:02X}, Ctrl-C quits", key),
+        (true, None) => "no key yet, Ctrl-C quits".to_string(),
+    }
+}
+
+/// Shows the alternate screen with the cursor hidden until dropped, so the
+/// terminal comes back even when the emulation panics.
+struct AlternateScreen;
+
+impl AlternateScreen {
+    fn enter() -> io::Result<Self> {
+        let mut out = io::stdout();
+        write!(out, "\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        out.flush()?;
+        Ok(AlternateScreen)
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        // nothing sensible to do if the terminal is gone
+        let mut out = io::stdout();
+        let _ = write!(out, "\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = out.flush();
+    }
+}
+
+fn seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    nanos | 1
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,69 @@
+use std::io;
+
+/// Puts the terminal on stdin into raw mode until dropped: no line
+/// buffering, no echo, no signals from Ctrl-C, and reads that return at
+/// once with whatever has been typed so far.
+pub struct RawMode {
+    #[cfg(unix)]
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawMode {
+    pub fn enable() -> io::Result<Self> {
+        // SAFETY: termios is plain data and tcgetattr fills it in
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut raw = saved;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        // cfmakeraw also drops output processing, keep "\n" meaning "\r\n"
+        raw.c_oflag |= libc::OPOST;
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { saved })
+    }
+
+    /// Bytes typed since the last call, empty when there are none.
+    pub fn read_available(&mut self) -> io::Result<Vec<u8>> {
+        let mut buffer = [0u8; 64];
+        // SAFETY: the buffer outlives the call and its length is passed along
+        let read = unsafe {
+            libc::read(
+                libc::STDIN_FILENO,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+            )
+        };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(buffer[..read as usize].to_vec())
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved) };
+    }
+}
+
+#[cfg(not(unix))]
+impl RawMode {
+    pub fn enable() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "raw terminal mode needs a Unix terminal",
+        ))
+    }
+
+    pub fn read_available(&mut self) -> io::Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+}
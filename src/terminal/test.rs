@@ -0,0 +1,88 @@
+use super::*;
+
+#[test]
+fn test_render_half_blocks() {
+    let display = MemoryDisplay {
+        base: 0x0200,
+        width: 2,
+        height: 3,
+    };
+    // white over red, then black over black; the odd last row has no
+    // pixel below it
+    let text = display.render(&[0x01, 0x00, 0x02, 0x10, 0x05, 0x0D]);
+
+    assert_eq!(
+        text,
+        "\x1b[H\
+         \x1b[38;2;255;255;255m\x1b[48;2;136;0;0m▀\
+         \x1b[38;2;0;0;0m\x1b[48;2;0;0;0m▀\x1b[0m\n\
+         \x1b[38;2;0;204;85m\x1b[49m▀\
+         \x1b[38;2;170;255;102m\x1b[49m▀\x1b[0m\n"
+    );
+}
+
+#[test]
+fn test_render_repeats_no_colours() {
+    let display = MemoryDisplay::easy6502();
+    let text = display.render(&[0x06; 32 * 32]);
+
+    assert_eq!(text.matches('▀').count(), 32 * 16);
+    // one pair of colour codes per line
+    assert_eq!(text.matches("\x1b[38;2;0;0;170m").count(), 16);
+}
+
+#[test]
+fn test_pixels_from_memory() {
+    let mut cpu = CPU::new();
+    cpu.load_at(0x0200, &[1, 2, 3]).unwrap();
+    cpu.load_at(0x05FF, &[4]).unwrap();
+
+    let pixels = MemoryDisplay::easy6502().pixels(&cpu);
+    assert_eq!(pixels.len(), 1024);
+    assert_eq!(&pixels[..4], &[1, 2, 3, 0]);
+    assert_eq!(pixels[1023], 4);
+}
+
+#[test]
+fn test_decode_keys() {
+    assert_eq!(
+        decode_keys(b"w\x1b[A\x1b[D\x1b[Bx"),
+        vec![
+            Key::Press(b'w'),
+            Key::Press(b'w'),
+            Key::Press(b'a'),
+            Key::Press(b's'),
+            Key::Press(b'x'),
+        ]
+    );
+    assert_eq!(
+        decode_keys(b"\x1b\x03d"),
+        vec![Key::Press(0x1B), Key::Quit, Key::Press(b'd')]
+    );
+    assert!(decode_keys(b"").is_empty());
+}
+
+#[test]
+fn test_status_line() {
+    let mut cpu = CPU::new();
+    assert_eq!(status(&cpu, true, None), "no key yet, Ctrl-C quits");
+    assert_eq!(status(&cpu, true, Some(b'w')), "last key 'w', Ctrl-C quits");
+
+    // INX; BRK
+    cpu.load_at(0x0600, &[0xE8, 0x00]).unwrap();
+    cpu.program_counter = 0x0600;
+    while cpu.step() {}
+    assert_eq!(
+        status(&cpu, false, None),
+        "halted at BRK, $0602, press a key to leave"
+    );
+
+    // PHP
+    cpu.load_at(0x0600, &[0x08]).unwrap();
+    cpu.program_counter = 0x0600;
+    cpu.step();
+    assert_eq!(
+        status(&cpu, false, None),
+        "unimplemented opcode $08 at $0600, press a key to leave"
+    );
+}
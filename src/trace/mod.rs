@@ -0,0 +1,154 @@
+use std::io;
+use std::io::Write;
+
+use crate::cpu::CPU;
+use crate::disasm;
+use crate::helper::AddressingMode;
+use crate::helper::EmmulationHelpers;
+use crate::helper::OpCodeCat;
+use crate::ppu::DOTS_PER_SCANLINE;
+
+// NTSC frame height, only used when there is no PPU to ask
+const SCANLINES_PER_FRAME: u64 = 262;
+
+/// One line of trace in the nestest.log format for the instruction at the
+/// program counter, e.g.
+///
+/// ```text
+/// C72A  B1 89     LDA ($89),Y = 0300 @ 0300 = 89         A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+/// ```
+///
+/// Memory is read with `CPU::mem_peek`, tracing does not change the state of
+/// the machine. Without a PPU on the bus its position is derived from the
+/// cycle count.
+pub fn trace(cpu: &CPU) -> String {
+    let pc = cpu.program_counter;
+    let bytes: Vec<u8> = (0..3).map(|i| cpu.mem_peek(pc.wrapping_add(i))).collect();
+    let instruction = disasm::decode(&bytes, pc);
+
+    let hex: Vec<String> = instruction
+        .bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect();
+    let operand = match instruction.bytes.len() {
+        1 => instruction.operand.clone(),
+        _ => describe_operand(cpu, &instruction),
+    };
+    let asm = format!(
+        "{:04X}  {:8} {:>4} {}",
+        pc,
+        hex.join(" "),
+        instruction.mnemonic,
+        operand
+    );
+
+    let (scanline, dot) = match cpu.bus.as_ref() {
+        Some(bus) => (bus.ppu.scanline as u64, bus.ppu.dot as u64),
+        None => {
+            let dots = cpu.cycles * 3;
+            let line = dots / DOTS_PER_SCANLINE as u64;
+            (line % SCANLINES_PER_FRAME, dots % DOTS_PER_SCANLINE as u64)
+        }
+    };
+
+    format!(
+        "{:47} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        asm.trim_end(),
+        cpu.register_a,
+        cpu.register_x,
+        cpu.register_y,
+        cpu.status.bits(),
+        cpu.stack_pointer,
+        scanline,
+        dot,
+        cpu.cycles
+    )
+}
+
+/// Operand followed by the effective address and the value found there.
+fn describe_operand(cpu: &CPU, instruction: &disasm::Instruction) -> String {
+    let opcode = EmmulationHelpers::get_op_code_struct(instruction.bytes[0]);
+    let operand = &instruction.operand;
+    let byte = instruction.bytes[1];
+    let word = || u16::from_le_bytes([instruction.bytes[1], instruction.bytes[2]]);
+    let peek_u16_zero_page = |ptr: u8| {
+        u16::from_le_bytes([
+            cpu.mem_peek(ptr as u16),
+            cpu.mem_peek(ptr.wrapping_add(1) as u16),
+        ])
+    };
+
+    match opcode.mode {
+        AddressingMode::ZeroPage => {
+            format!("{} = {:02X}", operand, cpu.mem_peek(byte as u16))
+        }
+        AddressingMode::ZeroPage_X | AddressingMode::ZeroPage_Y => {
+            let index = match opcode.mode {
+                AddressingMode::ZeroPage_X => cpu.register_x,
+                _ => cpu.register_y,
+            };
+            let addr = byte.wrapping_add(index) as u16;
+            format!("{} @ {:02X} = {:02X}", operand, addr, cpu.mem_peek(addr))
+        }
+        AddressingMode::Absolute => match opcode.match_code {
+            OpCodeCat::JMP_ABS | OpCodeCat::JSR => operand.clone(),
+            _ => format!("{} = {:02X}", operand, cpu.mem_peek(word())),
+        },
+        AddressingMode::Absolute_X | AddressingMode::Absolute_Y => {
+            let index = match opcode.mode {
+                AddressingMode::Absolute_X => cpu.register_x,
+                _ => cpu.register_y,
+            };
+            let addr = word().wrapping_add(index as u16);
+            format!("{} @ {:04X} = {:02X}", operand, addr, cpu.mem_peek(addr))
+        }
+        AddressingMode::Indirect => {
+            // JMP ($xxFF) takes the high byte from $xx00
+            let ptr = word();
+            let hi_ptr = (ptr & 0xFF00) | (ptr.wrapping_add(1) & 0x00FF);
+            let target = u16::from_le_bytes([cpu.mem_peek(ptr), cpu.mem_peek(hi_ptr)]);
+            format!("{} = {:04X}", operand, target)
+        }
+        AddressingMode::Indirect_X => {
+            let ptr = byte.wrapping_add(cpu.register_x);
+            let addr = peek_u16_zero_page(ptr);
+            format!(
+                "{} @ {:02X} = {:04X} = {:02X}",
+                operand,
+                ptr,
+                addr,
+                cpu.mem_peek(addr)
+            )
+        }
+        AddressingMode::Indirect_Y => {
+            let base = peek_u16_zero_page(byte);
+            let addr = base.wrapping_add(cpu.register_y as u16);
+            format!(
+                "{} = {:04X} @ {:04X} = {:02X}",
+                operand,
+                base,
+                addr,
+                cpu.mem_peek(addr)
+            )
+        }
+        _ => operand.clone(),
+    }
+}
+
+/// Runs `cpu` until BRK like `CPU::run`, writing a `trace` line to `out`
+/// before every instruction. Stops at the first write error.
+pub fn run_traced<W: Write>(cpu: &mut CPU, out: &mut W) -> io::Result<()> {
+    let mut result = Ok(());
+    cpu.run_with_callback(|cpu| {
+        if result.is_ok() {
+            result = writeln!(out, "{}", trace(cpu));
+        }
+    });
+    result
+}
+
+#[cfg(test)]
+mod nestest;
+#[cfg(test)]
+mod test;
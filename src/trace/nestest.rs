@@ -0,0 +1,224 @@
+//! Golden trace for kevtris' nestest.nes.
+//!
+//! Put `nestest.nes` and `nestest.log` from http://www.qmtpro.com/~nes/misc/
+//! into `src/trace/golden/` and run `cargo test nestest`. Without them the
+//! conformance test says so and checks nothing. The CPU does not implement
+//! every official opcode yet, so the trace is compared up to the first
+//! opcode it stops at. The comparison itself is covered with synthetic logs.
+
+use std::fs;
+use std::path::PathBuf;
+
+use super::*;
+use crate::cartridge::test_rom::test_rom;
+use crate::cpu::Unimplemented;
+use crate::nes::Nes;
+
+/// Lines of the golden log shown before a divergence.
+const CONTEXT: usize = 5;
+
+/// How much of a golden log the console reproduced.
+#[derive(Debug, PartialEq, Eq)]
+struct Matched {
+    lines: usize,
+    /// The opcode the comparison stopped at before the end of the log.
+    unimplemented: Option<Unimplemented>,
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "src", "trace", "golden", name]
+        .iter()
+        .collect()
+}
+
+/// Console with the cartridge `raw` inserted and execution starting at
+/// `entry`. The log counts the 7 cycles of the reset sequence on the PPU
+/// too, which `Nes::power_on` does not clock.
+fn nes_at(raw: &[u8], entry: u16) -> Nes {
+    let mut nes = Nes::new();
+    nes.load_rom(raw).unwrap();
+    nes.cpu.program_counter = entry;
+    let dots = nes.cpu.cycles as usize * 3;
+    nes.cpu.bus.as_mut().unwrap().ppu.tick(dots);
+    nes
+}
+
+/// Steps `nes` once per line of `expected`, comparing the whole trace line,
+/// PPU position included, before each instruction. Reports the first line
+/// that differs with the lines leading up to it. An unimplemented opcode
+/// ends the comparison early.
+fn compare_trace(nes: &mut Nes, expected: &str) -> Result<Matched, String> {
+    let lines: Vec<&str> = expected.lines().filter(|l| !l.is_empty()).collect();
+
+    for (i, line) in lines.iter().enumerate() {
+        let actual = trace(&nes.cpu);
+        if actual.trim_end() != line.trim_end() {
+            let context: Vec<String> = lines[i.saturating_sub(CONTEXT)..i]
+                .iter()
+                .map(|l| format!("           {}", l))
+                .collect();
+            return Err(format!(
+                "trace diverges at line {}:\n{}\n  expected {}\n  actual   {}",
+                i + 1,
+                context.join("\n"),
+                line,
+                actual
+            ));
+        }
+
+        nes.step_instruction();
+        if let Some(unimplemented) = nes.unimplemented() {
+            return Ok(Matched {
+                lines: i + 1,
+                unimplemented: Some(unimplemented),
+            });
+        }
+        if nes.is_halted() && i + 1 < lines.len() {
+            return Err(format!("CPU halted at line {}:\n  {}", i + 1, line));
+        }
+    }
+
+    Ok(Matched {
+        lines: lines.len(),
+        unimplemented: None,
+    })
+}
+
+/// Reads a fixture, `None` when it is not there.
+fn golden_file(name: &str) -> Option<Vec<u8>> {
+    let path = golden_path(name);
+    match fs::read(&path) {
+        Ok(data) => Some(data),
+        Err(e) => {
+            eprintln!("skipping nestest, cannot read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[test]
+fn test_nestest_golden_trace() {
+    let (Some(rom), Some(log)) = (golden_file("nestest.nes"), golden_file("nestest.log")) else {
+        return;
+    };
+    let log = String::from_utf8(log).unwrap();
+
+    // automation mode, the official opcodes are tested from $C000 on
+    let mut nes = nes_at(&rom, 0xC000);
+    let matched = compare_trace(&mut nes, &log).unwrap_or_else(|report| panic!("{}", report));
+
+    assert!(matched.lines > 0);
+    if let Some(unimplemented) = matched.unimplemented {
+        eprintln!(
+            "nestest matches for {} lines, up to the {}",
+            matched.lines, unimplemented
+        );
+    }
+}
+
+fn sample_rom(program: &[u8]) -> Vec<u8> {
+    test_rom(program, 0x8000, 0x8000, false)
+}
+
+fn sample_program() -> Vec<u8> {
+    crate::assemble!(
+        "loop:  INX",
+        "       CPX #3",
+        "       BNE loop",
+        "       BRK"
+    )
+}
+
+/// Trace lines of `program` up to the instruction the CPU stops at.
+fn sample_log(program: &[u8]) -> String {
+    let mut nes = nes_at(&sample_rom(program), 0x8000);
+    let mut log = String::new();
+    while !nes.is_halted() {
+        log.push_str(&trace(&nes.cpu));
+        log.push('\n');
+        nes.step_instruction();
+    }
+    log
+}
+
+#[test]
+fn test_compare_trace_matches() {
+    let log = sample_log(&sample_program());
+    assert!(log.starts_with(
+        "8000  E8        INX                             A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7\n"
+    ));
+
+    let mut nes = nes_at(&sample_rom(&sample_program()), 0x8000);
+    assert_eq!(
+        compare_trace(&mut nes, &log),
+        Ok(Matched {
+            lines: 10,
+            unimplemented: None
+        })
+    );
+}
+
+#[test]
+fn test_compare_trace_checks_ppu_column() {
+    let log = sample_log(&sample_program()).replacen("PPU:  0, 27", "PPU:  0, 28", 1);
+
+    let mut nes = nes_at(&sample_rom(&sample_program()), 0x8000);
+    let report = compare_trace(&mut nes, &log).unwrap_err();
+
+    assert!(
+        report.starts_with("trace diverges at line 2:"),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn test_compare_trace_reports_first_divergence() {
+    let log = sample_log(&sample_program()).replacen("X:02", "X:07", 1);
+
+    let mut nes = nes_at(&sample_rom(&sample_program()), 0x8000);
+    let report = compare_trace(&mut nes, &log).unwrap_err();
+
+    assert!(
+        report.starts_with("trace diverges at line 5:"),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains("expected 8001  E0 03     CPX #$03"),
+        "{}",
+        report
+    );
+    assert_eq!(report.lines().count(), 7, "{}", report);
+}
+
+#[test]
+fn test_compare_trace_reports_halt() {
+    let mut log = sample_log(&sample_program());
+    log.push_str(&log.clone());
+
+    let mut nes = nes_at(&sample_rom(&sample_program()), 0x8000);
+    let report = compare_trace(&mut nes, &log).unwrap_err();
+
+    assert!(report.starts_with("CPU halted at line 10:"), "{}", report);
+}
+
+#[test]
+fn test_compare_trace_stops_at_unimplemented_opcode() {
+    // INX; PHP, with the log going on after PHP
+    let program = [0xE8, 0x08];
+    let mut log = sample_log(&program);
+    log.push_str(&log.clone());
+
+    let mut nes = nes_at(&sample_rom(&program), 0x8000);
+    assert_eq!(
+        compare_trace(&mut nes, &log),
+        Ok(Matched {
+            lines: 2,
+            unimplemented: Some(Unimplemented {
+                opcode: 0x08,
+                pc: 0x8001
+            })
+        })
+    );
+}
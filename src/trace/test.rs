@@ -0,0 +1,93 @@
+use super::*;
+use crate::joypad::JoypadButton;
+
+fn trace_program(program: Vec<u8>) -> Vec<String> {
+    let mut cpu = CPU::new();
+    cpu.load(program);
+    cpu.reset();
+
+    let mut out = Vec::new();
+    run_traced(&mut cpu, &mut out).unwrap();
+    String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_trace_format() {
+    let lines = trace_program(crate::assemble!(
+        "       LDA #$05",
+        "       STA $10",
+        "       LDX #$01",
+        "       LDA $0F,X",
+        "       LDA #$00",
+        "       STA $11",
+        "       LDY #$02",
+        "       LDA ($0E),Y",
+        "       LDA ($0F,X)",
+        "       STA $0300,X",
+        "       JMP next",
+        "next:  LSR A",
+        "       BNE next",
+        "       BRK",
+    ));
+
+    assert_eq!(
+        lines,
+        vec![
+            "8000  A9 05     LDA #$05                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7",
+            "8002  85 10     STA $10 = 00                    A:05 X:00 Y:00 P:24 SP:FD PPU:  0, 27 CYC:9",
+            "8004  A2 01     LDX #$01                        A:05 X:00 Y:00 P:24 SP:FD PPU:  0, 36 CYC:12",
+            "8006  B5 0F     LDA $0F,X @ 10 = 05             A:05 X:01 Y:00 P:24 SP:FD PPU:  0, 42 CYC:14",
+            "8008  A9 00     LDA #$00                        A:05 X:01 Y:00 P:24 SP:FD PPU:  0, 54 CYC:18",
+            "800A  85 11     STA $11 = 00                    A:00 X:01 Y:00 P:26 SP:FD PPU:  0, 60 CYC:20",
+            "800C  A0 02     LDY #$02                        A:00 X:01 Y:00 P:26 SP:FD PPU:  0, 69 CYC:23",
+            "800E  B1 0E     LDA ($0E),Y = 0000 @ 0002 = 00  A:00 X:01 Y:02 P:24 SP:FD PPU:  0, 75 CYC:25",
+            "8010  A1 0F     LDA ($0F,X) @ 10 = 0005 = 00    A:00 X:01 Y:02 P:26 SP:FD PPU:  0, 90 CYC:30",
+            "8012  9D 00 03  STA $0300,X @ 0301 = 00         A:00 X:01 Y:02 P:26 SP:FD PPU:  0,108 CYC:36",
+            "8015  4C 18 80  JMP $8018                       A:00 X:01 Y:02 P:26 SP:FD PPU:  0,123 CYC:41",
+            "8018  4A        LSR A                           A:00 X:01 Y:02 P:26 SP:FD PPU:  0,132 CYC:44",
+            "8019  D0 FD     BNE $8018                       A:00 X:01 Y:02 P:26 SP:FD PPU:  0,138 CYC:46",
+            "801B  00        BRK                             A:00 X:01 Y:02 P:26 SP:FD PPU:  0,144 CYC:48",
+        ]
+    );
+}
+
+#[test]
+fn test_trace_has_no_side_effects() {
+    let mut cpu = CPU::new();
+    cpu.load(crate::assemble!(
+        "LDA #$01",
+        "STA $4016",
+        "LDA #$00",
+        "STA $4016",
+        "LDA $4016",
+        "BRK"
+    ));
+    cpu.reset();
+    cpu.joypad_mut(1)
+        .unwrap()
+        .set_button_pressed_status(JoypadButton::BUTTON_A, true);
+
+    let mut out = Vec::new();
+    run_traced(&mut cpu, &mut out).unwrap();
+
+    // tracing peeked the port before the read, which still sees button A
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("LDA $4016 = 41"), "{}", out);
+    assert_eq!(cpu.register_a, 0x41);
+}
+
+#[test]
+fn test_run_with_callback_counts_instructions() {
+    let mut cpu = CPU::new();
+    cpu.load(crate::assemble!("INX", "INX", "INX", "BRK"));
+    cpu.reset();
+
+    let mut count = 0;
+    cpu.run_with_callback(|_| count += 1);
+
+    assert_eq!(count, 4);
+}